@@ -34,4 +34,25 @@ module wvec_blas
     end subroutine sscal
   end interface
 
+  ! BLAS Level 2/3 interfaces (matrix operations)
+  interface
+    !> Matrix-vector product: y = alpha * op(A) * x + beta * y
+    subroutine sgemv(trans, m, n, alpha, a, lda, x, incx, beta, y, incy)
+      character, intent(in) :: trans
+      integer, intent(in) :: m, n, lda, incx, incy
+      real, intent(in) :: alpha, beta
+      real, intent(in) :: a(lda, *), x(*)
+      real, intent(inout) :: y(*)
+    end subroutine sgemv
+
+    !> Matrix-matrix product: C = alpha * op(A) * op(B) + beta * C
+    subroutine sgemm(transa, transb, m, n, k, alpha, a, lda, b, ldb, beta, c, ldc)
+      character, intent(in) :: transa, transb
+      integer, intent(in) :: m, n, k, lda, ldb, ldc
+      real, intent(in) :: alpha, beta
+      real, intent(in) :: a(lda, *), b(ldb, *)
+      real, intent(inout) :: c(ldc, *)
+    end subroutine sgemm
+  end interface
+
 end module wvec_blas
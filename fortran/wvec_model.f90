@@ -2,6 +2,7 @@
 module wvec_model
   use, intrinsic :: iso_c_binding
   use wvec_types
+  use wvec_rng
   implicit none
 
   ! Module-level model (singleton)
@@ -11,8 +12,17 @@ module wvec_model
   real(c_float), allocatable, save :: g_w_out(:, :)  ! Output embeddings (dim, vocab_size)
   logical, save :: g_initialized = .false.
 
-  !> Shutdown flag for graceful termination
-  logical, save :: g_shutdown_requested = .false.
+  !> L2-normalized copy of g_w_in for nearest-neighbour search.
+  !> Built on first use by wvec_search_topk, dropped with the model.
+  real(c_float), allocatable, save :: g_w_norm(:, :)  ! (dim, vocab_size)
+
+  !> Seed for the random initial embeddings (set with wvec_model_seed)
+  integer(c_int64_t), save :: g_init_seed = 42
+
+  !> Shutdown flag for graceful termination.
+  !> Set asynchronously from the Rust signal handler, so it must be volatile
+  !> to keep the training loop from caching it in a register.
+  logical, save, volatile :: g_shutdown_requested = .false.
 
 contains
 
@@ -22,7 +32,7 @@ contains
     integer(c_int), intent(in), value :: vocab_size, dim
     integer(c_int) :: status
     integer :: i, j, alloc_stat
-    real :: rand_val
+    integer(c_int64_t) :: rng_state
 
     ! Free existing model if any
     call wvec_model_free()
@@ -51,12 +61,11 @@ contains
     end if
 
     ! Initialize with small random values [-0.5/dim, 0.5/dim]
+    rng_state = rng_init(g_init_seed, 0)
     do j = 1, vocab_size
       do i = 1, dim
-        call random_number(rand_val)
-        g_w_in(i, j) = (rand_val - 0.5) / dim
-        call random_number(rand_val)
-        g_w_out(i, j) = (rand_val - 0.5) / dim  ! Also randomize output embeddings
+        g_w_in(i, j) = (rng_uniform(rng_state) - 0.5) / dim
+        g_w_out(i, j) = (rng_uniform(rng_state) - 0.5) / dim  ! Also randomize output embeddings
       end do
     end do
 
@@ -64,10 +73,17 @@ contains
     status = 0  ! SUCCESS
   end function wvec_model_init
 
+  !> Set the seed used by the next wvec_model_init
+  subroutine wvec_model_seed(seed) bind(C, name="wvec_model_seed")
+    integer(c_int64_t), intent(in), value :: seed
+    g_init_seed = seed
+  end subroutine wvec_model_seed
+
   !> Free model memory
   subroutine wvec_model_free() bind(C, name="wvec_model_free")
     if (allocated(g_w_in)) deallocate (g_w_in)
     if (allocated(g_w_out)) deallocate (g_w_out)
+    if (allocated(g_w_norm)) deallocate (g_w_norm)
     g_vocab_size = 0
     g_dim = 0
     g_initialized = .false.
@@ -112,6 +128,30 @@ contains
     status = 0
   end function wvec_get_embedding
 
+  !> Copy output embedding for word_id to output buffer (0-indexed)
+  !> With hierarchical softmax the columns belong to tree nodes, not words.
+  function wvec_get_output_embedding(word_id, out_vec, out_len) result(status) &
+    bind(C, name="wvec_get_output_embedding")
+    integer(c_int), intent(in), value :: word_id, out_len
+    real(c_float), intent(out) :: out_vec(out_len)
+    integer(c_int) :: status
+    integer :: fortran_id
+
+    if (.not. g_initialized) then
+      status = -1
+      return
+    end if
+
+    fortran_id = word_id + 1
+    if (fortran_id < 1 .or. fortran_id > g_vocab_size .or. out_len /= g_dim) then
+      status = -2
+      return
+    end if
+
+    out_vec(:) = g_w_out(:, fortran_id)
+    status = 0
+  end function wvec_get_output_embedding
+
   !> Request graceful shutdown (called from signal handler)
   subroutine wvec_shutdown_request() bind(C, name="wvec_shutdown_request")
     g_shutdown_requested = .true.
@@ -0,0 +1,61 @@
+! wvec_rng.f90 - Per-thread pseudo-random numbers for training
+module wvec_rng
+  use, intrinsic :: iso_c_binding
+  implicit none
+
+  !> Fallback state; xorshift has an all-zero fixed point
+  integer(c_int64_t), parameter :: RNG_NONZERO_STATE = 88172645463325252_c_int64_t
+
+contains
+
+  !> Derive a generator state from a seed and a salt (e.g. thread number)
+  !>
+  !> The salt is spread over the high bits so that threads sharing one seed
+  !> start from different states, then a few rounds decorrelate them.
+  function rng_init(seed, salt) result(state)
+    integer(c_int64_t), intent(in) :: seed
+    integer, intent(in) :: salt
+    integer(c_int64_t) :: state
+    integer :: i
+
+    ! 2654435761 (Knuth's multiplicative constant) times a small salt fits in 63 bits
+    state = ieor(seed, int(salt + 1, c_int64_t) * 2654435761_c_int64_t)
+    if (state == 0) state = RNG_NONZERO_STATE
+
+    do i = 1, 8
+      call rng_next(state)
+    end do
+  end function rng_init
+
+  !> Advance a xorshift64 state (Marsaglia, 2003: shifts 13, 7, 17)
+  !> ISHFT is a logical shift, so this matches the unsigned C version bit for bit.
+  subroutine rng_next(state)
+    integer(c_int64_t), intent(inout) :: state
+
+    state = ieor(state, ishft(state, 13))
+    state = ieor(state, ishft(state, -7))
+    state = ieor(state, ishft(state, 17))
+  end subroutine rng_next
+
+  !> Uniform random integer in [0, n)
+  function rng_below(state, n) result(r)
+    integer(c_int64_t), intent(inout) :: state
+    integer, intent(in) :: n
+    integer :: r
+
+    call rng_next(state)
+    ! Drop the sign bit so modulo sees a non-negative value
+    r = int(modulo(ishft(state, -1), int(n, c_int64_t)))
+  end function rng_below
+
+  !> Uniform random real in [0, 1)
+  function rng_uniform(state) result(u)
+    integer(c_int64_t), intent(inout) :: state
+    real(c_float) :: u
+
+    call rng_next(state)
+    ! Top 24 bits fill the c_float mantissa exactly
+    u = real(ishft(state, -40), c_float) / 16777216.0_c_float
+  end function rng_uniform
+
+end module wvec_rng
@@ -0,0 +1,211 @@
+! wvec_search.f90 - Nearest-neighbour search over normalized embeddings
+module wvec_search
+  use, intrinsic :: iso_c_binding
+  use wvec_blas
+  use wvec_model
+  implicit none
+
+  !> Floats in the score buffer (64 MB); a batch scores as many queries
+  !> per sgemm call as fit, at least one
+  integer, parameter :: SCORE_BUDGET = 16 * 1024 * 1024
+
+contains
+
+  !> Build g_w_norm: every column of g_w_in scaled to unit length
+  !> Zero vectors stay zero, so they score 0 against every query.
+  !> Returns 0 on success, negative on error
+  function build_normalized() result(status)
+    integer(c_int) :: status
+    real(c_float) :: nrm
+    integer :: j, one, alloc_stat
+
+    allocate (g_w_norm(g_dim, g_vocab_size), stat=alloc_stat)
+    if (alloc_stat /= 0) then
+      status = -3  ! ERR_OUT_OF_MEMORY
+      return
+    end if
+
+    one = 1
+    !$omp parallel do private(nrm)
+    do j = 1, g_vocab_size
+      g_w_norm(:, j) = g_w_in(:, j)
+      nrm = snrm2(g_dim, g_w_norm(1, j), one)
+      if (nrm > 0.0) call sscal(g_dim, 1.0 / nrm, g_w_norm(1, j), one)
+    end do
+    !$omp end parallel do
+
+    status = 0
+  end function build_normalized
+
+  !> Top-k cosine neighbours of a batch of query vectors
+  !>
+  !> queries:    (dim, n_queries) query vectors, need not be normalized
+  !> exclude:    (n_exclude, n_queries) 0-indexed IDs to skip per query;
+  !>             negative entries are ignored (padding)
+  !> out_ids:    (k, n_queries) 0-indexed IDs, best first; -1 past the
+  !>             last candidate
+  !> out_scores: (k, n_queries) cosine similarities matching out_ids
+  !>
+  !> Scores the whole vocabulary with one sgemv (single query) or sgemm
+  !> per batch of queries, then keeps the best k with a bounded min-heap.
+  !> Returns 0 on success, negative on error
+  function wvec_search_topk(queries, dim, n_queries, k, exclude, n_exclude, &
+                            out_ids, out_scores) result(status) bind(C, name="wvec_search_topk")
+    integer(c_int), intent(in), value :: dim, n_queries, k, n_exclude
+    real(c_float), intent(in) :: queries(dim, n_queries)
+    integer(c_int), intent(in) :: exclude(n_exclude, n_queries)
+    integer(c_int), intent(out) :: out_ids(k, n_queries)
+    real(c_float), intent(out) :: out_scores(k, n_queries)
+    integer(c_int) :: status
+
+    real(c_float), allocatable :: q(:, :), scores(:, :)
+    real(c_float) :: nrm
+    integer :: first, batch, nb, j, one, alloc_stat
+
+    if (.not. g_initialized) then
+      status = -1
+      return
+    end if
+
+    if (dim /= g_dim .or. n_queries < 0 .or. k <= 0 .or. n_exclude < 0) then
+      status = -2
+      return
+    end if
+
+    ! Normalize the vocabulary once per loaded model
+    if (.not. allocated(g_w_norm)) then
+      status = build_normalized()
+      if (status /= 0) return
+    end if
+
+    if (n_queries == 0) then
+      status = 0
+      return
+    end if
+
+    batch = max(1, min(n_queries, SCORE_BUDGET / g_vocab_size))
+    allocate (q(dim, batch), scores(g_vocab_size, batch), stat=alloc_stat)
+    if (alloc_stat /= 0) then
+      status = -3  ! ERR_OUT_OF_MEMORY
+      return
+    end if
+
+    one = 1
+    do first = 1, n_queries, batch
+      nb = min(batch, n_queries - first + 1)
+
+      ! Unit-length queries make the dot products cosine similarities
+      do j = 1, nb
+        q(:, j) = queries(:, first + j - 1)
+        nrm = snrm2(dim, q(1, j), one)
+        if (nrm > 0.0) call sscal(dim, 1.0 / nrm, q(1, j), one)
+      end do
+
+      ! scores = W_normᵀ · Q
+      if (nb == 1) then
+        call sgemv('T', dim, g_vocab_size, 1.0, g_w_norm, dim, q, one, &
+                   0.0, scores, one)
+      else
+        call sgemm('T', 'N', g_vocab_size, nb, dim, 1.0, g_w_norm, dim, &
+                   q, dim, 0.0, scores, g_vocab_size)
+      end if
+
+      !$omp parallel do if (nb > 1)
+      do j = 1, nb
+        call select_topk(scores(:, j), exclude(:, first + j - 1), k, &
+                         out_ids(:, first + j - 1), out_scores(:, first + j - 1))
+      end do
+      !$omp end parallel do
+    end do
+
+    deallocate (q, scores)
+    status = 0
+  end function wvec_search_topk
+
+  !> Keep the k best scores (0-indexed IDs not in exclude), sorted descending
+  subroutine select_topk(scores, exclude, k, ids, top)
+    real(c_float), intent(in) :: scores(:)
+    integer(c_int), intent(in) :: exclude(:)
+    integer(c_int), intent(in) :: k
+    integer(c_int), intent(out) :: ids(k)
+    real(c_float), intent(out) :: top(k)
+    integer :: i, n
+
+    ! Min-heap of the best n <= k so far: top(1) is the worst of them
+    n = 0
+    do i = 1, size(scores)
+      if (any(exclude == i - 1)) cycle
+      if (n < k) then
+        n = n + 1
+        top(n) = scores(i)
+        ids(n) = i - 1
+        call sift_up(top, ids, n)
+      else if (scores(i) > top(1)) then
+        top(1) = scores(i)
+        ids(1) = i - 1
+        call sift_down(top, ids, n)
+      end if
+    end do
+
+    ! Heap sort: moving the minimum to the back leaves the best in front
+    do i = n, 2, -1
+      call swap(top, ids, 1, i)
+      call sift_down(top, ids, i - 1)
+    end do
+
+    ids(n + 1:k) = -1
+    top(n + 1:k) = -huge(1.0_c_float)
+  end subroutine select_topk
+
+  !> Restore the heap after appending at position pos
+  pure subroutine sift_up(top, ids, pos)
+    real(c_float), intent(inout) :: top(:)
+    integer(c_int), intent(inout) :: ids(:)
+    integer, intent(in) :: pos
+    integer :: child, parent
+
+    child = pos
+    do while (child > 1)
+      parent = child / 2
+      if (top(parent) <= top(child)) exit
+      call swap(top, ids, parent, child)
+      child = parent
+    end do
+  end subroutine sift_up
+
+  !> Restore the heap of size n after replacing its root
+  pure subroutine sift_down(top, ids, n)
+    real(c_float), intent(inout) :: top(:)
+    integer(c_int), intent(inout) :: ids(:)
+    integer, intent(in) :: n
+    integer :: child, parent
+
+    parent = 1
+    do
+      child = 2 * parent
+      if (child > n) exit
+      if (child < n) then
+        if (top(child + 1) < top(child)) child = child + 1
+      end if
+      if (top(parent) <= top(child)) exit
+      call swap(top, ids, parent, child)
+      parent = child
+    end do
+  end subroutine sift_down
+
+  pure subroutine swap(top, ids, a, b)
+    real(c_float), intent(inout) :: top(:)
+    integer(c_int), intent(inout) :: ids(:)
+    integer, intent(in) :: a, b
+    real(c_float) :: score
+    integer(c_int) :: id
+
+    score = top(a)
+    top(a) = top(b)
+    top(b) = score
+    id = ids(a)
+    ids(a) = ids(b)
+    ids(b) = id
+  end subroutine swap
+
+end module wvec_search
@@ -3,9 +3,8 @@ module wvec_thermal
   use, intrinsic :: iso_c_binding
   implicit none
 
-  !> Default thermal zone path (x86_pkg_temp is reliable for Intel CPUs)
-  character(len=*), parameter :: DEFAULT_THERMAL_PATH = &
-                                 "/sys/class/thermal/thermal_zone10/temp"
+  ! Sensor paths are passed in by the caller; the Rust side (src/thermal.rs)
+  ! discovers a usable zone under /sys/class/thermal.
 
   !> Default temperature threshold in Celsius
   integer, parameter :: DEFAULT_THRESHOLD_C = 85
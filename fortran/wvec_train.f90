@@ -1,11 +1,28 @@
-! wvec_train.f90 - Skip-gram training with negative sampling
+! wvec_train.f90 - Skip-gram and CBOW training with negative sampling
 module wvec_train
   use, intrinsic :: iso_c_binding
   use wvec_types
   use wvec_blas
   use wvec_model
+  use wvec_rng
+  !$ use omp_lib
   implicit none
 
+  !> Words a thread trains between progress updates and learning-rate decay steps
+  integer, parameter :: PROGRESS_INTERVAL = 10000
+
+  !> Progress counters, polled from Rust via wvec_train_progress
+  integer(c_int64_t), save :: g_words_processed = 0  ! global position in the lr schedule
+  real(c_float), save :: g_current_lr = 0.0
+  real(c_double), save :: g_loss_sum = 0.0d0          ! cumulative, never reset
+  integer(c_int64_t), save :: g_loss_pairs = 0        ! pairs contributing to g_loss_sum
+
+  !> Huffman tree for hierarchical softmax, set from Rust via wvec_hs_set_tree.
+  !> Inner node vectors share g_w_out: inner node p (0-indexed) is column p + 1.
+  integer(c_int), allocatable, save :: g_hs_lens(:)          ! (vocab_size) code lengths
+  integer(c_int8_t), allocatable, save :: g_hs_codes(:, :)   ! (max_len, vocab_size) branch bits
+  integer(c_int), allocatable, save :: g_hs_points(:, :)     ! (max_len, vocab_size) inner nodes
+
 contains
 
   !> Sigmoid function: σ(x) = 1 / (1 + exp(-x))
@@ -81,67 +98,360 @@ contains
     status = 0
   end function wvec_train_pair
 
-  !> Train on a corpus of token IDs
+  !> Linearly decayed learning rate after words_done of total_words words
+  !>   lr = lr_start - (lr_start - lr_end) * words_done / total_words
+  pure real(c_float) function decayed_lr(lr_start, lr_end, words_done, total_words)
+    real(c_float), intent(in) :: lr_start, lr_end
+    integer(c_int64_t), intent(in) :: words_done, total_words
+    real(c_double) :: frac
+
+    if (total_words <= 0) then
+      decayed_lr = lr_start
+      return
+    end if
+
+    frac = min(1.0d0, real(words_done, c_double) / real(total_words, c_double))
+    decayed_lr = real(lr_start - (lr_start - lr_end) * frac, c_float)
+  end function decayed_lr
+
+  !> Train skip-gram on a corpus of token IDs
   !> Uses OpenMP for parallel training (Hogwild style)
-  function wvec_train_corpus(token_ids, n_tokens, window, n_neg, neg_table, neg_table_size, lr) &
+  !>
+  !> The learning rate decays linearly from lr_start to lr_end over total_words.
+  !> word_offset is the number of words already trained in earlier calls
+  !> (previous chunks and epochs), so the schedule continues across calls.
+  !>
+  !> Each thread draws negatives and window sizes from its own generator,
+  !> seeded from seed and the thread number. For every center word the
+  !> window shrinks to window - b, b uniform in [0, window), as in word2vec.
+  !>
+  !> hs = 1 replaces negative sampling with hierarchical softmax over the
+  !> tree from wvec_hs_set_tree; the negative table is then unused.
+  !>
+  !> Only tokens center_start+1 .. center_end (0-indexed half-open range
+  !> [center_start, center_end)) are trained as centers; the tokens around
+  !> them serve as context only. A streaming caller passes the tail of the
+  !> previous chunk and the head of the next one this way, so windows
+  !> carry across chunk boundaries.
+  function wvec_train_corpus(token_ids, n_tokens, center_start, center_end, window, n_neg, &
+                             neg_table, neg_table_size, lr_start, lr_end, word_offset, &
+                             total_words, seed, hs) &
     result(status) bind(C, name="wvec_train_corpus")
-    integer(c_int), intent(in), value :: n_tokens, window, n_neg, neg_table_size
+    integer(c_int), intent(in), value :: n_tokens, center_start, center_end
+    integer(c_int), intent(in), value :: window, n_neg, neg_table_size, hs
     integer(c_int), intent(in) :: token_ids(n_tokens)
     integer(c_int), intent(in) :: neg_table(neg_table_size)
-    real(c_float), intent(in), value :: lr
+    real(c_float), intent(in), value :: lr_start, lr_end
+    integer(c_int64_t), intent(in), value :: word_offset, total_words, seed
+    integer(c_int) :: status
+
+    status = train_corpus(token_ids, n_tokens, center_start, center_end, window, n_neg, &
+                          neg_table, neg_table_size, lr_start, lr_end, word_offset, &
+                          total_words, seed, hs /= 0, .false., .false.)
+  end function wvec_train_corpus
+
+  !> Train CBOW on a corpus of token IDs
+  !>
+  !> Same arguments and schedule as wvec_train_corpus. The context vectors
+  !> from g_w_in are averaged (cbow_mean = 1) or summed (cbow_mean = 0) and
+  !> trained to predict the center word against g_w_out.
+  function wvec_train_corpus_cbow(token_ids, n_tokens, center_start, center_end, window, n_neg, &
+                                  neg_table, neg_table_size, lr_start, lr_end, word_offset, &
+                                  total_words, seed, hs, cbow_mean) &
+    result(status) bind(C, name="wvec_train_corpus_cbow")
+    integer(c_int), intent(in), value :: n_tokens, center_start, center_end
+    integer(c_int), intent(in), value :: window, n_neg, neg_table_size, hs, cbow_mean
+    integer(c_int), intent(in) :: token_ids(n_tokens)
+    integer(c_int), intent(in) :: neg_table(neg_table_size)
+    real(c_float), intent(in), value :: lr_start, lr_end
+    integer(c_int64_t), intent(in), value :: word_offset, total_words, seed
+    integer(c_int) :: status
+
+    status = train_corpus(token_ids, n_tokens, center_start, center_end, window, n_neg, &
+                          neg_table, neg_table_size, lr_start, lr_end, word_offset, &
+                          total_words, seed, hs /= 0, .true., cbow_mean /= 0)
+  end function wvec_train_corpus_cbow
+
+  !> Shared Hogwild loop behind wvec_train_corpus and wvec_train_corpus_cbow
+  !> (not exported to C)
+  function train_corpus(token_ids, n_tokens, center_start, center_end, window, n_neg, &
+                        neg_table, neg_table_size, lr_start, lr_end, word_offset, &
+                        total_words, seed, hs, cbow, cbow_mean) &
+    result(status)
+    integer(c_int), intent(in) :: n_tokens, center_start, center_end
+    integer(c_int), intent(in) :: window, n_neg, neg_table_size
+    integer(c_int), intent(in) :: token_ids(n_tokens)
+    integer(c_int), intent(in) :: neg_table(neg_table_size)
+    real(c_float), intent(in) :: lr_start, lr_end
+    integer(c_int64_t), intent(in) :: word_offset, total_words, seed
+    logical, intent(in) :: hs, cbow, cbow_mean
     integer(c_int) :: status
 
     integer :: i, j, ctx_start, ctx_end, center_id, context_id
-    integer :: neg_idx, k
-    integer(c_int), allocatable :: neg_ids(:)
+    integer :: tid, eff_window, n_ctx
+    integer(c_int64_t) :: rng_state
+    integer(c_int), allocatable :: neg_ids(:), ctx_ids(:)
+    real(c_float), allocatable :: grad_center(:), neu1(:)
+    real(c_float) :: lr
+    integer(c_int64_t) :: local_words, local_pairs, words_done
+    real(c_double) :: local_loss
 
     if (.not. g_initialized) then
       status = -1
       return
     end if
 
-    !$omp parallel private(i, j, ctx_start, ctx_end, center_id, context_id, neg_ids, neg_idx, k)
+    ! The dynamic window and the negative draws need non-empty ranges
+    if (window < 1 .or. (.not. hs .and. neg_table_size < 1)) then
+      status = -2
+      return
+    end if
+
+    if (center_start < 0 .or. center_end < center_start .or. center_end > n_tokens) then
+      status = -2
+      return
+    end if
+
+    ! Hierarchical softmax needs a tree over this model's vocabulary
+    if (hs) then
+      if (.not. allocated(g_hs_lens)) then
+        status = -2
+        return
+      end if
+      if (size(g_hs_lens) /= g_vocab_size) then
+        status = -2
+        return
+      end if
+    end if
+
+    g_words_processed = word_offset
+    g_current_lr = decayed_lr(lr_start, lr_end, word_offset, total_words)
+
+    ! Hogwild: threads split the tokens and update the shared embeddings without
+    ! locks. Each thread owns its scratch buffers and generator state.
+    !$omp parallel private(i, j, ctx_start, ctx_end, center_id, context_id, neg_ids, ctx_ids) &
+    !$omp private(lr, local_words, local_pairs, local_loss, words_done) &
+    !$omp private(tid, eff_window, n_ctx, rng_state, grad_center, neu1)
     allocate (neg_ids(n_neg))
+    allocate (ctx_ids(2 * window))
+    allocate (grad_center(g_dim))
+    allocate (neu1(g_dim))
+    tid = 0
+    !$ tid = omp_get_thread_num()
+    rng_state = rng_init(seed, tid)
+    lr = decayed_lr(lr_start, lr_end, word_offset, total_words)
+    local_words = 0
+    local_pairs = 0
+    local_loss = 0.0d0
 
     !$omp do schedule(dynamic, 1000)
-    do i = 1, n_tokens
+    do i = center_start + 1, center_end
 
       ! Skip remaining work if shutdown requested
       if (g_shutdown_requested) cycle
 
-      center_id = token_ids(i)
-
-      ! Context window bounds
-      ctx_start = max(1, i - window)
-      ctx_end = min(n_tokens, i + window)
+      ! Publish progress and decay the learning rate every PROGRESS_INTERVAL words
+      local_words = local_words + 1
+      if (local_words >= PROGRESS_INTERVAL) then
+        call publish_progress(local_words, local_pairs, local_loss, words_done)
+        lr = decayed_lr(lr_start, lr_end, words_done, total_words)
+        !$omp atomic write
+        g_current_lr = lr
+      end if
 
-      ! Train with each context word
-      do j = ctx_start, ctx_end
-        if (j == i) cycle  ! Skip center word itself
-        context_id = token_ids(j)
+      center_id = token_ids(i)
 
-        ! Sample negative words from table
-        do k = 1, n_neg
-          neg_idx = modulo(i * 7 + j * 13 + k * 17, neg_table_size) + 1
-          neg_ids(k) = neg_table(neg_idx)
+      ! Dynamic window: nearer words are trained more often
+      eff_window = window - rng_below(rng_state, window)
+      ctx_start = max(1, i - eff_window)
+      ctx_end = min(n_tokens, i + eff_window)
+
+      if (cbow) then
+        ! One example per center word: predict it from the whole window
+        n_ctx = 0
+        do j = ctx_start, ctx_end
+          if (j == i) cycle
+          n_ctx = n_ctx + 1
+          ctx_ids(n_ctx) = token_ids(j)
         end do
-
-        ! Train this pair (updates shared g_w_in, g_w_out)
-        call train_pair_internal(center_id, context_id, neg_ids, n_neg, lr)
-      end do
+        if (n_ctx == 0) cycle
+
+        if (.not. hs) then
+          call draw_negatives(neg_table, neg_table_size, center_id, rng_state, neg_ids, n_neg)
+        end if
+        call train_cbow_internal(ctx_ids, n_ctx, center_id, neg_ids, n_neg, lr, hs, cbow_mean, &
+                                 neu1, grad_center, local_loss)
+        local_pairs = local_pairs + 1
+      else
+        ! Train with each context word
+        do j = ctx_start, ctx_end
+          if (j == i) cycle  ! Skip center word itself
+          context_id = token_ids(j)
+
+          if (.not. hs) then
+            call draw_negatives(neg_table, neg_table_size, context_id, rng_state, neg_ids, n_neg)
+          end if
+
+          ! Train this pair (updates shared g_w_in, g_w_out)
+          call train_pair_internal(center_id, context_id, neg_ids, n_neg, lr, hs, grad_center, &
+                                   local_loss)
+          local_pairs = local_pairs + 1
+        end do
+      end if
     end do
     !$omp end do
 
+    ! Flush this thread's remaining counts
+    call publish_progress(local_words, local_pairs, local_loss, words_done)
+
     deallocate (neg_ids)
+    deallocate (ctx_ids)
+    deallocate (grad_center)
+    deallocate (neu1)
     !$omp end parallel
 
+    g_current_lr = decayed_lr(lr_start, lr_end, g_words_processed, total_words)
+
     ! Return 1 if interrupted, 0 if completed normally
     if (g_shutdown_requested) then
       status = 1  ! STATUS_INTERRUPTED
     else
       status = 0  ! SUCCESS
     end if
-  end function wvec_train_corpus
+  end function train_corpus
+
+  !> Sample n_neg negative words from the unigram table, redrawing once when
+  !> a draw hits the positive target itself
+  subroutine draw_negatives(neg_table, neg_table_size, target_id, rng_state, neg_ids, n_neg)
+    integer(c_int), intent(in) :: neg_table_size, target_id, n_neg
+    integer(c_int), intent(in) :: neg_table(neg_table_size)
+    integer(c_int64_t), intent(inout) :: rng_state
+    integer(c_int), intent(out) :: neg_ids(n_neg)
+    integer :: k
+
+    do k = 1, n_neg
+      neg_ids(k) = neg_table(rng_below(rng_state, neg_table_size) + 1)
+      if (neg_ids(k) == target_id) then
+        neg_ids(k) = neg_table(rng_below(rng_state, neg_table_size) + 1)
+      end if
+    end do
+  end subroutine draw_negatives
+
+  !> Add a thread's local counters to the shared progress counters and reset them
+  !> Returns the updated global word position in words_done
+  subroutine publish_progress(local_words, local_pairs, local_loss, words_done)
+    integer(c_int64_t), intent(inout) :: local_words, local_pairs
+    real(c_double), intent(inout) :: local_loss
+    integer(c_int64_t), intent(out) :: words_done
+
+    !$omp atomic capture
+    g_words_processed = g_words_processed + local_words
+    words_done = g_words_processed
+    !$omp end atomic
+
+    !$omp atomic update
+    g_loss_pairs = g_loss_pairs + local_pairs
+
+    !$omp atomic update
+    g_loss_sum = g_loss_sum + local_loss
+
+    local_words = 0
+    local_pairs = 0
+    local_loss = 0.0d0
+  end subroutine publish_progress
+
+  !> Set the number of OpenMP threads used by wvec_train_corpus
+  !> n <= 0 keeps the OpenMP default (OMP_NUM_THREADS or all cores)
+  subroutine wvec_set_num_threads(n) bind(C, name="wvec_set_num_threads")
+    integer(c_int), intent(in), value :: n
+
+    !$ if (n > 0) call omp_set_num_threads(n)
+  end subroutine wvec_set_num_threads
+
+  !> Number of threads the next parallel region will use (1 without OpenMP)
+  function wvec_get_num_threads() result(n) bind(C, name="wvec_get_num_threads")
+    integer(c_int) :: n
+
+    n = 1
+    !$ n = omp_get_max_threads()
+  end function wvec_get_num_threads
+
+  !> Install the Huffman tree for hierarchical softmax
+  !> Parameters:
+  !>   vocab_size: number of leaves, must match the model when training
+  !>   max_len: longest code; codes and points are padded to it per token
+  !>   code_lens: code length of each token
+  !>   codes: branch bits (0/1), root first
+  !>   points: 0-indexed inner node ids in [0, vocab_size - 2], root first
+  function wvec_hs_set_tree(vocab_size, max_len, code_lens, codes, points) &
+    result(status) bind(C, name="wvec_hs_set_tree")
+    integer(c_int), intent(in), value :: vocab_size, max_len
+    integer(c_int), intent(in) :: code_lens(vocab_size)
+    integer(c_int8_t), intent(in) :: codes(max_len, vocab_size)
+    integer(c_int), intent(in) :: points(max_len, vocab_size)
+    integer(c_int) :: status
+    integer :: alloc_stat
+
+    if (vocab_size < 1 .or. max_len < 0) then
+      status = -2
+      return
+    end if
+    if (any(code_lens < 0) .or. any(code_lens > max_len)) then
+      status = -2
+      return
+    end if
+    if (any(codes < 0) .or. any(codes > 1)) then
+      status = -2
+      return
+    end if
+    if (any(points < 0) .or. any(points > max(vocab_size - 2, 0))) then
+      status = -2
+      return
+    end if
+
+    call wvec_hs_free()
+    allocate (g_hs_lens(vocab_size), g_hs_codes(max_len, vocab_size), &
+              g_hs_points(max_len, vocab_size), stat=alloc_stat)
+    if (alloc_stat /= 0) then
+      call wvec_hs_free()
+      status = -3
+      return
+    end if
+
+    g_hs_lens = code_lens
+    g_hs_codes = codes
+    g_hs_points = points
+    status = 0
+  end function wvec_hs_set_tree
+
+  !> Free the hierarchical softmax tree
+  subroutine wvec_hs_free() bind(C, name="wvec_hs_free")
+    if (allocated(g_hs_lens)) deallocate (g_hs_lens)
+    if (allocated(g_hs_codes)) deallocate (g_hs_codes)
+    if (allocated(g_hs_points)) deallocate (g_hs_points)
+  end subroutine wvec_hs_free
+
+  !> Report training progress; safe to call from another thread while training
+  !> Parameters:
+  !>   words: global word position in the learning-rate schedule
+  !>   lr: learning rate currently in use
+  !>   loss_sum: cumulative loss over all trained pairs
+  !>   loss_pairs: number of pairs contributing to loss_sum
+  subroutine wvec_train_progress(words, lr, loss_sum, loss_pairs) &
+    bind(C, name="wvec_train_progress")
+    integer(c_int64_t), intent(out) :: words, loss_pairs
+    real(c_float), intent(out) :: lr
+    real(c_double), intent(out) :: loss_sum
+
+    !$omp atomic read
+    words = g_words_processed
+    !$omp atomic read
+    lr = g_current_lr
+    !$omp atomic read
+    loss_sum = g_loss_sum
+    !$omp atomic read
+    loss_pairs = g_loss_pairs
+  end subroutine wvec_train_progress
 
   !> Internal training routine for skip-gram with negative sampling (not exported to C)
   !>
@@ -163,34 +473,47 @@ contains
   !> Intuition:
   !>   - Positive pair with low  score -> large    g -> push vectors together
   !>   - Negative pair with high score -> negative g -> push vectors apart
-  subroutine train_pair_internal(center_id, context_id, neg_ids, n_neg, lr)
+  !>
+  !> The pair's loss, -log σ(pos) - Σ log σ(-neg), is added to loss.
+  !> grad_center is caller-owned scratch space of length g_dim, so the hot
+  !> loop does not allocate. With hs, neg_ids is ignored and the output
+  !> layer is hierarchical softmax (see hs_output).
+  subroutine train_pair_internal(center_id, context_id, neg_ids, n_neg, lr, hs, grad_center, loss)
     integer(c_int), intent(in) :: center_id, context_id, n_neg
     integer(c_int), intent(in) :: neg_ids(n_neg)
     real(c_float), intent(in) :: lr
+    logical, intent(in) :: hs
+    real(c_float), intent(inout) :: grad_center(g_dim)
+    real(c_double), intent(inout) :: loss
 
-    real(c_float) :: score, g
-    real(c_float), allocatable :: grad_center(:)
+    real(c_float) :: score, g, sig
     integer :: dim, i, neg_id_fortran, center_fortran, context_fortran
     integer :: one
 
     dim = g_dim
     one = 1  ! BLAS stride (contiguous memory access)
 
-    ! Allocate buffer to accumulate gradients for center word.
-    ! We apply all updates at once at the end for efficiency.
-    allocate (grad_center(dim))
+    ! Accumulate gradients for the center word and apply them at the end
     grad_center = 0.0
 
     ! Convert from 0-indexed (C) to 1-indexed (Fortran)
     center_fortran = center_id + 1
     context_fortran = context_id + 1
 
+    if (hs) then
+      call hs_output(g_w_in(1:dim, center_fortran), context_id, lr, grad_center, loss)
+      call saxpy(dim, 1.0, grad_center, one, g_w_in(1, center_fortran), one)
+      return
+    end if
+
     ! Positive sample (center, context): make these vectors similar
     ! label = 1, so g = (1 - σ(score)) x lr
     ! If score is already high (correct), σ ≈ 1, g ≈ 0 (small update)
     ! If score is low (wrong), σ ≈ 0, g ≈ lr (large update to push together)
     score = sdot(dim, g_w_in(1, center_fortran), one, g_w_out(1, context_fortran), one)
-    g = (1.0 - sigmoid(score)) * lr
+    sig = sigmoid(score)
+    g = (1.0 - sig) * lr
+    loss = loss - log(max(sig, 1.0e-7))
     call saxpy(dim, g, g_w_out(1, context_fortran), one, grad_center, one)  ! accumulate
     call saxpy(dim, g, g_w_in(1, center_fortran), one, g_w_out(1, context_fortran), one)  ! update context
 
@@ -201,15 +524,123 @@ contains
     do i = 1, n_neg
       neg_id_fortran = neg_ids(i) + 1
       score = sdot(dim, g_w_in(1, center_fortran), one, g_w_out(1, neg_id_fortran), one)
-      g = -sigmoid(score) * lr
+      sig = sigmoid(score)
+      g = -sig * lr
+      loss = loss - log(max(1.0 - sig, 1.0e-7))
       call saxpy(dim, g, g_w_out(1, neg_id_fortran), one, grad_center, one)  ! accumulate
       call saxpy(dim, g, g_w_in(1, center_fortran), one, g_w_out(1, neg_id_fortran), one)  ! update negative
     end do
 
     ! Apply accumulated gradient to center embedding
     call saxpy(dim, 1.0, grad_center, one, g_w_in(1, center_fortran), one)
-
-    deallocate (grad_center)
   end subroutine train_pair_internal
 
+  !> Internal training routine for CBOW with negative sampling (not exported to C)
+  !>
+  !> CBOW objective: predict the center word from its context
+  !>
+  !>   h = Σ w_in[ctx]            (divided by n_ctx when cbow_mean)
+  !>   g = (label - σ(h · w_out[target])) × learning_rate
+  !>
+  !> with the same labels as skip-gram (1 for the center word, 0 for
+  !> negatives). The hidden-layer gradient is accumulated in grad_center and
+  !> added to every context word's input vector, as in word2vec.
+  !>
+  !> neu1 and grad_center are caller-owned scratch space of length g_dim.
+  !> With hs, neg_ids is ignored and the center word's Huffman path is trained.
+  subroutine train_cbow_internal(ctx_ids, n_ctx, center_id, neg_ids, n_neg, lr, hs, cbow_mean, &
+                                 neu1, grad_center, loss)
+    integer(c_int), intent(in) :: n_ctx, center_id, n_neg
+    integer(c_int), intent(in) :: ctx_ids(n_ctx), neg_ids(n_neg)
+    real(c_float), intent(in) :: lr
+    logical, intent(in) :: hs, cbow_mean
+    real(c_float), intent(inout) :: neu1(g_dim), grad_center(g_dim)
+    real(c_double), intent(inout) :: loss
+
+    real(c_float) :: score, g, sig
+    integer :: dim, i, target_fortran
+    integer :: one
+
+    dim = g_dim
+    one = 1
+
+    ! Hidden layer: sum (or mean) of the context input vectors
+    neu1 = 0.0
+    do i = 1, n_ctx
+      call saxpy(dim, 1.0, g_w_in(1, ctx_ids(i) + 1), one, neu1, one)
+    end do
+    if (cbow_mean) neu1 = neu1 / n_ctx
+
+    grad_center = 0.0
+
+    if (hs) then
+      call hs_output(neu1, center_id, lr, grad_center, loss)
+    else
+      ! Positive target: the center word, label = 1
+      target_fortran = center_id + 1
+      score = sdot(dim, neu1, one, g_w_out(1, target_fortran), one)
+      sig = sigmoid(score)
+      g = (1.0 - sig) * lr
+      loss = loss - log(max(sig, 1.0e-7))
+      call saxpy(dim, g, g_w_out(1, target_fortran), one, grad_center, one)
+      call saxpy(dim, g, neu1, one, g_w_out(1, target_fortran), one)
+
+      ! Negative samples, label = 0
+      do i = 1, n_neg
+        target_fortran = neg_ids(i) + 1
+        score = sdot(dim, neu1, one, g_w_out(1, target_fortran), one)
+        sig = sigmoid(score)
+        g = -sig * lr
+        loss = loss - log(max(1.0 - sig, 1.0e-7))
+        call saxpy(dim, g, g_w_out(1, target_fortran), one, grad_center, one)
+        call saxpy(dim, g, neu1, one, g_w_out(1, target_fortran), one)
+      end do
+    end if
+
+    ! Spread the hidden-layer gradient back over the context words
+    do i = 1, n_ctx
+      call saxpy(dim, 1.0, grad_center, one, g_w_in(1, ctx_ids(i) + 1), one)
+    end do
+  end subroutine train_cbow_internal
+
+  !> Hierarchical softmax output layer (not exported to C)
+  !>
+  !> Walks the Huffman path of target_id from the root. At each inner node
+  !> the label is 1 - code, as in word2vec:
+  !>   g = (1 - code - σ(h · w_out[point])) × learning_rate
+  !> The inner node vector is updated in place and the gradient for h is
+  !> accumulated into grad.
+  subroutine hs_output(h, target_id, lr, grad, loss)
+    real(c_float), intent(in) :: h(g_dim)
+    integer(c_int), intent(in) :: target_id
+    real(c_float), intent(in) :: lr
+    real(c_float), intent(inout) :: grad(g_dim)
+    real(c_double), intent(inout) :: loss
+
+    real(c_float) :: score, g, sig, label
+    integer :: dim, d, leaf, node
+    integer :: one
+
+    dim = g_dim
+    one = 1
+    leaf = target_id + 1
+
+    do d = 1, g_hs_lens(leaf)
+      node = g_hs_points(d, leaf) + 1
+      label = 1.0 - real(g_hs_codes(d, leaf), c_float)
+
+      score = sdot(dim, h, one, g_w_out(1, node), one)
+      sig = sigmoid(score)
+      g = (label - sig) * lr
+      if (label > 0.5) then
+        loss = loss - log(max(sig, 1.0e-7))
+      else
+        loss = loss - log(max(1.0 - sig, 1.0e-7))
+      end if
+
+      call saxpy(dim, g, g_w_out(1, node), one, grad, one)
+      call saxpy(dim, g, h, one, g_w_out(1, node), one)
+    end do
+  end subroutine hs_output
+
 end module wvec_train
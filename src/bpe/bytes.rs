@@ -0,0 +1,122 @@
+//! Byte-level alphabet
+//!
+//! Byte-level vocabularies split pre-tokens into UTF-8 bytes instead of
+//! characters, so any input can be encoded without `[UNK]`. Tokens are
+//! still stored as strings: each byte maps to one printable character, as
+//! in GPT-2. Printable Latin-1 bytes map to themselves and the others to
+//! U+0100 onwards, so ASCII tokens stay readable in the vocab file.
+
+/// Number of base tokens in a byte-level vocabulary
+pub const BYTE_TOKENS: usize = 256;
+
+/// Bytes that stand for themselves as characters
+const fn is_printable(b: u8) -> bool {
+    matches!(b, b'!'..=b'~' | 0xA1..=0xAC | 0xAE..=0xFF)
+}
+
+/// Number of bytes mapped past U+00FF
+const REMAPPED: usize = {
+    let mut n = 0;
+    let mut b = 0;
+    while b < 256 {
+        if !is_printable(b as u8) {
+            n += 1;
+        }
+        b += 1;
+    }
+    n
+};
+
+/// Character standing for each byte value
+const BYTE_CHARS: [char; 256] = {
+    let mut chars = ['\0'; 256];
+    let mut next = 0x100;
+    let mut b = 0;
+    while b < 256 {
+        chars[b] = if is_printable(b as u8) {
+            b as u8 as char
+        } else {
+            next += 1;
+            char::from_u32(next - 1).unwrap()
+        };
+        b += 1;
+    }
+    chars
+};
+
+/// Bytes mapped past U+00FF, in the order of their characters
+const REMAPPED_BYTES: [u8; REMAPPED] = {
+    let mut bytes = [0u8; REMAPPED];
+    let mut n = 0;
+    let mut b = 0;
+    while b < 256 {
+        if !is_printable(b as u8) {
+            bytes[n] = b as u8;
+            n += 1;
+        }
+        b += 1;
+    }
+    bytes
+};
+
+/// Returns the character that stands for byte `b` in token strings.
+pub fn byte_to_char(b: u8) -> char {
+    BYTE_CHARS[b as usize]
+}
+
+/// Returns the byte a token character stands for, or None if `c` is not
+/// part of the byte alphabet.
+pub fn char_to_byte(c: char) -> Option<u8> {
+    let code = c as u32;
+    if code < 0x100 {
+        let b = code as u8;
+        return is_printable(b).then_some(b);
+    }
+    REMAPPED_BYTES.get((code - 0x100) as usize).copied()
+}
+
+/// Spells `text` in the byte alphabet, e.g. "é" -> "Ã©".
+pub fn to_token_string(text: &str) -> String {
+    text.bytes().map(byte_to_char).collect()
+}
+
+/// Appends the bytes that a byte-level token string stands for.
+/// Characters outside the byte alphabet are kept as their UTF-8 encoding.
+pub fn push_token_bytes(token: &str, out: &mut Vec<u8>) {
+    let mut utf8 = [0u8; 4];
+    for c in token.chars() {
+        match char_to_byte(c) {
+            Some(b) => out.push(b),
+            None => out.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_alphabet_roundtrip() {
+        let mut seen = std::collections::HashSet::new();
+        for b in 0..=255u8 {
+            let c = byte_to_char(b);
+            assert!(!c.is_control() && !c.is_whitespace(), "byte {:#x}", b);
+            assert!(seen.insert(c));
+            assert_eq!(char_to_byte(c), Some(b));
+        }
+        assert_eq!(byte_to_char(b'a'), 'a');
+        assert_eq!(char_to_byte('\n'), None);
+    }
+
+    #[test]
+    fn test_token_string_roundtrip() {
+        let text = "naïve 日本 🙂";
+        let token = to_token_string(text);
+        assert_eq!(token.chars().count(), text.len());
+
+        let mut bytes = Vec::new();
+        push_token_bytes(&token, &mut bytes);
+        assert_eq!(bytes, text.as_bytes());
+    }
+}
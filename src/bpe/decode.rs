@@ -3,13 +3,18 @@
 //! Converts token IDs to text.
 
 use crate::bpe::{
+    bytes::push_token_bytes,
     types::{BpeTokenId, UNK_TOKEN},
     vocab::Vocabulary,
 };
 
 /// Decodes a sequence of token IDs back to a string.
 ///
-/// Unknown IDs are replaced with "[UNK]".
+/// Vocabularies with word boundaries keep the space before a word in its
+/// first token, so decoding the tokens of a whole text restores its spaces.
+/// Unknown IDs are replaced with "[UNK]". For byte-level vocabularies the
+/// token bytes are joined first, so characters split across tokens decode
+/// exactly; invalid UTF-8 is replaced with U+FFFD.
 ///
 /// # Example
 ///
@@ -22,6 +27,14 @@ use crate::bpe::{
 /// decode(vocab, [])      -> ""
 /// ```
 pub fn decode(vocab: &Vocabulary, ids: &[BpeTokenId]) -> String {
+    if vocab.is_byte_level() {
+        let mut bytes = Vec::new();
+        for &id in ids {
+            push_token_bytes(vocab.get_token(id).unwrap_or(UNK_TOKEN), &mut bytes);
+        }
+        return String::from_utf8_lossy(&bytes).into_owned();
+    }
+
     let mut result = String::new();
 
     for &id in ids.iter() {
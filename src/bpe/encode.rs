@@ -2,7 +2,16 @@
 //!
 //! Converts text to token IDs.
 
-use crate::bpe::{types::BpeTokenId, vocab::Vocabulary};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::bpe::{bytes::byte_to_char, types::BpeTokenId, vocab::Vocabulary};
+
+/// Maximum number of distinct pre-tokens kept by an [`Encoder`] cache.
+pub const DEFAULT_CACHE_SIZE: usize = 1 << 20;
+
+/// Marks the missing neighbour of the first and last symbol
+const NONE: usize = usize::MAX;
 
 /// Encodes a pre-token into a sequence of BPE token IDs.
 ///
@@ -10,8 +19,17 @@ use crate::bpe::{types::BpeTokenId, vocab::Vocabulary};
 ///
 /// # Algorithm
 ///
-/// 1. Convert each character to its token ID (unknown chars → UNK_ID)
-/// 2. Apply merge rules in priority order (most frequent merges first)
+/// 1. Convert each character to its token ID (unknown chars → UNK_ID), or
+///    each UTF-8 byte for byte-level vocabularies, which never yield UNK_ID
+/// 2. Put every adjacent pair that has a merge rule on a min-heap, keyed by
+///    rank and then position
+/// 3. Pop the best pair and merge it in a linked list of symbols; only the
+///    two pairs next to the merged symbol are new, so only they are pushed.
+///    Entries whose symbols have changed since are skipped
+///
+/// A merge costs O(log n) in the pre-token length, so long pre-tokens (URLs,
+/// digit runs, byte-level strings) stay cheap. The result is the same as
+/// applying every rule of `vocab.pairs()` in order.
 ///
 /// # Example
 ///
@@ -30,48 +48,209 @@ use crate::bpe::{types::BpeTokenId, vocab::Vocabulary};
 ///   Result: [6, 4]
 /// ```
 pub fn encode(vocab: &Vocabulary, pretoken: &str) -> Vec<BpeTokenId> {
-    if pretoken.is_empty() {
-        return Vec::new();
+    let mut utf8 = [0u8; 4];
+    let mut ids: Vec<BpeTokenId> = if vocab.is_byte_level() {
+        pretoken
+            .bytes()
+            .map(|b| vocab.get_id(byte_to_char(b).encode_utf8(&mut utf8)))
+            .collect()
+    } else {
+        pretoken
+            .chars()
+            .map(|ch| vocab.get_id(ch.encode_utf8(&mut utf8)))
+            .collect()
+    };
+    let n = ids.len();
+    if n < 2 {
+        return ids;
     }
 
-    let mut ids: Vec<BpeTokenId> = pretoken
-        .chars()
-        .map(|ch| vocab.get_id(&ch.to_string()))
-        .collect();
+    // Doubly linked list over the symbols; a merge keeps the left one
+    let mut prev: Vec<usize> = (0..n).map(|i| i.checked_sub(1).unwrap_or(NONE)).collect();
+    let mut next: Vec<usize> = (1..=n).map(|i| if i < n { i } else { NONE }).collect();
+    let mut removed = vec![false; n];
 
-    for pair in vocab.pairs() {
-        apply_merge(&mut ids, pair.left, pair.right, pair.id);
+    let mut heap = BinaryHeap::new();
+    for pos in 0..n - 1 {
+        push_pair(vocab, &mut heap, &ids, pos, pos + 1, 0);
     }
 
-    ids
+    while let Some(Reverse((rank, pos))) = heap.pop() {
+        let right = next[pos];
+        if removed[pos] || right == NONE {
+            continue;
+        }
+        let merged_id = match vocab.merge_rank(ids[pos], ids[right]) {
+            Some((r, merged_id)) if r == rank => merged_id,
+            _ => continue,
+        };
+
+        ids[pos] = merged_id;
+        removed[right] = true;
+        next[pos] = next[right];
+        if next[pos] != NONE {
+            prev[next[pos]] = pos;
+        }
+
+        // Rules up to `rank` were already applied; a merge can only create
+        // pairs for them through a duplicate token, which the rule-by-rule
+        // order would never revisit.
+        if prev[pos] != NONE {
+            push_pair(vocab, &mut heap, &ids, prev[pos], pos, rank + 1);
+        }
+        if next[pos] != NONE {
+            push_pair(vocab, &mut heap, &ids, pos, next[pos], rank + 1);
+        }
+    }
+
+    let mut out = Vec::with_capacity(n);
+    let mut pos = 0;
+    while pos != NONE {
+        out.push(ids[pos]);
+        pos = next[pos];
+    }
+    out
 }
 
-/// Applies a single merge rule to a token sequence.
-///
-/// Replaces all adjacent (left, right) pairs with merged_id.
-fn apply_merge(
-    ids: &mut Vec<BpeTokenId>,
-    left: BpeTokenId,
-    right: BpeTokenId,
-    merged_id: BpeTokenId,
+/// Queues the pair of symbols at `left` and `right` if it has a merge rule
+/// of rank `min_rank` or above.
+fn push_pair(
+    vocab: &Vocabulary,
+    heap: &mut BinaryHeap<Reverse<(u32, usize)>>,
+    ids: &[BpeTokenId],
+    left: usize,
+    right: usize,
+    min_rank: u32,
 ) {
-    let mut i = 0;
-    while i + 1 < ids.len() {
-        if ids[i] == left && ids[i + 1] == right {
-            ids[i] = merged_id;
-            ids.remove(i + 1);
-            // Don't increment: merged token might form new pair
-        } else {
-            i += 1;
+    if let Some((rank, _)) = vocab.merge_rank(ids[left], ids[right])
+        && rank >= min_rank
+    {
+        heap.push(Reverse((rank, left)));
+    }
+}
+
+/// BPE encoder that caches the IDs of the pre-tokens it has seen.
+///
+/// Natural text repeats a small set of words, so most pre-tokens are
+/// answered by a single hash lookup. The cache holds at most `capacity`
+/// entries; when full, the least recently used one is evicted, so frequent
+/// words stay cached however long the tail of rare ones.
+pub struct Encoder<'a> {
+    vocab: &'a Vocabulary,
+    /// Slot of every cached pre-token
+    index: HashMap<String, usize>,
+    /// Cached entries, linked from most to least recently used
+    slots: Vec<Slot>,
+    head: usize,
+    tail: usize,
+    capacity: usize,
+}
+
+struct Slot {
+    pretoken: String,
+    ids: Vec<BpeTokenId>,
+    prev: usize,
+    next: usize,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(vocab: &'a Vocabulary) -> Self {
+        Self::with_capacity(vocab, DEFAULT_CACHE_SIZE)
+    }
+
+    /// Creates an encoder caching at most `capacity` pre-tokens.
+    pub fn with_capacity(vocab: &'a Vocabulary, capacity: usize) -> Self {
+        Self {
+            vocab,
+            index: HashMap::new(),
+            slots: Vec::new(),
+            head: NONE,
+            tail: NONE,
+            capacity: capacity.max(1),
         }
     }
+
+    /// Encodes a pre-token, see [`encode`].
+    pub fn encode(&mut self, pretoken: &str) -> &[BpeTokenId] {
+        let slot = match self.index.get(pretoken) {
+            Some(&slot) => {
+                self.unlink(slot);
+                slot
+            }
+            None => {
+                let ids = encode(self.vocab, pretoken);
+                let slot = if self.slots.len() < self.capacity {
+                    self.slots.push(Slot {
+                        pretoken: pretoken.to_string(),
+                        ids,
+                        prev: NONE,
+                        next: NONE,
+                    });
+                    self.slots.len() - 1
+                } else {
+                    // Reuse the least recently used slot
+                    let slot = self.tail;
+                    self.unlink(slot);
+                    let evicted = &mut self.slots[slot];
+                    self.index.remove(&evicted.pretoken);
+                    evicted.pretoken.clear();
+                    evicted.pretoken.push_str(pretoken);
+                    evicted.ids = ids;
+                    slot
+                };
+                self.index.insert(pretoken.to_string(), slot);
+                slot
+            }
+        };
+        self.push_front(slot);
+        &self.slots[slot].ids
+    }
+
+    /// Number of cached pre-tokens.
+    pub fn cached(&self) -> usize {
+        self.index.len()
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        let Slot { prev, next, .. } = self.slots[slot];
+        match prev {
+            NONE => self.head = next,
+            prev => self.slots[prev].next = next,
+        }
+        match next {
+            NONE => self.tail = prev,
+            next => self.slots[next].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, slot: usize) {
+        self.slots[slot].prev = NONE;
+        self.slots[slot].next = self.head;
+        match self.head {
+            NONE => self.tail = slot,
+            head => self.slots[head].prev = slot,
+        }
+        self.head = slot;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use crate::bpe::{decode::decode, types::UNK_ID};
+    use crate::bpe::{decode::decode, train::merge_sequence, types::UNK_ID};
+
+    /// Reference encoder: applies every merge rule in order.
+    fn encode_naive(vocab: &Vocabulary, pretoken: &str) -> Vec<BpeTokenId> {
+        let mut ids: Vec<BpeTokenId> = pretoken
+            .chars()
+            .map(|ch| vocab.get_id(&ch.to_string()))
+            .collect();
+        for pair in vocab.pairs() {
+            merge_sequence(&mut ids, pair.left, pair.right, pair.id);
+        }
+        ids
+    }
 
     #[test]
     fn test_encode_basic() {
@@ -128,4 +307,138 @@ mod tests {
         // After merge, "aa" should be 1 token (not 2)
         assert_eq!(ids.len(), 1);
     }
+
+    #[test]
+    fn test_encode_matches_naive_encoder() {
+        use crate::bpe::train::train;
+
+        let alphabet: Vec<char> = "aabbcdeé你".chars().collect();
+        let mut state = 54321u64;
+        let mut next = move |n: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % n
+        };
+        let mut word = |max_len: usize| -> String {
+            let len = 1 + next(max_len);
+            (0..len).map(|_| alphabet[next(alphabet.len())]).collect()
+        };
+
+        let corpus: Vec<String> = (0..2000).map(|_| word(8)).collect();
+        let vocab = train(corpus.iter().map(|s| s.as_str()), 300);
+
+        // Unseen and longer words, plus a character missing from the vocab
+        let mut words: Vec<String> = (0..2000).map(|_| word(16)).collect();
+        words.push("abxba".to_string());
+        words.push(String::new());
+        // Long pre-tokens such as digit runs or URLs
+        words.push("ab".repeat(500));
+        words.push("a".repeat(999));
+        words.push(corpus[..200].concat());
+        for w in &words {
+            assert_eq!(encode(&vocab, w), encode_naive(&vocab, w), "word {:?}", w);
+        }
+    }
+
+    #[test]
+    fn test_encode_skips_rules_already_passed() {
+        // "abcd" is learned twice: as "ab"+"cd" (rank 3) and "a"+"bcd" (rank 6).
+        // In "abcdx" it only forms at rank 6, after the (abcd, x) rule.
+        let mut vocab = Vocabulary::new();
+        let [a, b, c, d, x] = ["a", "b", "c", "d", "x"].map(|t| vocab.add_token(t.to_string()));
+        let [bc, ab, cd, abcd, abcdx, bcd] =
+            ["bc", "ab", "cd", "abcd", "abcdx", "bcd"].map(|t| vocab.add_token(t.to_string()));
+        vocab.add_pair(b, c, bc);
+        vocab.add_pair(a, b, ab);
+        vocab.add_pair(c, d, cd);
+        vocab.add_pair(ab, cd, abcd);
+        vocab.add_pair(abcd, x, abcdx);
+        vocab.add_pair(bc, d, bcd);
+        vocab.add_pair(a, bcd, abcd);
+
+        assert_eq!(encode(&vocab, "abcdx"), vec![abcd, x]);
+        assert_eq!(encode(&vocab, "abcdx"), encode_naive(&vocab, "abcdx"));
+    }
+
+    #[test]
+    fn test_encoder_cache() {
+        use crate::bpe::train::train;
+
+        let vocab = train(["hello", "hello", "world"].into_iter(), 20);
+        let mut encoder = Encoder::with_capacity(&vocab, 2);
+
+        for w in ["hello", "world", "hello", "help", "hello"] {
+            assert_eq!(encoder.encode(w), encode(&vocab, w).as_slice());
+            assert!(encoder.cached() <= 2);
+        }
+        assert_eq!(encoder.cached(), 2);
+    }
+
+    #[test]
+    fn test_encoder_cache_evicts_least_recently_used() {
+        use crate::bpe::train::train;
+
+        let vocab = train(["hello", "hello", "world"].into_iter(), 20);
+        let mut encoder = Encoder::with_capacity(&vocab, 3);
+
+        // "hello" is used between every new word, so it is never the oldest
+        for w in ["hello", "a", "hello", "b", "hello", "c", "hello", "d"] {
+            assert_eq!(encoder.encode(w), encode(&vocab, w).as_slice());
+        }
+        assert_eq!(encoder.cached(), 3);
+        assert!(encoder.index.contains_key("hello"));
+        assert!(encoder.index.contains_key("c"));
+        assert!(encoder.index.contains_key("d"));
+        assert!(!encoder.index.contains_key("a"));
+        assert_eq!(encoder.encode("hello"), encode(&vocab, "hello").as_slice());
+    }
+
+    #[test]
+    fn test_encode_byte_level_roundtrip() {
+        use crate::bpe::train::{TrainOptions, train_from_counts_with};
+        use std::collections::HashMap;
+
+        let freqs = HashMap::from([("hello".to_string(), 5), ("héllo".to_string(), 2)]);
+        let options = TrainOptions {
+            byte_level: true,
+            ..Default::default()
+        };
+        let vocab = train_from_counts_with(&freqs, 280, options);
+
+        // Characters never seen in training, including emoji and rare CJK
+        for text in ["hello", "héllo", "𠀋🙂", "x\u{0}y"] {
+            let ids = encode(&vocab, text);
+            assert!(!ids.contains(&UNK_ID), "{:?}", text);
+            assert_eq!(decode(&vocab, &ids), text);
+        }
+        assert_eq!(encode(&vocab, "hello").len(), 1);
+    }
+
+    #[test]
+    fn test_encode_word_boundaries_roundtrip() {
+        use crate::bpe::train::{TrainOptions, train_from_counts_with};
+        use crate::text::pretokenize::pretokenize;
+        use std::collections::HashMap;
+
+        let text = "the cat sat on the mat, then the cat left.";
+        let marked: Vec<String> = pretokenize(text)
+            .into_iter()
+            .map(|pt| pt.into_bpe_text(true))
+            .collect();
+        let mut freqs = HashMap::new();
+        for pt in &marked {
+            *freqs.entry(pt.clone()).or_insert(0) += 1;
+        }
+        let options = TrainOptions {
+            word_boundaries: true,
+            ..Default::default()
+        };
+        let vocab = train_from_counts_with(&freqs, 40, options);
+
+        let ids: Vec<BpeTokenId> = marked.iter().flat_map(|pt| encode(&vocab, pt)).collect();
+        assert_eq!(decode(&vocab, &ids), text);
+        assert_ne!(vocab.get_text_id("cat"), vocab.get_id_opt("cat"));
+        assert_eq!(vocab.get_text_id("cat"), vocab.get_id_opt(" cat"));
+    }
 }
@@ -2,16 +2,19 @@
 //!
 //! Save and load trained BPE vocabularies to/from binary files.
 //!
-//! # File Format (v1)
+//! # File Format (v2)
 //!
-//! All integers are little-endian.
+//! All integers are little-endian. Version 1 files have no `flags` field
+//! and are still read as character-level vocabularies.
 //!
 //! ```text
 //! ┌─────────────────────────────────────────────────────────┐
 //! │                        HEADER                           │
 //! ├──────────────┬──────────┬───────────────────────────────┤
 //! │ magic        │ [u8; 4]  │ "BPE\0" - file identifier     │
-//! │ version      │ u32      │ format version (currently 1)  │
+//! │ version      │ u32      │ format version (currently 2)  │
+//! │ flags        │ u32      │ bit 0: byte-level tokens      │
+//! │              │          │ bit 1: word-boundary markers  │
 //! │ vocab_size   │ u32      │ number of tokens              │
 //! │ pairs_count  │ u32      │ number of merge rules         │
 //! ├──────────────┴──────────┴───────────────────────────────┤
@@ -52,7 +55,13 @@ use crate::bpe::vocab::Vocabulary;
 const MAGIC: &[u8; 4] = b"BPE\0";
 
 /// Current file format version
-const VERSION: u32 = 1;
+const VERSION: u32 = 2;
+
+/// Header flag: tokens spell bytes in the byte alphabet (v2+)
+const FLAG_BYTE_LEVEL: u32 = 1;
+
+/// Header flag: word-start tokens carry a leading space (v2+)
+const FLAG_WORD_BOUNDARIES: u32 = 2;
 
 /// Saves a vocabulary to a binary file.
 ///
@@ -66,6 +75,14 @@ pub fn save(vocab: &Vocabulary, path: &Path) -> io::Result<()> {
     // Write header
     writer.write_all(MAGIC)?;
     write_u32(&mut writer, VERSION)?;
+    let mut flags = 0;
+    if vocab.is_byte_level() {
+        flags |= FLAG_BYTE_LEVEL;
+    }
+    if vocab.has_word_boundaries() {
+        flags |= FLAG_WORD_BOUNDARIES;
+    }
+    write_u32(&mut writer, flags)?;
     write_u32(&mut writer, vocab.len() as u32)?;
     write_u32(&mut writer, vocab.pairs_count() as u32)?;
 
@@ -105,18 +122,32 @@ pub fn load(path: &Path) -> io::Result<Vocabulary> {
     }
 
     let version = read_u32(&mut reader)?;
-    if version != VERSION {
+    if version == 0 || version > VERSION {
         return Err(io::Error::new(
             io::ErrorKind::InvalidData,
             format!("unsupported version: {}", version),
         ));
     }
 
+    let flags = if version >= 2 {
+        read_u32(&mut reader)?
+    } else {
+        0
+    };
+    if flags & !(FLAG_BYTE_LEVEL | FLAG_WORD_BOUNDARIES) != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported flags: {:#x}", flags),
+        ));
+    }
+
     let vocab_size = read_u32(&mut reader)?;
     let pairs_count = read_u32(&mut reader)?;
 
     // Read tokens and build vocabulary
     let mut vocab = Vocabulary::empty(); // We need this method!
+    vocab.set_byte_level(flags & FLAG_BYTE_LEVEL != 0);
+    vocab.set_word_boundaries(flags & FLAG_WORD_BOUNDARIES != 0);
     for _id in 0..vocab_size {
         let token = read_string(&mut reader)?;
         vocab.add_token(token);
@@ -234,4 +265,49 @@ mod tests {
 
         let _ = fs::remove_file(path);
     }
+
+    #[test]
+    fn test_save_load_byte_level() {
+        use crate::bpe::{
+            encode::encode,
+            train::{TrainOptions, train_from_counts_with},
+        };
+        use std::collections::HashMap;
+
+        let freqs = HashMap::from([("über".to_string(), 3), ("uber".to_string(), 1)]);
+        let options = TrainOptions {
+            byte_level: true,
+            word_boundaries: true,
+        };
+        let vocab = train_from_counts_with(&freqs, 270, options);
+
+        let path = Path::new("/tmp/test_byte_vocab.bin");
+        save(&vocab, path).expect("save failed");
+        let loaded = load(path).expect("load failed");
+        let _ = fs::remove_file(path);
+
+        assert!(loaded.is_byte_level());
+        assert!(loaded.has_word_boundaries());
+        assert_eq!(loaded.len(), vocab.len());
+        assert_eq!(encode(&loaded, "übel"), encode(&vocab, "übel"));
+    }
+
+    #[test]
+    fn test_load_v1() {
+        // magic, version 1, no flags, one token "a", no pairs
+        let mut bytes = b"BPE\0".to_vec();
+        for v in [1u32, 1, 0, 1] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes.push(b'a');
+
+        let path = Path::new("/tmp/test_vocab_v1.bin");
+        fs::write(path, &bytes).expect("write failed");
+        let loaded = load(path).expect("load failed");
+        let _ = fs::remove_file(path);
+
+        assert!(!loaded.is_byte_level());
+        assert!(!loaded.has_word_boundaries());
+        assert_eq!(loaded.get_token(0), Some("a"));
+    }
 }
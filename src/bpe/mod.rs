@@ -2,6 +2,7 @@
 //!
 //! From-scratch implementation for multilingual text.
 
+mod bytes;
 mod decode;
 mod encode;
 mod io;
@@ -10,7 +11,9 @@ mod types;
 mod vocab;
 
 pub use decode::decode;
-pub use encode::encode;
+pub use encode::{Encoder, encode};
 pub use io::load;
 pub use io::save;
-pub use train::train;
+pub use train::{TrainOptions, train, train_from_counts, train_from_counts_with};
+pub use types::{BpeTokenId, FIRST_REGULAR_ID, UNK_ID, WORD_START_MARKER};
+pub use vocab::Vocabulary;
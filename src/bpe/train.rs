@@ -1,10 +1,16 @@
 //! BPE Training Algorithm
 //!
 //! Learns vocabulary from corpus by iteratively merging frequent pairs.
+//!
+//! Pair counts are kept in an index and updated only for the words that
+//! contain the merged pair, with a max-heap to find the next merge, so a
+//! merge costs time proportional to the words it touches rather than to
+//! the whole corpus.
 
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 
-use crate::bpe::{types::BpeTokenId, vocab::Vocabulary};
+use crate::bpe::{bytes::byte_to_char, types::BpeTokenId, vocab::Vocabulary};
 
 /// Trains a BPE vocabulary from pre-tokenized text.
 ///
@@ -49,29 +55,173 @@ pub fn train<'a, I>(pretokens: I, target_max_vocab_size: usize) -> Vocabulary
 where
     I: Iterator<Item = &'a str>,
 {
-    let mut vocab = Vocabulary::new();
-
     // Step 1: Count frequency of each unique pretoken
     let pretoken_freqs = count_pretoken_freqs(pretokens);
 
+    train_from_counts(&pretoken_freqs, target_max_vocab_size)
+}
+
+/// Trains a BPE vocabulary from pretoken frequencies.
+///
+/// Same as [`train`] for callers that count pretokens themselves, e.g. while
+/// streaming a corpus too large to hold in memory.
+pub fn train_from_counts(
+    pretoken_freqs: &HashMap<String, u32>,
+    target_max_vocab_size: usize,
+) -> Vocabulary {
+    train_from_counts_with(
+        pretoken_freqs,
+        target_max_vocab_size,
+        TrainOptions::default(),
+    )
+}
+
+/// Vocabulary options chosen at training time and stored in the vocab file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrainOptions {
+    /// Start from 256 byte tokens instead of the characters seen in the
+    /// corpus, so every input can be encoded and decoded exactly. The
+    /// target size includes these base tokens.
+    pub byte_level: bool,
+    /// Pre-tokens that follow whitespace carry a leading space, see
+    /// `PreToken::into_bpe_text`. The pretokens must already be marked.
+    pub word_boundaries: bool,
+}
+
+/// Trains a BPE vocabulary from pretoken frequencies with `options`.
+pub fn train_from_counts_with(
+    pretoken_freqs: &HashMap<String, u32>,
+    target_max_vocab_size: usize,
+    options: TrainOptions,
+) -> Vocabulary {
+    let mut vocab = if options.byte_level {
+        Vocabulary::new_byte_level()
+    } else {
+        Vocabulary::new()
+    };
+    vocab.set_word_boundaries(options.word_boundaries);
+
     // Step 2: Initialize character-level tokenization
-    let (mut sequences, freqs) = init_char_sequences(&pretoken_freqs, &mut vocab);
+    let (mut sequences, freqs) = init_char_sequences(pretoken_freqs, &mut vocab);
 
     // Step 3: Iteratively merge most frequent pairs
+    let mut index = PairIndex::new(&sequences, &freqs);
     while vocab.len() < target_max_vocab_size {
-        let pair_counts = count_pair_freqs(&sequences, &freqs);
-        if pair_counts.is_empty() {
+        let Some((left, right)) = index.pop_most_frequent() else {
             break; // No more pairs to merge
-        }
+        };
 
-        let (left, right) = find_most_frequent_pair(&pair_counts);
         let merged_id = merge_tokens(&mut vocab, left, right);
-        apply_merge(&mut sequences, left, right, merged_id);
+        index.merge(&mut sequences, &freqs, (left, right), merged_id);
     }
 
     vocab
 }
 
+/// Adjacent token pair
+type Pair = (BpeTokenId, BpeTokenId);
+
+/// Pair frequencies with the words each pair occurs in.
+///
+/// The heap orders pairs by count, then by lowest ids, the same tie-break
+/// as `find_most_frequent_pair`. Entries go stale when a count changes; a
+/// fresh entry is pushed instead and stale ones are skipped when popped.
+struct PairIndex {
+    counts: HashMap<Pair, u64>,
+    /// Indices of the words containing each pair
+    words: HashMap<Pair, Vec<usize>>,
+    heap: BinaryHeap<(u64, Reverse<Pair>)>,
+}
+
+impl PairIndex {
+    fn new(sequences: &[Vec<BpeTokenId>], freqs: &[u32]) -> Self {
+        let mut counts: HashMap<Pair, u64> = HashMap::new();
+        let mut words: HashMap<Pair, Vec<usize>> = HashMap::new();
+
+        for (w, (seq, &freq)) in sequences.iter().zip(freqs).enumerate() {
+            for window in seq.windows(2) {
+                let pair = (window[0], window[1]);
+                *counts.entry(pair).or_insert(0) += freq as u64;
+                let list = words.entry(pair).or_default();
+                if list.last() != Some(&w) {
+                    list.push(w);
+                }
+            }
+        }
+
+        let heap = counts
+            .iter()
+            .map(|(&pair, &count)| (count, Reverse(pair)))
+            .collect();
+        Self {
+            counts,
+            words,
+            heap,
+        }
+    }
+
+    /// Removes and returns the most frequent pair, or None if no pairs are left.
+    fn pop_most_frequent(&mut self) -> Option<Pair> {
+        while let Some((count, Reverse(pair))) = self.heap.pop() {
+            if self.counts.get(&pair) == Some(&count) {
+                return Some(pair);
+            }
+        }
+        None
+    }
+
+    /// Merges `pair` in every word containing it and updates the counts of
+    /// the pairs around each merge.
+    fn merge(
+        &mut self,
+        sequences: &mut [Vec<BpeTokenId>],
+        freqs: &[u32],
+        pair: Pair,
+        merged_id: BpeTokenId,
+    ) {
+        let mut changed = HashSet::new();
+
+        for w in self.words.remove(&pair).unwrap_or_default() {
+            let seq = &mut sequences[w];
+            let freq = freqs[w] as u64;
+
+            // Recount the word: small, and exact for overlapping pairs like (a, a)
+            let old_pairs: Vec<Pair> = seq.windows(2).map(|p| (p[0], p[1])).collect();
+            for &old in &old_pairs {
+                if let Some(count) = self.counts.get_mut(&old) {
+                    *count -= freq;
+                }
+                changed.insert(old);
+            }
+
+            merge_sequence(seq, pair.0, pair.1, merged_id);
+
+            for window in seq.windows(2) {
+                let new = (window[0], window[1]);
+                *self.counts.entry(new).or_insert(0) += freq;
+                changed.insert(new);
+                if !old_pairs.contains(&new) {
+                    let list = self.words.entry(new).or_default();
+                    if list.last() != Some(&w) {
+                        list.push(w);
+                    }
+                }
+            }
+        }
+
+        for pair in changed {
+            match self.counts.get(&pair) {
+                Some(0) => {
+                    self.counts.remove(&pair);
+                    self.words.remove(&pair);
+                }
+                Some(&count) => self.heap.push((count, Reverse(pair))),
+                None => {}
+            }
+        }
+    }
+}
+
 /// Counts frequency of each unique pretoken in the corpus.
 ///
 /// # Example
@@ -98,7 +248,9 @@ where
 /// Initializes character-level token sequences from pretokens.
 ///
 /// Each character becomes a separate token ID. Populates the vocabulary
-/// with all unique characters encountered.
+/// with all unique characters encountered. Pretokens are visited in sorted
+/// order, so IDs do not depend on hash order. A byte-level vocabulary
+/// already holds every byte token, and pretokens are split into bytes.
 ///
 /// # Example
 ///
@@ -118,11 +270,22 @@ fn init_char_sequences(
     let mut sequences = Vec::with_capacity(pretoken_freqs.len());
     let mut freqs = Vec::with_capacity(pretoken_freqs.len());
 
-    for (pretoken, &freq) in pretoken_freqs {
-        let token_ids: Vec<BpeTokenId> = pretoken
-            .chars()
-            .map(|ch| vocab.add_token(ch.to_string()))
-            .collect();
+    let mut pretokens: Vec<(&String, &u32)> = pretoken_freqs.iter().collect();
+    pretokens.sort_unstable();
+
+    let mut utf8 = [0u8; 4];
+    for (pretoken, &freq) in pretokens {
+        let token_ids: Vec<BpeTokenId> = if vocab.is_byte_level() {
+            pretoken
+                .bytes()
+                .map(|b| vocab.get_id(byte_to_char(b).encode_utf8(&mut utf8)))
+                .collect()
+        } else {
+            pretoken
+                .chars()
+                .map(|ch| vocab.add_token(ch.to_string()))
+                .collect()
+        };
 
         if !token_ids.is_empty() {
             sequences.push(token_ids);
@@ -135,6 +298,8 @@ fn init_char_sequences(
 
 /// Counts adjacent token pair frequencies across all sequences.
 ///
+/// Used by the reference trainer that the differential tests compare against.
+///
 /// # Example
 ///
 /// ```text
@@ -150,6 +315,7 @@ fn init_char_sequences(
 ///   { (7,  4) : 2, (4,  11): 2, (11, 11): 2, (11, 14): 2,
 ///     (22, 14): 1, (14, 17): 1, (17, 11): 1, (11, 3): 1 }
 /// ```
+#[cfg(test)]
 fn count_pair_freqs(
     sequences: &[Vec<BpeTokenId>],
     freqs: &[u32],
@@ -166,14 +332,15 @@ fn count_pair_freqs(
     pair_counts
 }
 
-/// Finds the most frequent token pair.
+/// Finds the most frequent token pair, the one with the lowest IDs on a tie.
 /// Panics if `pair_counts` is empty.
+#[cfg(test)]
 fn find_most_frequent_pair(
     pair_counts: &HashMap<(BpeTokenId, BpeTokenId), u64>,
 ) -> (BpeTokenId, BpeTokenId) {
     pair_counts
         .iter()
-        .max_by_key(|((_l, _r), count)| *count)
+        .max_by_key(|&(&pair, &count)| (count, Reverse(pair)))
         .map(|(&pair, _count)| pair)
         .expect("pair_counts should not be empty")
 }
@@ -224,6 +391,7 @@ fn merge_tokens(vocab: &mut Vocabulary, left: BpeTokenId, right: BpeTokenId) ->
 /// After:
 /// - sequences = [[7, 4, 256, 14], [7, 4, 11, 15]]
 /// ```
+#[cfg(test)]
 fn apply_merge(
     sequences: &mut [Vec<BpeTokenId>],
     left: BpeTokenId,
@@ -231,23 +399,59 @@ fn apply_merge(
     merged_id: BpeTokenId,
 ) {
     for seq in sequences {
-        let mut i = 0;
-        while i + 1 < seq.len() {
-            if seq[i] == left && seq[i + 1] == right {
-                seq[i] = merged_id;
-                seq.remove(i + 1);
-                // Don't increment i: new token might form another pair
-            } else {
-                i += 1;
-            }
+        merge_sequence(seq, left, right, merged_id);
+    }
+}
+
+/// Applies a merge rule to one sequence in-place, left to right.
+pub(super) fn merge_sequence(
+    seq: &mut Vec<BpeTokenId>,
+    left: BpeTokenId,
+    right: BpeTokenId,
+    merged_id: BpeTokenId,
+) {
+    let mut write = 0;
+    let mut read = 0;
+    while read < seq.len() {
+        if read + 1 < seq.len() && seq[read] == left && seq[read + 1] == right {
+            seq[write] = merged_id;
+            read += 2;
+        } else {
+            seq[write] = seq[read];
+            read += 1;
         }
+        write += 1;
     }
+    seq.truncate(write);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Reference trainer: recounts every pair for every merge.
+    fn train_naive(pretoken_freqs: &HashMap<String, u32>, target: usize) -> Vocabulary {
+        let mut vocab = Vocabulary::new();
+        let (mut sequences, freqs) = init_char_sequences(pretoken_freqs, &mut vocab);
+
+        while vocab.len() < target {
+            let pair_counts = count_pair_freqs(&sequences, &freqs);
+            if pair_counts.is_empty() {
+                break;
+            }
+            let (left, right) = find_most_frequent_pair(&pair_counts);
+            let merged_id = merge_tokens(&mut vocab, left, right);
+            apply_merge(&mut sequences, left, right, merged_id);
+        }
+
+        vocab
+    }
+
+    fn assert_same_vocab(a: &Vocabulary, b: &Vocabulary) {
+        assert_eq!(a.iter().collect::<Vec<_>>(), b.iter().collect::<Vec<_>>());
+        assert_eq!(a.pairs(), b.pairs());
+    }
+
     #[test]
     fn test_count_pretoken_freqs_basic() {
         let pretokens = ["hello", "world", "hello", "hello"];
@@ -517,6 +721,64 @@ mod tests {
         assert!(has_aa, "Expected 'aa' to be merged");
     }
 
+    #[test]
+    fn test_train_from_counts_matches_train() {
+        let pretokens = ["aab", "aab", "aab", "aac"];
+        let vocab = train(pretokens.into_iter(), 20);
+
+        let freqs = HashMap::from([("aab".to_string(), 3), ("aac".to_string(), 1)]);
+        let from_counts = train_from_counts(&freqs, 20);
+
+        assert_eq!(vocab.len(), from_counts.len());
+        assert_eq!(vocab.pairs_count(), from_counts.pairs_count());
+        assert!(from_counts.contains("aa"));
+    }
+
+    #[test]
+    fn test_train_matches_naive_trainer() {
+        // Pseudo-random words over a small alphabet: many ties and repeated letters
+        let alphabet: Vec<char> = "aabbcdeé你".chars().collect();
+        let mut state = 12345u64;
+        let mut next = move |n: usize| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize % n
+        };
+
+        let mut freqs: HashMap<String, u32> = HashMap::new();
+        for _ in 0..1500 {
+            let len = 1 + next(9);
+            let word: String = (0..len).map(|_| alphabet[next(alphabet.len())]).collect();
+            *freqs.entry(word).or_insert(0) += 1 + next(3) as u32;
+        }
+
+        for target in [10, 100, 1000] {
+            let fast = train_from_counts(&freqs, target);
+            assert_same_vocab(&fast, &train_naive(&freqs, target));
+        }
+    }
+
+    #[test]
+    fn test_train_ties_prefer_lowest_ids() {
+        let freqs = HashMap::from([
+            ("cd".to_string(), 1),
+            ("ab".to_string(), 1),
+            ("aaaa".to_string(), 1),
+        ]);
+        let vocab = train_from_counts(&freqs, 100);
+        assert_same_vocab(&vocab, &train_naive(&freqs, 100));
+
+        // (a, a) occurs 3 times; the remaining ties go to the lowest ids, and
+        // "aa" is added after the characters of all three words
+        let merged: Vec<&str> = vocab
+            .pairs()
+            .iter()
+            .map(|p| vocab.get_token(p.id).unwrap())
+            .collect();
+        assert_eq!(merged, vec!["aa", "ab", "cd", "aaaa"]);
+    }
+
     #[test]
     fn test_train_unicode() {
         let pretokens = ["你好", "你好", "世界"];
@@ -526,4 +788,21 @@ mod tests {
         // 4 special + 你 + 好 + 世 + 界 = 8 base tokens
         assert!(vocab.len() >= 8);
     }
+
+    #[test]
+    fn test_train_byte_level() {
+        let freqs = HashMap::from([("你好".to_string(), 3), ("好".to_string(), 2)]);
+        let options = TrainOptions {
+            byte_level: true,
+            ..Default::default()
+        };
+        let vocab = train_from_counts_with(&freqs, 262, options);
+
+        // 4 special + 256 bytes, then 好 (3 bytes) needs two merges
+        assert!(vocab.is_byte_level());
+        assert_eq!(vocab.len(), 262);
+        assert_eq!(vocab.pairs_count(), 2);
+        assert!(vocab.get_text_id("好").is_some());
+        assert!(vocab.get_text_id("你").is_none());
+    }
 }
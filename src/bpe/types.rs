@@ -15,6 +15,9 @@ pub const BOS_TOKEN: &str = "[BOS]";
 /// End of Sequence marker, indicates where a sentence ends
 pub const EOS_TOKEN: &str = "[EOS]";
 
+/// Shown in place of the leading space of word-start tokens, as in GPT-2
+pub const WORD_START_MARKER: char = '\u{120}';
+
 /// Reserved IDs for special tokens
 pub const UNK_ID: BpeTokenId = 0;
 pub const PAD_ID: BpeTokenId = 1;
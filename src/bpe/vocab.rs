@@ -4,6 +4,7 @@
 
 use std::collections::HashMap;
 
+use crate::bpe::bytes::{BYTE_TOKENS, byte_to_char, push_token_bytes, to_token_string};
 use crate::bpe::types::{BOS_TOKEN, BpePair, BpeTokenId, EOS_TOKEN, PAD_TOKEN, UNK_ID, UNK_TOKEN};
 
 /// BPE Vocabulary with bidirectional lookup
@@ -12,6 +13,12 @@ pub struct Vocabulary {
     token_to_id: HashMap<String, BpeTokenId>,
     id_to_token: Vec<String>,
     pairs: Vec<BpePair>,
+    /// (left, right) -> (rank, merged id); rank is the index in `pairs`
+    ranks: HashMap<(BpeTokenId, BpeTokenId), (u32, BpeTokenId)>,
+    /// Tokens spell UTF-8 bytes in the byte alphabet instead of characters
+    byte_level: bool,
+    /// Word-start tokens carry a leading space
+    word_boundaries: bool,
 }
 
 impl Vocabulary {
@@ -20,6 +27,9 @@ impl Vocabulary {
             token_to_id: HashMap::new(),
             id_to_token: Vec::new(),
             pairs: Vec::new(),
+            ranks: HashMap::new(),
+            byte_level: false,
+            word_boundaries: false,
         };
 
         // Register special tokens
@@ -31,6 +41,36 @@ impl Vocabulary {
         vocab
     }
 
+    /// Creates a byte-level vocabulary: the special tokens followed by one
+    /// token per byte value, in byte order.
+    pub fn new_byte_level() -> Self {
+        let mut vocab = Self::new();
+        vocab.byte_level = true;
+        for b in 0..BYTE_TOKENS {
+            vocab.add_token(byte_to_char(b as u8).to_string());
+        }
+        vocab
+    }
+
+    /// Returns true if tokens are made of bytes rather than characters.
+    pub fn is_byte_level(&self) -> bool {
+        self.byte_level
+    }
+
+    pub(crate) fn set_byte_level(&mut self, byte_level: bool) {
+        self.byte_level = byte_level;
+    }
+
+    /// Returns true if pre-tokens that follow whitespace are encoded with a
+    /// leading space, so decoding restores the spaces between words.
+    pub fn has_word_boundaries(&self) -> bool {
+        self.word_boundaries
+    }
+
+    pub(crate) fn set_word_boundaries(&mut self, word_boundaries: bool) {
+        self.word_boundaries = word_boundaries;
+    }
+
     /// Returns vocabulary size (number of tokens).
     pub fn len(&self) -> usize {
         self.id_to_token.len()
@@ -65,10 +105,60 @@ impl Vocabulary {
         self.id_to_token.get(id as usize).map(|s| s.as_str())
     }
 
+    /// Looks up the token for a piece of text, e.g. a word typed by the user.
+    /// Unlike `get_id_opt`, this spells the text in the byte alphabet first
+    /// for byte-level vocabularies, and prefers the word-start token of a
+    /// word when the vocabulary has word boundaries.
+    pub fn get_text_id(&self, text: &str) -> Option<BpeTokenId> {
+        let lookup = |text: &str| {
+            if self.byte_level {
+                self.get_id_opt(&to_token_string(text))
+            } else {
+                self.get_id_opt(text)
+            }
+        };
+        if self.word_boundaries
+            && !text.starts_with(' ')
+            && let Some(id) = lookup(&format!(" {}", text))
+        {
+            return Some(id);
+        }
+        lookup(text)
+    }
+
+    /// Returns the text a token stands for, for display.
+    /// Byte tokens that end inside a UTF-8 sequence show U+FFFD.
+    pub fn get_text(&self, id: BpeTokenId) -> Option<String> {
+        let bytes = self.get_bytes(id)?;
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Returns the bytes a token stands for: the UTF-8 of its text, which
+    /// for byte-level tokens may end inside a character.
+    pub fn get_bytes(&self, id: BpeTokenId) -> Option<Vec<u8>> {
+        let token = self.get_token(id)?;
+        if !self.byte_level {
+            return Some(token.as_bytes().to_vec());
+        }
+        let mut bytes = Vec::with_capacity(token.len());
+        push_token_bytes(token, &mut bytes);
+        Some(bytes)
+    }
+
+    /// Appends a merge rule with the lowest priority so far.
+    /// If the same (left, right) pair was already added, the earlier rule wins.
     pub fn add_pair(&mut self, left: BpeTokenId, right: BpeTokenId, id: BpeTokenId) {
+        let rank = self.pairs.len() as u32;
+        self.ranks.entry((left, right)).or_insert((rank, id));
         self.pairs.push(BpePair::new(left, right, id))
     }
 
+    /// Returns the (rank, merged id) of the merge rule for `left` + `right`.
+    /// Lower ranks are applied first.
+    pub fn merge_rank(&self, left: BpeTokenId, right: BpeTokenId) -> Option<(u32, BpeTokenId)> {
+        self.ranks.get(&(left, right)).copied()
+    }
+
     /// Returns BPE pairs in priority order.
     pub fn pairs(&self) -> &[BpePair] {
         &self.pairs
@@ -97,6 +187,9 @@ impl Vocabulary {
             token_to_id: HashMap::new(),
             id_to_token: Vec::new(),
             pairs: Vec::new(),
+            ranks: HashMap::new(),
+            byte_level: false,
+            word_boundaries: false,
         }
     }
 }
@@ -162,6 +255,21 @@ mod tests {
         assert_eq!(pair.left, a);
         assert_eq!(pair.right, b);
         assert_eq!(pair.id, ab);
+        assert_eq!(vocab.merge_rank(a, b), Some((0, ab)));
+        assert_eq!(vocab.merge_rank(b, a), None);
+    }
+
+    #[test]
+    fn test_byte_level() {
+        let vocab = Vocabulary::new_byte_level();
+        assert!(vocab.is_byte_level());
+        assert_eq!(vocab.len(), FIRST_REGULAR_ID as usize + BYTE_TOKENS);
+        assert_eq!(vocab.get_id(UNK_TOKEN), UNK_ID);
+
+        let newline = vocab.get_text_id("\n").unwrap();
+        assert_eq!(vocab.get_text(newline).as_deref(), Some("\n"));
+        assert_eq!(vocab.get_text_id("a"), vocab.get_id_opt("a"));
+        assert_eq!(vocab.get_text_id("é"), None); // two bytes, not merged
     }
 
     #[test]
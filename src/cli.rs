@@ -7,8 +7,13 @@ use std::env;
 pub enum SubCommand {
     Train,
     Embed,
+    EmbedText,
     Similar,
     Analogy,
+    Index,
+    Repl,
+    Serve,
+    Export,
     BpeTrain,
     BpeEncode,
     Info,
@@ -34,8 +39,13 @@ impl Args {
         let cmd = match cmd.as_deref() {
             Some("train") => SubCommand::Train,
             Some("embed") => SubCommand::Embed,
+            Some("embed-text") => SubCommand::EmbedText,
             Some("similar") => SubCommand::Similar,
             Some("analogy") => SubCommand::Analogy,
+            Some("index") => SubCommand::Index,
+            Some("repl") => SubCommand::Repl,
+            Some("serve") => SubCommand::Serve,
+            Some("export") => SubCommand::Export,
             Some("bpe-train") => SubCommand::BpeTrain,
             Some("bpe-encode") => SubCommand::BpeEncode,
             Some("info") => SubCommand::Info,
@@ -58,6 +68,74 @@ pub fn get_arg(args: &[String], key: &str) -> Option<String> {
         .and_then(|i| args.get(i + 1).cloned())
 }
 
+/// Collects every value of a repeatable option.
+///
+/// Takes the arguments after each occurrence of `key` up to the next
+/// `--option`, so both `--input a b` and `--input a --input b` work.
+pub fn get_arg_values(args: &[String], key: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == key {
+            let taken = args[i + 1..]
+                .iter()
+                .take_while(|a| !a.starts_with("--"))
+                .count();
+            values.extend_from_slice(&args[i + 1..i + 1 + taken]);
+            i += taken;
+        }
+        i += 1;
+    }
+    values
+}
+
+/// Reads the non-empty lines of a file, trimmed (e.g. a `--queries-file`).
+pub fn read_lines(path: &str) -> Result<Vec<String>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Splits a command line into words on whitespace. A double or single
+/// quote at the start of a word groups words up to the closing quote
+/// (`tok "some text"`) and is removed; quotes inside a word (`don't`)
+/// are kept.
+pub fn split_line(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+
+    for ch in line.chars() {
+        match quote {
+            Some(q) if ch == q => quote = None,
+            Some(_) => current.push(ch),
+            None if !in_word && (ch == '"' || ch == '\'') => {
+                quote = Some(ch);
+                in_word = true;
+            }
+            None if ch.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            None => {
+                current.push(ch);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
 /// Check if flag is present
 pub fn has_flag(args: &[String], flag: &str) -> bool {
     args.iter().any(|a| a == flag)
@@ -74,8 +152,13 @@ pub fn print_help() {
   COMMANDS:
       train       Train word vectors on a corpus
       embed       Get embedding for text
+      embed-text  Pool sentence or document vectors
       similar     Find similar words
       analogy     Solve word analogies
+      index       Build a nearest-neighbour index
+      repl        Query a model interactively
+      serve       Serve queries over HTTP
+      export      Export vectors for other tools
       bpe-train   Train BPE tokenizer
       bpe-encode  Encode text with BPE
       info        Show model information
@@ -84,3 +167,19 @@ pub fn print_help() {
   Run 'wvec <COMMAND> --help' for more information on a command."
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_line() {
+        assert_eq!(split_line("  sim  king "), vec!["sim", "king"]);
+        assert_eq!(
+            split_line(r#"tok "some text" 'a b'c don't"#),
+            vec!["tok", "some text", "a bc", "don't"]
+        );
+        assert_eq!(split_line(r#"dist "" x"#), vec!["dist", "", "x"]);
+        assert!(split_line("   ").is_empty());
+    }
+}
@@ -1,8 +1,11 @@
 //! analogy command: Solve word analogies (king - man + woman = ?)
 
-use crate::bpe::load as load_vocab;
-use crate::cli::{get_arg, has_flag};
-use crate::ffi::{self, wvec_checkpoint_load, wvec_get_embedding, wvec_model_free};
+use crate::bpe::{Vocabulary, load as load_vocab};
+use crate::cli::{get_arg, has_flag, read_lines};
+use crate::cmd::similar::{describe_pieces, load_index, nearest, print_neighbours, word_vector};
+use crate::compose::{Compose, query_tokens};
+use crate::ffi::{self, wvec_checkpoint_load, wvec_model_free};
+use crate::hnsw::DEFAULT_EF_SEARCH;
 use std::ffi::c_int;
 use std::path::Path;
 
@@ -13,23 +16,51 @@ pub fn run(args: &[String]) -> Result<(), String> {
     }
 
     let model_path = get_arg(args, "--model").ok_or("Missing --model <file>")?;
-    let query = get_arg(args, "--query").ok_or("Missing --query <expr>")?;
+    let mut expressions: Vec<String> = get_arg(args, "--query").into_iter().collect();
+    if let Some(path) = get_arg(args, "--queries-file") {
+        expressions.extend(read_lines(&path)?);
+    }
+    if expressions.is_empty() {
+        return Err("Missing --query <expr> or --queries-file <file>".to_string());
+    }
     let topk: usize = get_arg(args, "--topk")
         .unwrap_or_else(|| "5".to_string())
         .parse()
         .map_err(|_| "Invalid --topk")?;
+    let method = match get_arg(args, "--compose") {
+        Some(name) => Compose::parse(&name).ok_or("Invalid --compose (expected mean or sum)")?,
+        None => Compose::default(),
+    };
+    let ef: usize = get_arg(args, "--ef")
+        .unwrap_or_else(|| DEFAULT_EF_SEARCH.to_string())
+        .parse()
+        .map_err(|_| "Invalid --ef")?;
 
-    // Parse query: "king - man + woman" -> [(king, +1), (man, -1), (woman, +1)]
-    let terms = parse_query(&query)?;
-    if terms.is_empty() {
-        return Err("Empty query".to_string());
-    }
+    // Use the HNSW index built for this model, unless asked for exact search
+    let index_path = format!("{}.hnsw", model_path);
+    let use_index = !has_flag(args, "--exact") && Path::new(&index_path).exists();
 
-    // Load vocab and model
+    // Load vocab
     let vocab_path = format!("{}.vocab", model_path);
     let vocab =
         load_vocab(Path::new(&vocab_path)).map_err(|e| format!("Cannot load vocab: {}", e))?;
 
+    // Parse each query: "king - man + woman" -> [(king, +1), (man, -1), (woman, +1)]
+    // and find the tokens of its words
+    let single = expressions.len() == 1;
+    let mut queries: Vec<Query> = Vec::new();
+    for expr in expressions {
+        match resolve_query(&vocab, &expr) {
+            Ok(terms) => queries.push(Query { expr, terms }),
+            Err(e) if single => return Err(e),
+            Err(e) => eprintln!("Skipping '{}': {}", expr, e),
+        }
+    }
+    if queries.is_empty() {
+        return Err("No query could be resolved".to_string());
+    }
+
+    // Load model
     let mut epoch: c_int = 0;
     let mut lr: f32 = 0.0;
     unsafe {
@@ -50,70 +81,144 @@ pub fn run(args: &[String]) -> Result<(), String> {
         ffi::wvec_model_get_dims(&mut vocab_size, &mut dim);
     }
 
-    // Build query vector
-    let mut query_vec = vec![0.0f32; dim as usize];
-    let mut query_word_ids: Vec<u32> = Vec::new();
-
-    for (word, sign) in &terms {
-        let word_id = vocab
-            .get_id_opt(word)
-            .ok_or(format!("Word '{}' not in vocabulary", word))?;
-        query_word_ids.push(word_id);
-
-        let mut emb = vec![0.0f32; dim as usize];
-        unsafe {
-            let status = wvec_get_embedding(word_id as c_int, emb.as_mut_ptr(), dim);
-            if status != ffi::status::SUCCESS {
-                wvec_model_free();
-                return Err(format!("Cannot get embedding for '{}': {}", word, status));
-            }
-        }
+    let results = search(
+        &queries,
+        dim,
+        method,
+        topk,
+        use_index.then_some((index_path.as_str(), ef)),
+    );
+    unsafe {
+        wvec_model_free();
+    }
+    let results = results?;
 
-        for (q, e) in query_vec.iter_mut().zip(emb.iter()) {
-            *q += sign * e;
+    // Print results
+    for (i, (query, neighbours)) in queries.iter().zip(&results).enumerate() {
+        if i > 0 {
+            println!();
         }
+        println!("Analogy: {}", query.expr);
+        print_terms(&vocab, &query.terms, method);
+        println!("Results:");
+        print_neighbours(&vocab, neighbours);
     }
 
-    // Find most similar (excluding query words)
-    let mut similarities: Vec<(u32, f32)> = Vec::new();
-    let mut other_emb = vec![0.0f32; dim as usize];
+    Ok(())
+}
 
-    for id in 0..vocab_size as u32 {
-        if query_word_ids.contains(&id) {
-            continue;
-        }
+/// A word of an analogy expression with its sign and tokens
+pub(crate) type Term = (String, f32, Vec<u32>);
 
-        unsafe {
-            let status = wvec_get_embedding(id as c_int, other_emb.as_mut_ptr(), dim);
-            if status != ffi::status::SUCCESS {
-                continue;
-            }
-        }
+/// An analogy expression and its terms
+struct Query {
+    expr: String,
+    terms: Vec<Term>,
+}
 
-        let sim = cosine_similarity(&query_vec, &other_emb);
-        similarities.push((id, sim));
+/// Parses an expression and looks up the tokens of its words.
+pub(crate) fn resolve_query(vocab: &Vocabulary, expr: &str) -> Result<Vec<Term>, String> {
+    let terms = parse_query(expr)?;
+    if terms.is_empty() {
+        return Err("Empty query".to_string());
     }
+    resolve_terms(vocab, terms)
+}
 
-    similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+/// Looks up the tokens of the words of a parsed expression.
+pub(crate) fn resolve_terms(
+    vocab: &Vocabulary,
+    terms: Vec<(String, f32)>,
+) -> Result<Vec<Term>, String> {
+    terms
+        .into_iter()
+        .map(|(word, sign)| {
+            let ids = query_tokens(vocab, &word);
+            if ids.is_empty() {
+                Err(format!("Word '{}' not in vocabulary", word))
+            } else {
+                Ok((word, sign, ids))
+            }
+        })
+        .collect()
+}
 
-    unsafe {
-        wvec_model_free();
+/// Builds the vector of every query and finds their nearest neighbours in
+/// one batch, leaving out the query words.
+fn search(
+    queries: &[Query],
+    dim: c_int,
+    method: Compose,
+    topk: usize,
+    index: Option<(&str, usize)>,
+) -> Result<Vec<Vec<(u32, f32)>>, String> {
+    let mut vectors = Vec::with_capacity(queries.len() * dim as usize);
+    let mut exclude = Vec::with_capacity(queries.len());
+
+    for query in queries {
+        let (query_vec, query_word_ids) = query_vector(&query.terms, dim, method)?;
+        vectors.extend(query_vec);
+        exclude.push(query_word_ids);
     }
 
-    // Print results
-    println!("Analogy: {}", query);
-    println!("Results:");
-    for (id, sim) in similarities.iter().take(topk) {
-        if let Some(token) = vocab.get_token(*id) {
-            println!("  {:>6.4}  {}", sim, token);
+    let index = match index {
+        Some((path, ef)) => load_index(path, dim as usize)?.map(|index| (index, ef)),
+        None => None,
+    };
+    nearest(
+        &vectors,
+        dim as usize,
+        topk,
+        &exclude,
+        index.as_ref().map(|(index, ef)| (index, *ef)),
+    )
+}
+
+/// Adds up the signed word vectors of an expression. Returns the vector
+/// and the tokens of all its words. The model must be loaded.
+pub(crate) fn query_vector(
+    terms: &[Term],
+    dim: c_int,
+    method: Compose,
+) -> Result<(Vec<f32>, Vec<u32>), String> {
+    combine_terms(terms, dim as usize, |word, ids| {
+        word_vector(word, ids, dim, method)
+    })
+}
+
+/// Adds up the signed word vectors of an expression, each from
+/// `word_vector(word, ids)`. Returns the vector and the tokens of all its
+/// words.
+pub(crate) fn combine_terms<E>(
+    terms: &[Term],
+    dim: usize,
+    mut word_vector: impl FnMut(&str, &[u32]) -> Result<Vec<f32>, E>,
+) -> Result<(Vec<f32>, Vec<u32>), E> {
+    let mut query_vec = vec![0.0f32; dim];
+    let mut query_word_ids: Vec<u32> = Vec::new();
+
+    for (word, sign, ids) in terms {
+        query_word_ids.extend(ids);
+        let emb = word_vector(word, ids)?;
+        for (q, e) in query_vec.iter_mut().zip(emb.iter()) {
+            *q += sign * e;
         }
     }
 
-    Ok(())
+    Ok((query_vec, query_word_ids))
+}
+
+/// Shows the subword tokens of the composed words of an expression.
+pub(crate) fn print_terms(vocab: &Vocabulary, terms: &[Term], method: Compose) {
+    for (word, _, ids) in terms {
+        if let Some(pieces) = describe_pieces(vocab, ids, method) {
+            println!("  '{}' {}", word, pieces);
+        }
+    }
 }
 
 /// Parse "king - man + woman" into [(king, 1.0), (man, -1.0), (woman, 1.0)]
-fn parse_query(query: &str) -> Result<Vec<(String, f32)>, String> {
+pub(crate) fn parse_query(query: &str) -> Result<Vec<(String, f32)>, String> {
     let mut terms = Vec::new();
     let mut sign = 1.0f32;
 
@@ -131,24 +236,13 @@ fn parse_query(query: &str) -> Result<Vec<(String, f32)>, String> {
     Ok(terms)
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-
-    if norm_a > 0.0 && norm_b > 0.0 {
-        dot / (norm_a * norm_b)
-    } else {
-        0.0
-    }
-}
-
 fn print_help() {
     println!(
         "wvec analogy - Solve word analogies
 
   USAGE:
-      wvec analogy --model <file> --query <expr>
+      wvec analogy --model <file> --query <expr> [OPTIONS]
+      wvec analogy --model <file> --queries-file <file> [OPTIONS]
 
   EXAMPLES:
       wvec analogy --model model.bin --query \"king - man + woman\"
@@ -156,7 +250,12 @@ fn print_help() {
   OPTIONS:
       --model <file>       Path to trained model
       --query <expr>       Analogy expression (e.g., \"king - man + woman\")
+      --queries-file <f>   Analogy expressions, one per line
       --topk <n>           Number of results (default: 5)
+      --compose <m>        Combine the subword vectors of a word that is not
+                           a single token: mean or sum (default: mean)
+      --ef <n>             Search width with an HNSW index (default: 64)
+      --exact              Search all words even if <model>.hnsw exists
       -h, --help           Show this help message"
     );
 }
@@ -1,6 +1,6 @@
 //! bpe-encode command: Encode text using BPE vocabulary
 
-use crate::bpe::{encode, load as load_vocab};
+use crate::bpe::{decode, encode, load as load_vocab};
 use crate::cli::{get_arg, has_flag};
 use crate::text::normalize::normalize;
 use crate::text::pretokenize::pretokenize;
@@ -30,8 +30,9 @@ pub fn run(args: &[String]) -> Result<(), String> {
 
     let mut all_ids = Vec::new();
     for pt in pretokens {
-        let ids = encode(&vocab, &pt.text);
-        print!("  '{}' -> [", pt.text);
+        let text = pt.into_bpe_text(vocab.has_word_boundaries());
+        let ids = encode(&vocab, &text);
+        print!("  '{}' -> [", text);
         for (i, &id) in ids.iter().enumerate() {
             if i > 0 {
                 print!(", ");
@@ -44,6 +45,7 @@ pub fn run(args: &[String]) -> Result<(), String> {
 
     println!("\nAll token IDs: {:?}", all_ids);
     println!("Total: {} tokens", all_ids.len());
+    println!("Decoded: {}", decode(&vocab, &all_ids));
 
     Ok(())
 }
@@ -1,10 +1,10 @@
 //! bpe-train command: Train BPE tokenizer from corpus
 
-use crate::bpe::{save, train};
-use crate::cli::{get_arg, has_flag};
-use crate::input::text::TextReader;
-use crate::text::normalize::normalize;
-use crate::text::pretokenize::pretokenize;
+use crate::bpe::{TrainOptions, save, train_from_counts_with};
+use crate::cli::{get_arg, get_arg_values, has_flag};
+use crate::input::corpus::{Format, MultiPretokens};
+use crate::input::files::expand_inputs;
+use std::collections::HashMap;
 use std::path::Path;
 
 pub fn run(args: &[String]) -> Result<(), String> {
@@ -14,35 +14,71 @@ pub fn run(args: &[String]) -> Result<(), String> {
         return Ok(());
     }
 
-    let input = get_arg(args, "--input").ok_or("Missing --input <file>")?;
+    let inputs = get_arg_values(args, "--input");
+    if inputs.is_empty() {
+        return Err("Missing --input <path>...".to_string());
+    }
     let output = get_arg(args, "--output").ok_or("Missing --output <file>")?;
+    let format = match get_arg(args, "--format") {
+        Some(name) => {
+            Some(Format::parse(&name).ok_or("Invalid --format (expected txt, xml or html)")?)
+        }
+        None => None,
+    };
     let vocab_size: usize = get_arg(args, "--vocab-size")
         .unwrap_or_else(|| "10000".to_string())
         .parse()
         .map_err(|_| "Invalid --vocab-size")?;
+    let options = TrainOptions {
+        byte_level: has_flag(args, "--byte-level"),
+        word_boundaries: has_flag(args, "--word-boundaries"),
+    };
 
     eprintln!("Training BPE tokenizer...");
-    eprintln!("  Input: {}", input);
+    let files = expand_inputs(&inputs);
+    match files.as_slice() {
+        [] => return Err("No input files found".to_string()),
+        [file] => eprintln!(
+            "  Input: {} ({:?})",
+            file.display(),
+            format.unwrap_or_else(|| Format::detect(file))
+        ),
+        _ => eprintln!("  Input: {} files", files.len()),
+    }
     eprintln!("  Output: {}", output);
     eprintln!("  Vocab size: {}", vocab_size);
+    if options.byte_level {
+        eprintln!("  Byte-level: 256 base tokens");
+    }
+    if options.word_boundaries {
+        eprintln!("  Word boundaries: leading space on word starts");
+    }
 
-    // Read and preprocess input
-    let reader = TextReader::open(&input).map_err(|e| format!("Cannot open {}: {}", input, e))?;
-
-    // Collect pre-tokens
-    let mut pretokens: Vec<String> = Vec::new();
-    for line_result in reader {
-        let line = line_result.map_err(|e| format!("Read error: {}", e))?;
-        let normalized = normalize(&line);
-        for pt in pretokenize(&normalized) {
-            pretokens.push(pt.text.to_string());
+    // Read, preprocess and count pre-tokens
+    let mut freqs: HashMap<String, u32> = HashMap::new();
+    let mut file_pretokens = vec![0u64; files.len()];
+    for (file, pt) in
+        MultiPretokens::new(&files, format).with_word_boundaries(options.word_boundaries)
+    {
+        file_pretokens[file] += 1;
+        let count = freqs.entry(pt).or_insert(0);
+        *count = count.saturating_add(1);
+    }
+    if files.len() > 1 {
+        for (file, n) in files.iter().zip(&file_pretokens) {
+            eprintln!("    {}: {} pre-tokens", file.display(), n);
         }
     }
+    let total: u64 = file_pretokens.iter().sum();
 
-    eprintln!("  Collected {} pre-tokens", pretokens.len());
+    eprintln!(
+        "  Collected {} pre-tokens ({} distinct)",
+        total,
+        freqs.len()
+    );
 
     // Train BPE
-    let vocab = train(pretokens.iter().map(|s| s.as_str()), vocab_size);
+    let vocab = train_from_counts_with(&freqs, vocab_size, options);
     eprintln!("  Vocabulary: {} tokens", vocab.len());
 
     // Save vocabulary
@@ -57,12 +93,21 @@ fn print_help() {
         "wvec bpe-train - Train BPE tokenizer
 
   USAGE:
-      wvec bpe-train --input <file> --output <file> [OPTIONS]
+      wvec bpe-train --input <path>... --output <file> [OPTIONS]
 
   OPTIONS:
-      --input <file>       Input text file
+      --input <path>...    Input corpus files, directories (read recursively)
+                           or globs such as 'dump/*.xml'; .gz and .bz2
+                           files are decompressed on the fly; '-' reads stdin
+      --format <f>         Input format: txt, xml (Wikipedia dump) or html
+                           (default: detected from each file's extension)
       --output <file>      Output vocabulary file (.bin)
       --vocab-size <n>     Target vocabulary size (default: 10000)
+      --byte-level         Start from 256 byte tokens instead of characters,
+                           so any text encodes without [UNK]
+      --word-boundaries    Encode the space before a word as part of its
+                           first token, so bpe-encode output decodes back
+                           to the spaced text
       -h, --help           Show this help message"
     );
 }
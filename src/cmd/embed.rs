@@ -2,6 +2,8 @@
 
 use crate::bpe::load as load_vocab;
 use crate::cli::{get_arg, has_flag};
+use crate::cmd::similar::print_pieces;
+use crate::compose::{Compose, compose, query_tokens};
 use crate::ffi::{self, wvec_checkpoint_load, wvec_get_embedding, wvec_model_free};
 use std::ffi::c_int;
 use std::path::Path;
@@ -14,6 +16,10 @@ pub fn run(args: &[String]) -> Result<(), String> {
 
     let model_path = get_arg(args, "--model").ok_or("Missing --model <file>")?;
     let word = get_arg(args, "--word").ok_or("Missing --word <word>")?;
+    let method = match get_arg(args, "--compose") {
+        Some(name) => Compose::parse(&name).ok_or("Invalid --compose (expected mean or sum)")?,
+        None => Compose::default(),
+    };
 
     // Derive vocab path from model path
     let vocab_path = format!("{}.vocab", model_path);
@@ -22,10 +28,11 @@ pub fn run(args: &[String]) -> Result<(), String> {
     let vocab = load_vocab(Path::new(&vocab_path))
         .map_err(|e| format!("Cannot load vocab {}: {}", vocab_path, e))?;
 
-    // Find word ID
-    let word_id = vocab
-        .get_id_opt(&word)
-        .ok_or(format!("Word '{}' not in vocabulary", word))?;
+    // Find the word's token, or the subword tokens it is composed from
+    let query_ids = query_tokens(&vocab, &word);
+    if query_ids.is_empty() {
+        return Err(format!("Word '{}' not in vocabulary", word));
+    }
 
     // Load model
     let mut epoch: c_int = 0;
@@ -50,18 +57,25 @@ pub fn run(args: &[String]) -> Result<(), String> {
     }
 
     // Get embedding
-    let mut embedding = vec![0.0f32; dim as usize];
-    unsafe {
-        let status = wvec_get_embedding(word_id as c_int, embedding.as_mut_ptr(), dim);
-        if status != ffi::status::SUCCESS {
-            wvec_model_free();
-            return Err(format!("Cannot get embedding: {}", status));
+    let embedding = compose(&query_ids, dim as usize, method, |id, out| {
+        match unsafe { wvec_get_embedding(id as c_int, out.as_mut_ptr(), dim) } {
+            ffi::status::SUCCESS => Ok(()),
+            status => Err(status),
         }
-        wvec_model_free();
-    }
+    });
+    unsafe { wvec_model_free() };
+    let embedding = embedding.map_err(|status| format!("Cannot get embedding: {}", status))?;
 
     // Print embedding
     println!("Embedding for '{}' (dim={}):", word, dim);
+    print_pieces(&vocab, &query_ids, method);
+    print_embedding(&embedding);
+
+    Ok(())
+}
+
+/// Prints a vector in brackets, eight values per line.
+pub(crate) fn print_embedding(embedding: &[f32]) {
     println!("[");
     for (i, val) in embedding.iter().enumerate() {
         if i > 0 && i % 8 == 0 {
@@ -70,8 +84,6 @@ pub fn run(args: &[String]) -> Result<(), String> {
         print!(" {:>9.6}", val);
     }
     println!("\n]");
-
-    Ok(())
 }
 
 fn print_help() {
@@ -79,11 +91,13 @@ fn print_help() {
         "wvec embed - Get embedding vector for a word
 
   USAGE:
-      wvec embed --model <file> --word <word>
+      wvec embed --model <file> --word <word> [OPTIONS]
 
   OPTIONS:
       --model <file>       Path to trained model
       --word <word>        Word to get embedding for
+      --compose <m>        Combine the subword vectors of a word that is not
+                           a single token: mean or sum (default: mean)
       -h, --help           Show this help message"
     );
 }
@@ -0,0 +1,299 @@
+//! embed-text command: Pool sentence and document vectors
+
+use crate::bpe::{Encoder, UNK_ID, Vocabulary, load as load_vocab};
+use crate::cli::{get_arg, has_flag};
+use crate::ffi::{self, wvec_checkpoint_load, wvec_get_embedding, wvec_model_free};
+use crate::input::corpus::{CorpusReader, Format};
+use crate::pooling::{
+    DEFAULT_SIF_A, Pooling, first_component, load_component, load_counts, pool, remove_component,
+    save_component, sif_weights,
+};
+use crate::text::normalize::normalize;
+use crate::text::pretokenize::pretokenize;
+use std::ffi::c_int;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Magic bytes of the binary output
+const MAGIC: &[u8; 4] = b"WVDV";
+
+/// Binary output format version
+const VERSION: u32 = 1;
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    if has_flag(args, "--help") || has_flag(args, "-h") {
+        print_help();
+        return Ok(());
+    }
+
+    let model_path = get_arg(args, "--model").ok_or("Missing --model <file>")?;
+    let text = get_arg(args, "--text");
+    let input = get_arg(args, "--input");
+    if text.is_some() == input.is_some() {
+        return Err("Expected one of --text <string> or --input <file>".to_string());
+    }
+    let pooling = match get_arg(args, "--pooling") {
+        Some(name) => Pooling::parse(&name).ok_or("Invalid --pooling (expected mean or sif)")?,
+        None => Pooling::default(),
+    };
+    let sif_a: f64 = get_arg(args, "--sif-a")
+        .unwrap_or_else(|| DEFAULT_SIF_A.to_string())
+        .parse()
+        .ok()
+        .filter(|&a: &f64| a > 0.0)
+        .ok_or("Invalid --sif-a")?;
+    let binary = match get_arg(args, "--output-format").as_deref() {
+        None | Some("tsv") => false,
+        Some("bin") => true,
+        Some(other) => {
+            return Err(format!(
+                "Invalid --output-format '{}' (expected tsv or bin)",
+                other
+            ));
+        }
+    };
+    let output = get_arg(args, "--output");
+    let component_path = get_arg(args, "--component");
+    let save_component_path = get_arg(args, "--save-component");
+    if pooling != Pooling::Sif && (component_path.is_some() || save_component_path.is_some()) {
+        return Err("--component and --save-component need --pooling sif".to_string());
+    }
+    if component_path.is_some() && save_component_path.is_some() {
+        return Err("Expected at most one of --component and --save-component".to_string());
+    }
+
+    // Load vocabulary
+    let vocab_path = format!("{}.vocab", model_path);
+    let vocab = load_vocab(Path::new(&vocab_path))
+        .map_err(|e| format!("Cannot load vocab {}: {}", vocab_path, e))?;
+
+    // SIF weights come from the token counts of the training corpus
+    let weights = match pooling {
+        Pooling::Mean => None,
+        Pooling::Sif => {
+            let counts_path =
+                get_arg(args, "--counts").unwrap_or_else(|| format!("{}.counts", model_path));
+            let counts = load_counts(Path::new(&counts_path))
+                .map_err(|e| format!("Cannot load token counts {}: {}", counts_path, e))?;
+            if counts.len() != vocab.len() {
+                return Err(format!(
+                    "Token counts cover {} tokens, vocabulary has {}",
+                    counts.len(),
+                    vocab.len()
+                ));
+            }
+            Some(sif_weights(&counts, sif_a))
+        }
+    };
+
+    // Load model
+    let mut epoch: c_int = 0;
+    let mut lr: f32 = 0.0;
+    unsafe {
+        let status = wvec_checkpoint_load(
+            model_path.as_ptr() as *const i8,
+            model_path.len() as c_int,
+            &mut epoch,
+            &mut lr,
+        );
+        if status != ffi::status::SUCCESS {
+            return Err(format!("Cannot load model: {}", status));
+        }
+    }
+
+    let mut vocab_size: c_int = 0;
+    let mut dim: c_int = 0;
+    unsafe {
+        ffi::wvec_model_get_dims(&mut vocab_size, &mut dim);
+    }
+
+    // Encode and pool each document
+    let documents: Box<dyn Iterator<Item = io::Result<String>>> = match (text, input) {
+        (Some(text), _) => Box::new(std::iter::once(Ok(text))),
+        (None, Some(path)) => match CorpusReader::open(&path, Format::Text) {
+            Ok(reader) => Box::new(reader),
+            Err(e) => {
+                unsafe { wvec_model_free() };
+                return Err(format!("Cannot open {}: {}", path, e));
+            }
+        },
+        (None, None) => unreachable!(),
+    };
+    let vectors = pool_documents(documents, &vocab, dim, weights.as_deref());
+    unsafe { wvec_model_free() };
+    let (mut vectors, empty) = vectors?;
+
+    eprintln!(
+        "Pooled {} documents ({}, dim={})",
+        vectors.len(),
+        pooling,
+        dim
+    );
+    if empty > 0 {
+        eprintln!("  Warning: {} documents have no known tokens", empty);
+    }
+
+    // Remove the component shared by all documents: a saved one, so that
+    // queries match the documents it came from, or that of this input
+    if let Some(path) = component_path {
+        let u = load_component(Path::new(&path))
+            .map_err(|e| format!("Cannot load component {}: {}", path, e))?;
+        if u.len() != dim as usize {
+            return Err(format!(
+                "Component {} has dim {}, model has {}",
+                path,
+                u.len(),
+                dim
+            ));
+        }
+        remove_component(&mut vectors, &u);
+    } else if pooling == Pooling::Sif {
+        let u = if vectors.len() < 2 {
+            None
+        } else {
+            first_component(&vectors, dim as usize)
+        };
+        if let Some(u) = u {
+            remove_component(&mut vectors, &u);
+            if let Some(path) = save_component_path {
+                save_component(&u, Path::new(&path))
+                    .map_err(|e| format!("Cannot save component {}: {}", path, e))?;
+                eprintln!("Saved common component to {}", path);
+            }
+        } else if let Some(path) = save_component_path {
+            // A later --component run would find no file, or a stale one
+            return Err(format!(
+                "No common component to save to {}: it needs 2+ documents with \
+                 nonzero vectors",
+                path
+            ));
+        } else if vectors.len() < 2 {
+            eprintln!(
+                "  Warning: common component removal needs 2+ documents or --component, skipped"
+            );
+        }
+    }
+
+    // Write vectors, one row per document
+    let writer: Box<dyn Write> = match output {
+        Some(ref path) => {
+            Box::new(File::create(path).map_err(|e| format!("Cannot create {}: {}", path, e))?)
+        }
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = BufWriter::new(writer);
+    let written = if binary {
+        write_binary(&mut writer, &vectors, dim as usize)
+    } else {
+        write_tsv(&mut writer, &vectors)
+    };
+    written
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Cannot write vectors: {}", e))?;
+
+    if let Some(path) = output {
+        eprintln!("Saved to {}", path);
+    }
+    Ok(())
+}
+
+/// Pools the vector of every document. Returns the vectors and the number
+/// of documents without any known token, which get a zero vector.
+fn pool_documents(
+    documents: impl Iterator<Item = io::Result<String>>,
+    vocab: &Vocabulary,
+    dim: c_int,
+    weights: Option<&[f32]>,
+) -> Result<(Vec<Vec<f32>>, usize), String> {
+    let mut encoder = Encoder::new(vocab);
+    let mut vectors = Vec::new();
+    let mut empty = 0;
+    let mut ids = Vec::new();
+
+    for doc in documents {
+        let doc = doc.map_err(|e| format!("Cannot read input: {}", e))?;
+
+        ids.clear();
+        for pt in pretokenize(&normalize(&doc)) {
+            let text = pt.into_bpe_text(vocab.has_word_boundaries());
+            ids.extend_from_slice(encoder.encode(&text));
+        }
+        ids.retain(|&id| id != UNK_ID);
+        if ids.is_empty() {
+            empty += 1;
+        }
+
+        let vector = pool(&ids, dim as usize, weights, |id, out| {
+            match unsafe { wvec_get_embedding(id as c_int, out.as_mut_ptr(), dim) } {
+                ffi::status::SUCCESS => Ok(()),
+                status => Err(status),
+            }
+        })
+        .map_err(|status| format!("Cannot get embedding: {}", status))?;
+        vectors.push(vector);
+    }
+
+    Ok((vectors, empty))
+}
+
+/// Writes one tab-separated row per vector.
+fn write_tsv<W: Write>(writer: &mut W, vectors: &[Vec<f32>]) -> io::Result<()> {
+    for v in vectors {
+        for (i, x) in v.iter().enumerate() {
+            if i > 0 {
+                writer.write_all(b"\t")?;
+            }
+            write!(writer, "{:.6}", x)?;
+        }
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Writes the header (magic, version, count, dim) and the vectors as
+/// little-endian f32, row by row.
+fn write_binary<W: Write>(writer: &mut W, vectors: &[Vec<f32>], dim: usize) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(vectors.len() as u32).to_le_bytes())?;
+    writer.write_all(&(dim as u32).to_le_bytes())?;
+    for v in vectors {
+        for x in v {
+            writer.write_all(&x.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn print_help() {
+    println!(
+        "wvec embed-text - Pool sentence or document vectors
+
+  USAGE:
+      wvec embed-text --model <file> --text <string> [OPTIONS]
+      wvec embed-text --model <file> --input <file> [OPTIONS]
+
+  OPTIONS:
+      --model <file>       Path to trained model
+      --text <string>      A single sentence or document
+      --input <file>       One document per line; .gz and .bz2 files are
+                           decompressed on the fly; '-' reads stdin
+      --pooling <p>        mean or sif (default: mean). SIF weights tokens by
+                           a / (a + p(token)) and removes the component
+                           shared by all documents of the input
+      --sif-a <f>          SIF smoothing parameter a (default: 1e-3)
+      --counts <file>      Token counts for SIF (default: <model>.counts,
+                           saved by train)
+      --save-component <f> Save the removed common component (SIF); an
+                           error if none can be computed (under 2 documents)
+      --component <file>   Remove a saved component instead of computing
+                           one, so queries pool like the documents it came
+                           from (SIF, works for a single --text)
+      --output-format <f>  tsv (one row per document) or bin (\"WVDV\",
+                           version, count, dim as u32, then f32 rows;
+                           little-endian) (default: tsv)
+      --output <file>      Output file (default: stdout)
+      -h, --help           Show this help message"
+    );
+}
@@ -0,0 +1,160 @@
+//! export command: Write word vectors for other tools
+
+use crate::bpe::{BpeTokenId, FIRST_REGULAR_ID, load as load_vocab};
+use crate::cli::{get_arg, has_flag};
+use crate::export::{Format, write_metadata, write_vectors};
+use crate::ffi::{self, wvec_checkpoint_load, wvec_model_free};
+use crate::pooling::load_counts;
+use std::ffi::c_int;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    if has_flag(args, "--help") || has_flag(args, "-h") {
+        print_help();
+        return Ok(());
+    }
+
+    let model_path = get_arg(args, "--model").ok_or("Missing --model <file>")?;
+    let format = match get_arg(args, "--format") {
+        Some(name) => Format::parse(&name)
+            .ok_or("Invalid --format (expected w2v-text, w2v-bin, glove or tsv)")?,
+        None => Format::W2vText,
+    };
+    let output = get_arg(args, "--output")
+        .unwrap_or_else(|| format!("{}.{}", model_path, format.extension()));
+    let metadata = if has_flag(args, "--no-metadata") {
+        None
+    } else {
+        Some(get_arg(args, "--metadata").unwrap_or_else(|| {
+            Path::new(&output)
+                .with_file_name("metadata.tsv")
+                .to_string_lossy()
+                .into_owned()
+        }))
+    };
+    let skip_special = has_flag(args, "--skip-special");
+    let add_output = has_flag(args, "--add-output");
+
+    // Output vectors of a hierarchical softmax model belong to tree nodes
+    let tree_path = format!("{}.tree", model_path);
+    if add_output && Path::new(&tree_path).exists() {
+        return Err(format!(
+            "--add-output needs a negative sampling model ({} exists, so the output \
+             vectors belong to hierarchical softmax nodes)",
+            tree_path
+        ));
+    }
+
+    // Load vocabulary
+    let vocab_path = format!("{}.vocab", model_path);
+    let vocab = load_vocab(Path::new(&vocab_path))
+        .map_err(|e| format!("Cannot load vocab {}: {}", vocab_path, e))?;
+
+    // Load model
+    let mut epoch: c_int = 0;
+    let mut lr: f32 = 0.0;
+    unsafe {
+        let status = wvec_checkpoint_load(
+            model_path.as_ptr() as *const i8,
+            model_path.len() as c_int,
+            &mut epoch,
+            &mut lr,
+        );
+        if status != ffi::status::SUCCESS {
+            return Err(format!("Cannot load model: {}", status));
+        }
+    }
+
+    let mut vocab_size: c_int = 0;
+    let mut dim: c_int = 0;
+    unsafe {
+        ffi::wvec_model_get_dims(&mut vocab_size, &mut dim);
+    }
+
+    let vectors = ffi::embeddings().and_then(|mut vectors| {
+        if add_output {
+            let output = ffi::output_embeddings()?;
+            vectors.iter_mut().zip(output).for_each(|(v, o)| *v += o);
+        }
+        Ok(vectors)
+    });
+    unsafe { wvec_model_free() };
+    let vectors = vectors.map_err(|e| format!("Cannot read embeddings: {}", e))?;
+
+    if vocab.len() != vocab_size as usize {
+        return Err(format!(
+            "Vocabulary has {} tokens, model has {}",
+            vocab.len(),
+            vocab_size
+        ));
+    }
+
+    let first = if skip_special { FIRST_REGULAR_ID } else { 0 };
+    let ids: Vec<BpeTokenId> = (first..vocab_size as BpeTokenId).collect();
+
+    let mut writer = BufWriter::new(
+        File::create(&output).map_err(|e| format!("Cannot create {}: {}", output, e))?,
+    );
+    write_vectors(&mut writer, format, &vocab, &ids, &vectors, dim as usize)
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Cannot write {}: {}", output, e))?;
+    println!(
+        "Exported {} vectors (dim={}, {}{}) to {}",
+        ids.len(),
+        dim,
+        format,
+        if add_output { ", input + output" } else { "" },
+        output
+    );
+
+    if let Some(path) = metadata {
+        // Token counts are optional; models from older versions lack them
+        let counts_path = format!("{}.counts", model_path);
+        let counts = load_counts(Path::new(&counts_path))
+            .ok()
+            .filter(|counts| counts.len() == vocab.len());
+
+        let mut writer = BufWriter::new(
+            File::create(&path).map_err(|e| format!("Cannot create {}: {}", path, e))?,
+        );
+        write_metadata(&mut writer, &vocab, &ids, counts.as_deref())
+            .and_then(|_| writer.flush())
+            .map_err(|e| format!("Cannot write {}: {}", path, e))?;
+        println!("Saved metadata to {}", path);
+    }
+
+    Ok(())
+}
+
+fn print_help() {
+    println!(
+        "wvec export - Write word vectors for other tools
+
+  USAGE:
+      wvec export --model <file> [OPTIONS]
+
+  EXAMPLES:
+      wvec export --model model.bin --format w2v-bin --skip-special
+      wvec export --model model.bin --format tsv --output vectors.tsv
+
+  Tokens that contain whitespace are written with the space as 'Ġ' and
+  other whitespace, and a literal 'Ġ', as \\u{{..}} escapes.
+
+  OPTIONS:
+      --model <file>       Path to trained model
+      --format <f>         w2v-text, w2v-bin (word2vec C tool and gensim),
+                           glove (text without header) or tsv (vectors only,
+                           for the Embedding Projector) (default: w2v-text)
+      --output <file>      Output file (default: <model>.w2v.txt, .w2v.bin,
+                           .glove.txt or .vectors.tsv)
+      --metadata <file>    Token names, IDs and counts as TSV
+                           (default: metadata.tsv next to the output)
+      --no-metadata        Do not write the metadata file
+      --skip-special       Leave out [UNK], [PAD], [BOS] and [EOS]
+      --add-output         Export input + output vectors (negative sampling
+                           models only)
+      -h, --help           Show this help message"
+    );
+}
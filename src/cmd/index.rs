@@ -0,0 +1,115 @@
+//! index command: Build an approximate nearest-neighbour index
+
+use crate::cli::{get_arg, has_flag};
+use crate::ffi::{self, wvec_checkpoint_load, wvec_model_free};
+use crate::hnsw::{Hnsw, HnswParams, MAX_M};
+use std::ffi::c_int;
+use std::path::Path;
+use std::time::Instant;
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    match args.first().map(String::as_str) {
+        Some("build") => build(&args[1..]),
+        Some("--help") | Some("-h") | None => {
+            print_help();
+            Ok(())
+        }
+        Some(other) => Err(format!("Unknown index action: {}", other)),
+    }
+}
+
+fn build(args: &[String]) -> Result<(), String> {
+    if has_flag(args, "--help") || has_flag(args, "-h") {
+        print_help();
+        return Ok(());
+    }
+
+    let model_path = get_arg(args, "--model").ok_or("Missing --model <file>")?;
+    let output = get_arg(args, "--output").unwrap_or_else(|| format!("{}.hnsw", model_path));
+    let defaults = HnswParams::default();
+    let params = HnswParams {
+        m: get_arg(args, "--m")
+            .map(|s| s.parse().map_err(|_| "Invalid --m"))
+            .transpose()?
+            .unwrap_or(defaults.m),
+        ef_construction: get_arg(args, "--ef-construction")
+            .map(|s| s.parse().map_err(|_| "Invalid --ef-construction"))
+            .transpose()?
+            .unwrap_or(defaults.ef_construction),
+        seed: get_arg(args, "--seed")
+            .map(|s| s.parse().map_err(|_| "Invalid --seed"))
+            .transpose()?
+            .unwrap_or(defaults.seed),
+    };
+    if !(2..=MAX_M).contains(&params.m) || params.ef_construction == 0 {
+        return Err(format!(
+            "--m must be between 2 and {} and --ef-construction at least 1",
+            MAX_M
+        ));
+    }
+
+    // Load model
+    let mut epoch: c_int = 0;
+    let mut lr: f32 = 0.0;
+    unsafe {
+        let status = wvec_checkpoint_load(
+            model_path.as_ptr() as *const i8,
+            model_path.len() as c_int,
+            &mut epoch,
+            &mut lr,
+        );
+        if status != ffi::status::SUCCESS {
+            return Err(format!("Cannot load model: {}", status));
+        }
+    }
+
+    let mut vocab_size: c_int = 0;
+    let mut dim: c_int = 0;
+    unsafe {
+        ffi::wvec_model_get_dims(&mut vocab_size, &mut dim);
+    }
+
+    let vectors = ffi::embeddings();
+    unsafe { wvec_model_free() };
+    let vectors = vectors.map_err(|e| format!("Cannot read embeddings: {}", e))?;
+
+    println!(
+        "Building HNSW index over {} vectors (dim={}, M={}, ef_construction={})...",
+        vocab_size, dim, params.m, params.ef_construction
+    );
+    let start = Instant::now();
+    let index = Hnsw::build(vectors, dim as usize, params);
+    println!(
+        "  {} layers, built in {:.1}s",
+        index.layers(),
+        start.elapsed().as_secs_f64()
+    );
+
+    index
+        .save(Path::new(&output))
+        .map_err(|e| format!("Cannot save index {}: {}", output, e))?;
+    println!("Saved to {}", output);
+
+    Ok(())
+}
+
+fn print_help() {
+    println!(
+        "wvec index - Build an approximate nearest-neighbour index
+
+  USAGE:
+      wvec index build --model <file> [OPTIONS]
+
+  'similar' and 'analogy' use <model>.hnsw automatically when it exists.
+  Rebuild the index after retraining the model; until then it is ignored
+  with a warning and searches are exact.
+
+  OPTIONS:
+      --model <file>           Path to trained model
+      --output <file>          Index file (default: <model>.hnsw)
+      --m <n>                  Links per node; layer 0 keeps 2*m (default: 16)
+      --ef-construction <n>    Search width while building (default: 200)
+      --seed <n>               Seed for the node levels (default: 42)
+      -h, --help               Show this help message"
+    );
+}
@@ -1,6 +1,6 @@
 //! info command: Show model/vocabulary information
 
-use crate::bpe::load;
+use crate::bpe::{WORD_START_MARKER, load};
 use crate::cli::{get_arg, has_flag};
 use std::path::Path;
 
@@ -24,15 +24,27 @@ pub fn run(args: &[String]) -> Result<(), String> {
             println!("BPE Vocabulary: {}", path);
             println!("  Tokens: {}", vocab.len());
             println!("  Merge rules: {}", vocab.pairs().len());
+            if vocab.is_byte_level() {
+                println!("  Byte-level: yes");
+            }
+            if vocab.has_word_boundaries() {
+                println!(
+                    "  Word boundaries: yes ('{}' marks a word start)",
+                    WORD_START_MARKER
+                );
+            }
 
             // Show sample tokens
             println!("\n  Sample tokens:");
             for id in 0..vocab.len().min(10) as u32 {
-                if let Some(token) = vocab.get_token(id) {
-                    let display = if token.contains('\n') || token.contains('\r') {
+                if let Some(mut token) = vocab.get_text(id) {
+                    if let Some(rest) = token.strip_prefix(' ') {
+                        token = format!("{}{}", WORD_START_MARKER, rest);
+                    }
+                    let display = if token.chars().any(char::is_control) {
                         format!("{:?}", token) // Escape special chars
                     } else {
-                        token.to_string()
+                        token
                     };
                     println!("    {:>4}: {}", id, display);
                 }
@@ -2,6 +2,11 @@ pub mod analogy;
 pub mod bpe_encode;
 pub mod bpe_train;
 pub mod embed;
+pub mod embed_text;
+pub mod export;
+pub mod index;
 pub mod info;
+pub mod repl;
+pub mod serve;
 pub mod similar;
 pub mod train;
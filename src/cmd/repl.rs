@@ -0,0 +1,326 @@
+//! repl command: Query a model interactively or from a script
+
+use crate::bpe::{Encoder, UNK_ID, Vocabulary, load as load_vocab};
+use crate::cli::{get_arg, has_flag, split_line};
+use crate::cmd::analogy::{print_terms, query_vector, resolve_query};
+use crate::cmd::embed::print_embedding;
+use crate::cmd::similar::{load_index, nearest, print_neighbours, print_pieces, word_vector};
+use crate::compose::{Compose, query_tokens};
+use crate::cosine::cosine;
+use crate::ffi::{self, wvec_checkpoint_load, wvec_model_free};
+use crate::hnsw::{DEFAULT_EF_SEARCH, Hnsw};
+use crate::text::normalize::normalize;
+use crate::text::pretokenize::pretokenize;
+use std::cell::RefCell;
+use std::ffi::c_int;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::Path;
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    if has_flag(args, "--help") || has_flag(args, "-h") {
+        print_help();
+        return Ok(());
+    }
+
+    let model_path = get_arg(args, "--model").ok_or("Missing --model <file>")?;
+    let topk: usize = get_arg(args, "--topk")
+        .unwrap_or_else(|| "10".to_string())
+        .parse()
+        .map_err(|_| "Invalid --topk")?;
+    let method = match get_arg(args, "--compose") {
+        Some(name) => Compose::parse(&name).ok_or("Invalid --compose (expected mean or sum)")?,
+        None => Compose::default(),
+    };
+    let ef: usize = get_arg(args, "--ef")
+        .unwrap_or_else(|| DEFAULT_EF_SEARCH.to_string())
+        .parse()
+        .map_err(|_| "Invalid --ef")?;
+    let script = get_arg(args, "--script");
+    let interactive = script.is_none() && !has_flag(args, "--batch") && io::stdin().is_terminal();
+
+    // Use the HNSW index built for this model, unless asked for exact search
+    let index_path = format!("{}.hnsw", model_path);
+    let use_index = !has_flag(args, "--exact") && Path::new(&index_path).exists();
+
+    let input: Box<dyn BufRead> = match script {
+        Some(ref path) => Box::new(BufReader::new(
+            File::open(path).map_err(|e| format!("Cannot open {}: {}", path, e))?,
+        )),
+        None => Box::new(io::stdin().lock()),
+    };
+
+    // Load vocabulary
+    let vocab_path = format!("{}.vocab", model_path);
+    let vocab = load_vocab(Path::new(&vocab_path))
+        .map_err(|e| format!("Cannot load vocab {}: {}", vocab_path, e))?;
+
+    // Load model
+    let mut epoch: c_int = 0;
+    let mut lr: f32 = 0.0;
+    unsafe {
+        let status = wvec_checkpoint_load(
+            model_path.as_ptr() as *const i8,
+            model_path.len() as c_int,
+            &mut epoch,
+            &mut lr,
+        );
+        if status != ffi::status::SUCCESS {
+            return Err(format!("Cannot load model: {}", status));
+        }
+    }
+
+    let mut vocab_size: c_int = 0;
+    let mut dim: c_int = 0;
+    unsafe {
+        ffi::wvec_model_get_dims(&mut vocab_size, &mut dim);
+    }
+
+    let index = if use_index {
+        load_index(&index_path, dim as usize)
+    } else {
+        Ok(None)
+    };
+    let result = index.and_then(|index| {
+        let session = Session {
+            encoder: RefCell::new(Encoder::new(&vocab)),
+            vocab: &vocab,
+            dim,
+            method,
+            topk,
+            index: index.map(|index| (index, ef)),
+        };
+        if interactive {
+            println!(
+                "Loaded {} ({} tokens, dim={}{}). Type 'help' for commands, 'quit' to exit.",
+                model_path,
+                vocab_size,
+                dim,
+                if session.index.is_some() {
+                    ", HNSW index"
+                } else {
+                    ""
+                }
+            );
+        }
+        session.serve(input, interactive)
+    });
+
+    unsafe { wvec_model_free() };
+    result
+}
+
+/// A loaded model answering query commands
+struct Session<'a> {
+    vocab: &'a Vocabulary,
+    encoder: RefCell<Encoder<'a>>,
+    dim: c_int,
+    method: Compose,
+    topk: usize,
+    index: Option<(Hnsw, usize)>,
+}
+
+impl Session<'_> {
+    /// Runs commands line by line until end of input or `quit`. Errors are
+    /// reported and the session goes on; in batch mode the run fails at the
+    /// end if any command failed.
+    fn serve(&self, input: impl BufRead, interactive: bool) -> Result<(), String> {
+        let mut failed = 0;
+        let mut total = 0;
+
+        prompt(interactive);
+        for line in input.lines() {
+            let line = line.map_err(|e| format!("Cannot read input: {}", e))?;
+            let words = split_line(&line);
+
+            match words.first().map(String::as_str) {
+                None => {}
+                Some(cmd) if cmd.starts_with('#') => {}
+                Some("quit") | Some("exit") => break,
+                Some(cmd) => {
+                    total += 1;
+                    if let Err(e) = self.execute(cmd, &words[1..]) {
+                        failed += 1;
+                        eprintln!("Error: {}", e);
+                    }
+                    // Keep command output in order with the error messages
+                    let _ = io::stdout().flush();
+                }
+            }
+            prompt(interactive);
+        }
+
+        if failed > 0 && !interactive {
+            return Err(format!("{} of {} commands failed", failed, total));
+        }
+        Ok(())
+    }
+
+    fn execute(&self, cmd: &str, args: &[String]) -> Result<(), String> {
+        // Words before the first --option
+        let words: Vec<&str> = args
+            .iter()
+            .take_while(|a| !a.starts_with("--"))
+            .map(String::as_str)
+            .collect();
+        let topk = match get_arg(args, "--topk") {
+            Some(k) => k.parse().map_err(|_| "Invalid --topk")?,
+            None => self.topk,
+        };
+
+        match (cmd, words.as_slice()) {
+            ("sim", [word]) => self.similar(word, topk),
+            ("ana", [_, ..]) => self.analogy(&words.join(" "), topk),
+            ("vec", [word]) => self.vector(word),
+            ("tok", [_, ..]) => self.tokenize(&words.join(" ")),
+            ("dist", [a, b]) => self.distance(a, b),
+            ("help", _) => {
+                print_commands();
+                Ok(())
+            }
+            ("sim" | "ana" | "vec" | "tok" | "dist", _) => {
+                Err(format!("Wrong arguments for '{}' (type 'help')", cmd))
+            }
+            _ => Err(format!("Unknown command '{}' (type 'help')", cmd)),
+        }
+    }
+
+    /// `sim <word>`: nearest neighbours of a word
+    fn similar(&self, word: &str, topk: usize) -> Result<(), String> {
+        let ids = self.tokens(word)?;
+        let vector = word_vector(word, &ids, self.dim, self.method)?;
+        let results = self.nearest(&vector, topk, ids.clone())?;
+
+        println!("Similar to '{}':", word);
+        print_pieces(self.vocab, &ids, self.method);
+        print_neighbours(self.vocab, &results);
+        Ok(())
+    }
+
+    /// `ana <expr>`: solve an analogy such as `king - man + woman`
+    fn analogy(&self, expr: &str, topk: usize) -> Result<(), String> {
+        let terms = resolve_query(self.vocab, expr)?;
+        let (vector, ids) = query_vector(&terms, self.dim, self.method)?;
+        let results = self.nearest(&vector, topk, ids)?;
+
+        println!("Analogy: {}", expr);
+        print_terms(self.vocab, &terms, self.method);
+        println!("Results:");
+        print_neighbours(self.vocab, &results);
+        Ok(())
+    }
+
+    /// `vec <word>`: print a word vector
+    fn vector(&self, word: &str) -> Result<(), String> {
+        let ids = self.tokens(word)?;
+        let vector = word_vector(word, &ids, self.dim, self.method)?;
+
+        println!("Embedding for '{}' (dim={}):", word, self.dim);
+        print_pieces(self.vocab, &ids, self.method);
+        print_embedding(&vector);
+        Ok(())
+    }
+
+    /// `tok <text>`: show how a text is tokenized
+    fn tokenize(&self, text: &str) -> Result<(), String> {
+        let mut encoder = self.encoder.borrow_mut();
+        let mut count = 0;
+        for pt in pretokenize(&normalize(text)) {
+            for &id in encoder.encode(&pt.into_bpe_text(self.vocab.has_word_boundaries())) {
+                let token = match id {
+                    UNK_ID => "[UNK]".to_string(),
+                    _ => self.vocab.get_text(id).unwrap_or_default(),
+                };
+                println!("  {:>6}  {:?}", id, token);
+                count += 1;
+            }
+        }
+        println!("{} tokens", count);
+        Ok(())
+    }
+
+    /// `dist <a> <b>`: cosine similarity of two words
+    fn distance(&self, a: &str, b: &str) -> Result<(), String> {
+        let va = word_vector(a, &self.tokens(a)?, self.dim, self.method)?;
+        let vb = word_vector(b, &self.tokens(b)?, self.dim, self.method)?;
+        let sim = cosine(&va, &vb);
+        println!(
+            "cosine('{}', '{}') = {:.4}  (distance {:.4})",
+            a,
+            b,
+            sim,
+            1.0 - sim
+        );
+        Ok(())
+    }
+
+    fn tokens(&self, word: &str) -> Result<Vec<u32>, String> {
+        let ids = query_tokens(self.vocab, word);
+        if ids.is_empty() {
+            return Err(format!("Word '{}' not in vocabulary", word));
+        }
+        Ok(ids)
+    }
+
+    fn nearest(
+        &self,
+        vector: &[f32],
+        topk: usize,
+        exclude: Vec<u32>,
+    ) -> Result<Vec<(u32, f32)>, String> {
+        let index = self.index.as_ref().map(|(index, ef)| (index, *ef));
+        let mut results = nearest(vector, self.dim as usize, topk, &[exclude], index)?;
+        Ok(results.pop().unwrap_or_default())
+    }
+}
+
+fn prompt(interactive: bool) {
+    if interactive {
+        print!("wvec> ");
+        let _ = io::stdout().flush();
+    }
+}
+
+fn print_commands() {
+    println!(
+        "  COMMANDS:
+      sim <word> [--topk <n>]     Most similar words
+      ana <expr> [--topk <n>]     Analogy, e.g. ana king - man + woman
+      vec <word>                  Word vector
+      tok <text>                  Tokens of a text, e.g. tok \"some text\"
+      dist <a> <b>                Cosine similarity of two words
+      help                        Show this list
+      quit                        End the session
+
+  Lines starting with # are ignored."
+    );
+}
+
+fn print_help() {
+    println!(
+        "wvec repl - Query a model interactively or from a script
+
+  USAGE:
+      wvec repl --model <file> [OPTIONS]
+      wvec repl --model <file> --script <file>
+      wvec repl --model <file> --batch < commands.txt
+
+  The model, vocabulary and <model>.hnsw index (if present) are loaded once;
+  commands are then read line by line.
+
+  OPTIONS:
+      --model <file>       Path to trained model
+      --topk <n>           Default number of results (default: 10)
+      --compose <m>        Combine the subword vectors of a word that is not
+                           a single token: mean or sum (default: mean)
+      --ef <n>             Search width with an HNSW index (default: 64)
+      --exact              Search all words even if <model>.hnsw exists
+      --script <file>      Read commands from a file
+      --batch              No prompt or banner; exit with an error if any
+                           command failed (implied by --script and when
+                           stdin is not a terminal)
+      -h, --help           Show this help message
+"
+    );
+    print_commands();
+}
@@ -0,0 +1,133 @@
+//! serve command: Answer queries over HTTP
+
+use crate::bpe::load as load_vocab;
+use crate::cli::{get_arg, has_flag};
+use crate::cmd::similar::open_index;
+use crate::compose::Compose;
+use crate::ffi::{self, wvec_checkpoint_load, wvec_model_free};
+use crate::hnsw::DEFAULT_EF_SEARCH;
+use crate::server::{QueryModel, serve};
+use std::ffi::c_int;
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    if has_flag(args, "--help") || has_flag(args, "-h") {
+        print_help();
+        return Ok(());
+    }
+
+    let model_path = get_arg(args, "--model").ok_or("Missing --model <file>")?;
+    let addr = get_arg(args, "--addr").unwrap_or_else(|| "127.0.0.1:8080".to_string());
+    let threads: usize = match get_arg(args, "--threads") {
+        Some(n) => n.parse().map_err(|_| "Invalid --threads")?,
+        None => thread::available_parallelism().map_or(4, |n| n.get()),
+    };
+    if threads == 0 {
+        return Err("--threads must be at least 1".to_string());
+    }
+    let method = match get_arg(args, "--compose") {
+        Some(name) => Compose::parse(&name).ok_or("Invalid --compose (expected mean or sum)")?,
+        None => Compose::default(),
+    };
+    let ef: usize = get_arg(args, "--ef")
+        .unwrap_or_else(|| DEFAULT_EF_SEARCH.to_string())
+        .parse()
+        .map_err(|_| "Invalid --ef")?;
+
+    // Use the HNSW index built for this model, unless asked for exact search
+    let index_path = format!("{}.hnsw", model_path);
+    let use_index = !has_flag(args, "--exact") && Path::new(&index_path).exists();
+
+    // Load vocabulary
+    let vocab_path = format!("{}.vocab", model_path);
+    let vocab = load_vocab(Path::new(&vocab_path))
+        .map_err(|e| format!("Cannot load vocab {}: {}", vocab_path, e))?;
+
+    // Load model, keep a copy of its vectors and release it again
+    let mut epoch: c_int = 0;
+    let mut lr: f32 = 0.0;
+    unsafe {
+        let status = wvec_checkpoint_load(
+            model_path.as_ptr() as *const i8,
+            model_path.len() as c_int,
+            &mut epoch,
+            &mut lr,
+        );
+        if status != ffi::status::SUCCESS {
+            return Err(format!("Cannot load model: {}", status));
+        }
+    }
+
+    let mut vocab_size: c_int = 0;
+    let mut dim: c_int = 0;
+    unsafe {
+        ffi::wvec_model_get_dims(&mut vocab_size, &mut dim);
+    }
+
+    let vectors = ffi::embeddings();
+    unsafe { wvec_model_free() };
+    let vectors = vectors.map_err(|e| format!("Cannot read embeddings: {}", e))?;
+    let dim = dim as usize;
+
+    if vocab.len() != vocab_size as usize {
+        return Err(format!(
+            "Vocabulary has {} tokens, model has {}",
+            vocab.len(),
+            vocab_size
+        ));
+    }
+
+    let mut model = QueryModel::new(vocab, vectors.clone(), dim, method);
+    if use_index && let Some(index) = open_index(&index_path, vectors, dim) {
+        model = model.with_index(index, ef);
+    }
+
+    let listener =
+        TcpListener::bind(&addr).map_err(|e| format!("Cannot listen on {}: {}", addr, e))?;
+    println!(
+        "Loaded {} ({} tokens, dim={}{})",
+        model_path,
+        vocab_size,
+        dim,
+        if model.has_index() {
+            ", HNSW index"
+        } else {
+            ""
+        }
+    );
+    println!("Listening on http://{} with {} threads", addr, threads);
+
+    serve(listener, Arc::new(model), threads).map_err(|e| format!("Server failed: {}", e))
+}
+
+fn print_help() {
+    println!(
+        "wvec serve - Answer queries over HTTP
+
+  USAGE:
+      wvec serve --model <file> [OPTIONS]
+
+  The model, vocabulary and <model>.hnsw index (if present) are loaded once.
+  Every endpoint takes GET query parameters or a POST JSON object and
+  answers with JSON:
+
+      /health                     Model size and whether an index is used
+      /embed?word=<w>             Word vector
+      /similar?word=<w>&k=<n>     Most similar words (default k: 10)
+      /analogy?query=<expr>&k=<n> Analogy, e.g. query=king - man + woman
+      /tokenize?text=<text>       BPE tokens of a text
+
+  OPTIONS:
+      --model <file>       Path to trained model
+      --addr <host:port>   Address to listen on (default: 127.0.0.1:8080)
+      --threads <n>        Worker threads (default: number of CPUs)
+      --compose <m>        Combine the subword vectors of a word that is not
+                           a single token: mean or sum (default: mean)
+      --ef <n>             Search width with an HNSW index (default: 64)
+      --exact              Search all words even if <model>.hnsw exists
+      -h, --help           Show this help message"
+    );
+}
@@ -1,8 +1,10 @@
 //! similar command: Find similar words
 
-use crate::bpe::load as load_vocab;
-use crate::cli::{get_arg, has_flag};
-use crate::ffi::{self, wvec_checkpoint_load, wvec_get_embedding, wvec_model_free};
+use crate::bpe::{Vocabulary, load as load_vocab};
+use crate::cli::{get_arg, has_flag, read_lines};
+use crate::compose::{Compose, compose, query_tokens};
+use crate::ffi::{self, search_topk, wvec_checkpoint_load, wvec_get_embedding, wvec_model_free};
+use crate::hnsw::{DEFAULT_EF_SEARCH, Hnsw};
 use std::ffi::c_int;
 use std::path::Path;
 
@@ -13,11 +15,37 @@ pub fn run(args: &[String]) -> Result<(), String> {
     }
 
     let model_path = get_arg(args, "--model").ok_or("Missing --model <file>")?;
-    let word = get_arg(args, "--word").ok_or("Missing --word <word>")?;
+    let mut words: Vec<String> = get_arg(args, "--word").into_iter().collect();
+    if let Some(list) = get_arg(args, "--words") {
+        words.extend(
+            list.split(',')
+                .map(str::trim)
+                .filter(|w| !w.is_empty())
+                .map(str::to_string),
+        );
+    }
+    if let Some(path) = get_arg(args, "--queries-file") {
+        words.extend(read_lines(&path)?);
+    }
+    if words.is_empty() {
+        return Err("Missing --word <word>, --words <a,b,c> or --queries-file <file>".to_string());
+    }
     let topk: usize = get_arg(args, "--topk")
         .unwrap_or_else(|| "10".to_string())
         .parse()
         .map_err(|_| "Invalid --topk")?;
+    let method = match get_arg(args, "--compose") {
+        Some(name) => Compose::parse(&name).ok_or("Invalid --compose (expected mean or sum)")?,
+        None => Compose::default(),
+    };
+    let ef: usize = get_arg(args, "--ef")
+        .unwrap_or_else(|| DEFAULT_EF_SEARCH.to_string())
+        .parse()
+        .map_err(|_| "Invalid --ef")?;
+
+    // Use the HNSW index built for this model, unless asked for exact search
+    let index_path = format!("{}.hnsw", model_path);
+    let use_index = !has_flag(args, "--exact") && Path::new(&index_path).exists();
 
     // Derive vocab path from model path
     let vocab_path = format!("{}.vocab", model_path);
@@ -26,10 +54,23 @@ pub fn run(args: &[String]) -> Result<(), String> {
     let vocab = load_vocab(Path::new(&vocab_path))
         .map_err(|e| format!("Cannot load vocab {}: {}", vocab_path, e))?;
 
-    // Find word ID
-    let word_id = vocab
-        .get_id_opt(&word)
-        .ok_or(format!("Word '{}' not in vocabulary", word))?;
+    // Find each word's token, or the subword tokens it is composed from
+    let single = words.len() == 1;
+    let mut queries: Vec<(String, Vec<u32>)> = Vec::new();
+    for word in words {
+        let query_ids = query_tokens(&vocab, &word);
+        if query_ids.is_empty() {
+            if single {
+                return Err(format!("Word '{}' not in vocabulary", word));
+            }
+            eprintln!("Skipping '{}': not in vocabulary", word);
+            continue;
+        }
+        queries.push((word, query_ids));
+    }
+    if queries.is_empty() {
+        return Err("No query word in vocabulary".to_string());
+    }
 
     // Load model
     let mut epoch: c_int = 0;
@@ -53,64 +94,146 @@ pub fn run(args: &[String]) -> Result<(), String> {
         ffi::wvec_model_get_dims(&mut vocab_size, &mut dim);
     }
 
-    // Get query embedding
-    let mut query_emb = vec![0.0f32; dim as usize];
+    let results = search(
+        &queries,
+        dim,
+        method,
+        topk,
+        use_index.then_some((index_path.as_str(), ef)),
+    );
     unsafe {
-        let status = wvec_get_embedding(word_id as c_int, query_emb.as_mut_ptr(), dim);
-        if status != ffi::status::SUCCESS {
-            wvec_model_free();
-            return Err(format!("Cannot get embedding for '{}': {}", word, status));
+        wvec_model_free();
+    }
+    let results = results?;
+
+    // Print results
+    for (i, ((word, query_ids), neighbours)) in queries.iter().zip(&results).enumerate() {
+        if i > 0 {
+            println!();
         }
+        println!("Similar to '{}':", word);
+        print_pieces(&vocab, query_ids, method);
+        print_neighbours(&vocab, neighbours);
     }
 
-    // Compute similarities with all words
-    let mut similarities: Vec<(u32, f32)> = Vec::new();
-    let mut other_emb = vec![0.0f32; dim as usize];
+    Ok(())
+}
 
-    for id in 0..vocab_size as u32 {
-        if id == word_id {
-            continue; // Skip the query word itself
-        }
+/// Builds the query vectors and finds the nearest neighbours of all of them
+/// in one batch, leaving out each query's own tokens.
+fn search(
+    queries: &[(String, Vec<u32>)],
+    dim: c_int,
+    method: Compose,
+    topk: usize,
+    index: Option<(&str, usize)>,
+) -> Result<Vec<Vec<(u32, f32)>>, String> {
+    let mut vectors = Vec::with_capacity(queries.len() * dim as usize);
+    for (word, query_ids) in queries {
+        vectors.extend(word_vector(word, query_ids, dim, method)?);
+    }
 
-        unsafe {
-            let status = wvec_get_embedding(id as c_int, other_emb.as_mut_ptr(), dim);
-            if status != ffi::status::SUCCESS {
-                continue;
-            }
+    let index = match index {
+        Some((path, ef)) => load_index(path, dim as usize)?.map(|index| (index, ef)),
+        None => None,
+    };
+    let exclude: Vec<Vec<u32>> = queries.iter().map(|(_, ids)| ids.clone()).collect();
+    nearest(
+        &vectors,
+        dim as usize,
+        topk,
+        &exclude,
+        index.as_ref().map(|(index, ef)| (index, *ef)),
+    )
+}
+
+/// Composes the vector of `word` from the model vectors of its tokens.
+/// The model must be loaded.
+pub(crate) fn word_vector(
+    word: &str,
+    ids: &[u32],
+    dim: c_int,
+    method: Compose,
+) -> Result<Vec<f32>, String> {
+    compose(ids, dim as usize, method, |id, out| {
+        match unsafe { wvec_get_embedding(id as c_int, out.as_mut_ptr(), dim) } {
+            ffi::status::SUCCESS => Ok(()),
+            status => Err(status),
         }
+    })
+    .map_err(|status| format!("Cannot get embedding for '{}': {}", word, status))
+}
 
-        let sim = cosine_similarity(&query_emb, &other_emb);
-        similarities.push((id, sim));
+/// Loads the HNSW index at `path` over the vectors of the loaded model.
+/// An index that cannot be used (e.g. one built before the model was
+/// retrained) is reported and `None` is returned, so the search is exact.
+pub(crate) fn load_index(path: &str, dim: usize) -> Result<Option<Hnsw>, String> {
+    let embeddings = ffi::embeddings().map_err(|e| format!("Cannot read embeddings: {}", e))?;
+    Ok(open_index(path, embeddings, dim))
+}
+
+/// Loads the HNSW index at `path` over `vectors`, or warns and returns
+/// `None` if it does not fit them.
+pub(crate) fn open_index(path: &str, vectors: Vec<f32>, dim: usize) -> Option<Hnsw> {
+    match Hnsw::load(Path::new(path), vectors, dim) {
+        Ok(index) => Some(index),
+        Err(e) => {
+            eprintln!(
+                "Warning: not using index {} ({}); searching exactly",
+                path, e
+            );
+            None
+        }
     }
+}
 
-    // Sort by similarity (descending)
-    similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+/// Finds the `topk` nearest neighbours of each query vector, leaving out
+/// `exclude[i]` for query `i`. With `index` (and its search width) the
+/// answers are approximate, otherwise the model searches exactly.
+/// The model must be loaded.
+pub(crate) fn nearest(
+    vectors: &[f32],
+    dim: usize,
+    topk: usize,
+    exclude: &[Vec<u32>],
+    index: Option<(&Hnsw, usize)>,
+) -> Result<Vec<Vec<(u32, f32)>>, String> {
+    match index {
+        Some((index, ef)) => Ok(index.search_batch(vectors, topk, ef, exclude)),
+        None => search_topk(vectors, dim, topk, exclude)
+            .map_err(|e| format!("Nearest-neighbour search failed: {}", e)),
+    }
+}
 
-    // Print results
-    println!("Similar to '{}':", word);
-    for (id, sim) in similarities.iter().take(topk) {
-        if let Some(token) = vocab.get_token(*id) {
+/// Prints one `similarity  token` line per neighbour.
+pub(crate) fn print_neighbours(vocab: &Vocabulary, neighbours: &[(u32, f32)]) {
+    for &(id, sim) in neighbours {
+        if let Some(token) = vocab.get_text(id) {
             println!("  {:>6.4}  {}", sim, token);
         }
     }
+}
 
-    unsafe {
-        wvec_model_free();
+/// Shows the subword tokens of a composed query.
+pub(crate) fn print_pieces(vocab: &Vocabulary, ids: &[u32], method: Compose) {
+    if let Some(pieces) = describe_pieces(vocab, ids, method) {
+        println!("  {}", pieces);
     }
-
-    Ok(())
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-
-    if norm_a > 0.0 && norm_b > 0.0 {
-        dot / (norm_a * norm_b)
-    } else {
-        0.0
+/// Describes the subword tokens of a composed query, e.g.
+/// `(mean of 2 tokens: king | ship)`; None for a single token.
+pub(crate) fn describe_pieces(vocab: &Vocabulary, ids: &[u32], method: Compose) -> Option<String> {
+    if ids.len() < 2 {
+        return None;
     }
+    let pieces: Vec<String> = ids.iter().filter_map(|&id| vocab.get_text(id)).collect();
+    Some(format!(
+        "({} of {} tokens: {})",
+        method,
+        ids.len(),
+        pieces.join(" | ")
+    ))
 }
 
 fn print_help() {
@@ -119,11 +242,19 @@ fn print_help() {
 
   USAGE:
       wvec similar --model <file> --word <word> [OPTIONS]
+      wvec similar --model <file> --words <a,b,c> [OPTIONS]
+      wvec similar --model <file> --queries-file <file> [OPTIONS]
 
   OPTIONS:
       --model <file>       Path to trained model
       --word <word>        Query word
+      --words <a,b,c>      Comma-separated query words
+      --queries-file <f>   Query words, one per line
       --topk <n>           Number of results (default: 10)
+      --compose <m>        Combine the subword vectors of a word that is not
+                           a single token: mean or sum (default: mean)
+      --ef <n>             Search width with an HNSW index (default: 64)
+      --exact              Search all words even if <model>.hnsw exists
       -h, --help           Show this help message"
     );
 }
@@ -1,16 +1,37 @@
 //! train command: Train word vectors on a corpus
 
-use crate::bpe::{encode, load as load_vocab, save as save_vocab, train as train_bpe};
-use crate::cli::{get_arg, has_flag};
+use crate::bpe::{
+    Encoder, TrainOptions, Vocabulary, encode, load as load_vocab, save as save_vocab,
+    train_from_counts_with as train_bpe_from_counts,
+};
+use crate::cli::{get_arg, get_arg_values, has_flag};
 use crate::ffi::{
-    self, wvec_checkpoint_save, wvec_model_free, wvec_model_init, wvec_shutdown_reset,
-    wvec_train_corpus,
+    self, wvec_checkpoint_load, wvec_checkpoint_save, wvec_get_num_threads, wvec_hs_free,
+    wvec_hs_set_tree, wvec_model_free, wvec_model_get_dims, wvec_model_init, wvec_model_seed,
+    wvec_set_num_threads, wvec_shutdown_reset, wvec_train_corpus, wvec_train_corpus_cbow,
+    wvec_train_progress,
 };
-use crate::input::text::TextReader;
-use crate::text::normalize::normalize;
-use crate::text::pretokenize::pretokenize;
+use crate::huffman::HuffmanTree;
+use crate::input::corpus::{Format, MultiPretokens};
+use crate::input::files::{expand_inputs, is_rereadable, is_stdin};
+use crate::pooling::save_counts;
+use crate::rng::{DEFAULT_SEED, Rng};
+use crate::signal::{install_shutdown_handler, restore_default_handlers};
+use crate::thermal::{DEFAULT_COOLDOWN_SECS, DEFAULT_MAX_TEMP_C, ThermalMonitor, discover_zone};
+use std::collections::HashMap;
 use std::ffi::c_int;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Default number of center tokens handed to the Fortran trainer per call.
+/// Thermal checks run between chunks.
+const DEFAULT_CHUNK_TOKENS: usize = 1_000_000;
 
 pub fn run(args: &[String]) -> Result<(), String> {
     if has_flag(args, "--help") || has_flag(args, "-h") {
@@ -19,17 +40,43 @@ pub fn run(args: &[String]) -> Result<(), String> {
     }
 
     // Parse arguments
-    let input = get_arg(args, "--input").ok_or("Missing --input <file>")?;
+    let inputs = get_arg_values(args, "--input");
+    if inputs.is_empty() {
+        return Err("Missing --input <path>...".to_string());
+    }
+    let format = match get_arg(args, "--format") {
+        Some(name) => {
+            Some(Format::parse(&name).ok_or("Invalid --format (expected txt, xml or html)")?)
+        }
+        None => None,
+    };
     let output = get_arg(args, "--output").ok_or("Missing --output <file>")?;
+    let resume = get_arg(args, "--resume");
     let vocab_file = get_arg(args, "--vocab");
     let vocab_size: usize = get_arg(args, "--vocab-size")
         .unwrap_or_else(|| "50000".to_string())
         .parse()
         .map_err(|_| "Invalid --vocab-size")?;
+    let bpe_options = TrainOptions {
+        byte_level: has_flag(args, "--byte-level"),
+        word_boundaries: has_flag(args, "--word-boundaries"),
+    };
     let dim: i32 = get_arg(args, "--dim")
         .unwrap_or_else(|| "100".to_string())
         .parse()
         .map_err(|_| "Invalid --dim")?;
+    let model = match get_arg(args, "--model").as_deref() {
+        None | Some("skipgram") => Model::SkipGram,
+        Some("cbow") => Model::Cbow {
+            mean: !has_flag(args, "--cbow-sum"),
+        },
+        Some(other) => {
+            return Err(format!(
+                "Invalid --model '{}' (expected cbow or skipgram)",
+                other
+            ));
+        }
+    };
     let window: i32 = get_arg(args, "--window")
         .unwrap_or_else(|| "5".to_string())
         .parse()
@@ -42,41 +89,156 @@ pub fn run(args: &[String]) -> Result<(), String> {
         .unwrap_or_else(|| "0.025".to_string())
         .parse()
         .map_err(|_| "Invalid --lr")?;
+    let min_lr: f32 = match get_arg(args, "--min-lr") {
+        Some(v) => v.parse().map_err(|_| "Invalid --min-lr")?,
+        None => lr * 0.0001,
+    };
+    let sample: f64 = get_arg(args, "--sample")
+        .unwrap_or_else(|| "1e-3".to_string())
+        .parse()
+        .map_err(|_| "Invalid --sample")?;
     let epochs: usize = get_arg(args, "--epochs")
         .unwrap_or_else(|| "5".to_string())
         .parse()
         .map_err(|_| "Invalid --epochs")?;
+    let seed: u64 = get_arg(args, "--seed")
+        .unwrap_or_else(|| DEFAULT_SEED.to_string())
+        .parse()
+        .map_err(|_| "Invalid --seed")?;
+    let threads: i32 = get_arg(args, "--threads")
+        .unwrap_or_else(|| "0".to_string())
+        .parse()
+        .map_err(|_| "Invalid --threads")?;
+    let chunk_tokens: usize = get_arg(args, "--chunk-tokens")
+        .unwrap_or_else(|| DEFAULT_CHUNK_TOKENS.to_string())
+        .parse()
+        .ok()
+        .filter(|&n: &usize| n > 0 && n <= c_int::MAX as usize / 2)
+        .ok_or("Invalid --chunk-tokens")?;
+    let checkpoint_every: usize = get_arg(args, "--checkpoint-every")
+        .unwrap_or_else(|| "1".to_string())
+        .parse()
+        .map_err(|_| "Invalid --checkpoint-every")?;
+    let thermal_zone = get_arg(args, "--thermal-zone");
+    let max_temp: i32 = get_arg(args, "--max-temp")
+        .unwrap_or_else(|| DEFAULT_MAX_TEMP_C.to_string())
+        .parse()
+        .map_err(|_| "Invalid --max-temp")?;
+    let cooldown: u64 = get_arg(args, "--cooldown")
+        .unwrap_or_else(|| DEFAULT_COOLDOWN_SECS.to_string())
+        .parse()
+        .map_err(|_| "Invalid --cooldown")?;
+
+    // A resumed run must keep the vocabulary the checkpoint was trained with
+    let vocab_file = vocab_file.or_else(|| resume.as_ref().map(|ckpt| format!("{}.vocab", ckpt)));
+
+    // ...and its output layer: a saved tree means hierarchical softmax
+    let resume_tree = resume
+        .as_ref()
+        .map(|ckpt| format!("{}.tree", ckpt))
+        .filter(|tree| Path::new(tree).exists());
+    let hs = has_flag(args, "--hs") || resume_tree.is_some();
+
+    // A given vocabulary decides how pre-tokens are marked, so load it first
+    let loaded_vocab = match vocab_file {
+        Some(ref vf) => {
+            Some(load_vocab(Path::new(vf)).map_err(|e| format!("Cannot load vocab: {}", e))?)
+        }
+        None => None,
+    };
+    let word_boundaries = loaded_vocab
+        .as_ref()
+        .map_or(bpe_options.word_boundaries, |v| v.has_word_boundaries());
 
     eprintln!("Training word vectors...");
-    eprintln!("  Input: {}", input);
+    let files = expand_inputs(&inputs);
+    match files.as_slice() {
+        [] => return Err("No input files found".to_string()),
+        [file] => eprintln!(
+            "  Input: {} ({:?})",
+            file.display(),
+            format.unwrap_or_else(|| Format::detect(file))
+        ),
+        _ => eprintln!("  Input: {} files", files.len()),
+    }
     eprintln!("  Output: {}", output);
+    if let Some(ref ckpt) = resume {
+        eprintln!("  Resume: {}", ckpt);
+    }
     eprintln!(
-        "  Dim: {}, Window: {}, Neg: {}, LR: {}, Epochs: {}",
-        dim, window, neg_samples, lr, epochs
+        "  Model: {} + {}",
+        model,
+        if hs {
+            "hierarchical softmax"
+        } else {
+            "negative sampling"
+        }
+    );
+    eprintln!(
+        "  Dim: {}, Window: {}, Neg: {}, LR: {} -> {}, Epochs: {}",
+        dim, window, neg_samples, lr, min_lr, epochs
+    );
+    unsafe { wvec_set_num_threads(threads) };
+    eprintln!(
+        "  Threads: {}, Seed: {}",
+        unsafe { wvec_get_num_threads() },
+        seed
     );
 
-    // Step 1: Read and preprocess corpus
-    eprintln!("\n[1/5] Reading corpus...");
-    let reader = TextReader::open(&input).map_err(|e| format!("Cannot open {}: {}", input, e))?;
+    // Every epoch re-reads the corpus, so stdin and pipes go to disk first
+    let spool = Spool::create(&files, &output)?;
 
-    let mut pretokens: Vec<String> = Vec::new();
-    for line_result in reader {
-        let line = line_result.map_err(|e| format!("Read error: {}", e))?;
-        let normalized = normalize(&line);
-        for pt in pretokenize(&normalized) {
-            pretokens.push(pt.text.to_string());
+    // Step 1: Count pre-tokens in a first streaming pass. Only the distinct
+    // pre-tokens are held in memory, never the corpus itself.
+    eprintln!("\n[1/5] Counting pre-tokens...");
+    let mut word_counts: HashMap<String, u32> = HashMap::new();
+    let mut file_pretokens = vec![0u64; files.len()];
+    for (file, pt) in
+        MultiPretokens::new(&spool.files, format).with_word_boundaries(word_boundaries)
+    {
+        file_pretokens[file] += 1;
+        let count = word_counts.entry(pt).or_insert(0);
+        *count = count.saturating_add(1);
+    }
+    // Pre-tokens, not BPE tokens: the vocabulary is trained from these counts
+    if files.len() > 1 {
+        for (file, n) in files.iter().zip(&file_pretokens) {
+            eprintln!("    {}: {} pre-tokens", file.display(), n);
         }
     }
-    eprintln!("  {} pre-tokens", pretokens.len());
+    let n_pretokens: u64 = file_pretokens.iter().sum();
+    if n_pretokens == 0 {
+        return Err("No pre-tokens read from the input".to_string());
+    }
+    eprintln!(
+        "  {} pre-tokens ({} distinct)",
+        n_pretokens,
+        word_counts.len()
+    );
 
     // Step 2: Train or load BPE vocabulary
     eprintln!("\n[2/5] Preparing vocabulary...");
-    let vocab = if let Some(ref vf) = vocab_file {
-        eprintln!("  Loading from {}", vf);
-        load_vocab(Path::new(vf)).map_err(|e| format!("Cannot load vocab: {}", e))?
+    let vocab = if let (Some(v), Some(vf)) = (loaded_vocab, &vocab_file) {
+        eprintln!("  Loaded from {}", vf);
+        // Keep the vocab next to the model when resuming into a new output file
+        let vocab_path = format!("{}.vocab", output);
+        if resume.is_some() && *vf != vocab_path {
+            save_vocab(&v, Path::new(&vocab_path))
+                .map_err(|e| format!("Cannot save vocab: {}", e))?;
+            eprintln!("  Saved vocabulary to {}", vocab_path);
+        }
+        v
     } else {
-        eprintln!("  Training BPE (target size: {})", vocab_size);
-        let v = train_bpe(pretokens.iter().map(|s| s.as_str()), vocab_size);
+        eprintln!(
+            "  Training {}BPE (target size: {})",
+            if bpe_options.byte_level {
+                "byte-level "
+            } else {
+                ""
+            },
+            vocab_size
+        );
+        let v = train_bpe_from_counts(&word_counts, vocab_size, bpe_options);
         // Save vocab alongside model
         let vocab_path = format!("{}.vocab", output);
         save_vocab(&v, Path::new(&vocab_path)).map_err(|e| format!("Cannot save vocab: {}", e))?;
@@ -85,80 +247,694 @@ pub fn run(args: &[String]) -> Result<(), String> {
     };
     eprintln!("  Vocabulary size: {}", vocab.len());
 
-    // Step 3: Encode corpus to token IDs
-    eprintln!("\n[3/5] Encoding corpus...");
-    let mut token_ids: Vec<c_int> = Vec::new();
-    for pt in &pretokens {
-        let ids = encode(&vocab, pt);
-        token_ids.extend(ids.iter().map(|&id| id as c_int));
-    }
-    eprintln!("  {} token IDs", token_ids.len());
+    // Step 3: Token frequencies, encoding each distinct pre-token once
+    eprintln!("\n[3/5] Counting tokens...");
+    let counts = count_tokens(&word_counts, &vocab);
+    drop(word_counts);
+    let total_tokens: u64 = counts.iter().sum();
+    eprintln!("  {} tokens", total_tokens);
+    // Kept for SIF weighting in embed-text
+    let counts_path = format!("{}.counts", output);
+    save_counts(&counts, Path::new(&counts_path))
+        .map_err(|e| format!("Cannot save token counts: {}", e))?;
+    eprintln!("  Saved token counts to {}", counts_path);
 
-    // Step 4: Build negative sampling table (unigram distribution)
-    eprintln!("\n[4/5] Building negative sampling table...");
-    let neg_table = build_neg_table(&token_ids, vocab.len());
-    eprintln!("  Table size: {}", neg_table.len());
+    // Step 4: Build sampling tables from token frequencies
+    eprintln!("\n[4/5] Building sampling tables...");
+    let (neg_table, tree) = if hs {
+        let tree = prepare_tree(resume_tree.as_deref(), &counts, &output)?;
+        eprintln!("  Huffman tree: max code length {}", tree.max_code_len());
+        (Vec::new(), Some(tree))
+    } else {
+        let neg_table = build_neg_table(&counts);
+        eprintln!("  Negative sampling table size: {}", neg_table.len());
+        (neg_table, None)
+    };
+
+    let keep_probs = subsample_keep_probs(&counts, sample);
+    let kept: f64 = counts
+        .iter()
+        .zip(&keep_probs)
+        .map(|(&c, &p)| c as f64 * p as f64)
+        .sum();
+    if sample > 0.0 {
+        eprintln!(
+            "  Subsampling (t={}): ~{} of {} tokens per epoch ({:.1}%)",
+            sample,
+            kept.round() as u64,
+            total_tokens,
+            100.0 * kept / total_tokens.max(1) as f64
+        );
+    }
 
     // Step 5: Train model
     eprintln!("\n[5/5] Training...");
+    let mut thermal = setup_thermal(thermal_zone, max_temp, Duration::from_secs(cooldown))?;
     unsafe {
         wvec_shutdown_reset();
+    }
 
-        let status = wvec_model_init(vocab.len() as c_int, dim);
-        if status != ffi::status::SUCCESS {
-            return Err(format!("Failed to initialize model: {}", status));
+    let (start_epoch, lr) = match resume {
+        Some(ref ckpt) => {
+            let (done, saved_lr) = resume_checkpoint(ckpt, vocab.len())?;
+            eprintln!("  Resumed after epoch {} (lr: {})", done, saved_lr);
+            (done + 1, saved_lr)
+        }
+        None => {
+            let status = unsafe {
+                wvec_model_seed(seed as i64);
+                wvec_model_init(vocab.len() as c_int, dim)
+            };
+            if status != ffi::status::SUCCESS {
+                return Err(format!("Failed to initialize model: {}", status));
+            }
+            (1, lr)
         }
+    };
+
+    // From here on every exit frees the tree (if any) along with the model
+    let free_model = || unsafe {
+        wvec_hs_free();
+        wvec_model_free();
+    };
 
-        for epoch in 1..=epochs {
-            eprintln!("  Epoch {}/{}", epoch, epochs);
+    if let Some(ref tree) = tree
+        && let Err(e) = install_tree(tree)
+    {
+        free_model();
+        return Err(e);
+    }
 
-            let status = wvec_train_corpus(
-                token_ids.as_ptr(),
-                token_ids.len() as c_int,
-                window,
-                neg_samples,
-                neg_table.as_ptr(),
-                neg_table.len() as c_int,
-                lr,
-            );
+    if start_epoch > epochs {
+        eprintln!(
+            "  Checkpoint already covers {} epochs, nothing to train",
+            epochs
+        );
+    }
+
+    // Number of fully completed epochs, as recorded in the checkpoint
+    let mut completed = start_epoch - 1;
 
-            if status == ffi::status::STATUS_INTERRUPTED {
-                eprintln!("  Interrupted! Saving checkpoint...");
+    // Decay over the (subsampled) words of the epochs left in this run;
+    // a resumed run continues from the saved lr
+    let epoch_words = kept.round() as u64;
+    let params = EpochParams {
+        model,
+        hs,
+        chunk_tokens,
+        window,
+        neg_samples,
+        neg_table: &neg_table,
+        keep_probs: &keep_probs,
+        schedule: LrSchedule {
+            start: lr,
+            end: min_lr,
+            total_words: epochs.saturating_sub(completed) as u64 * epoch_words,
+        },
+    };
+    let schedule = params.schedule;
+    let words_before = |epoch: usize| (epoch - start_epoch) as u64 * epoch_words;
+
+    // Ctrl-C / SIGTERM now stop training and fall through to the final checkpoint
+    if let Err(e) = install_shutdown_handler() {
+        free_model();
+        return Err(e);
+    }
+
+    for epoch in start_epoch..=epochs {
+        match thermal.as_ref().map(|m| m.read_celsius()) {
+            Some(Ok(temp)) => eprintln!("  Epoch {}/{} (CPU {}°C)", epoch, epochs, temp),
+            _ => eprintln!("  Epoch {}/{}", epoch, epochs),
+        }
+
+        let progress = ProgressReporter::start(schedule.total_words);
+        // Seeded per epoch, so a resumed run draws the same tokens
+        let mut rng = Rng::new(seed.wrapping_add(epoch as u64));
+        let finished = train_epoch(
+            &spool.files,
+            format,
+            &vocab,
+            &params,
+            words_before(epoch),
+            &mut rng,
+            &mut thermal,
+        );
+        progress.finish();
+
+        match finished {
+            Ok(true) => {}
+            Ok(false) => {
+                eprintln!(
+                    "  Interrupted during epoch {}! Saving checkpoint (epoch {} completed)...",
+                    epoch, completed
+                );
                 break;
-            } else if status != ffi::status::SUCCESS {
-                wvec_model_free();
-                return Err(format!("Training failed: {}", status));
+            }
+            Err(e) => {
+                restore_default_handlers();
+                free_model();
+                return Err(e);
             }
         }
+        completed = epoch;
 
-        // Save checkpoint
-        let status = wvec_checkpoint_save(
-            output.as_ptr() as *const i8,
-            output.len() as c_int,
-            epochs as c_int,
-            lr,
+        // Periodic checkpoint so a long run can be resumed
+        if checkpoint_every > 0 && epoch % checkpoint_every == 0 && epoch < epochs {
+            let lr_now = schedule.at(words_before(epoch + 1));
+            if let Err(e) = save_checkpoint(&output, completed, lr_now) {
+                restore_default_handlers();
+                free_model();
+                return Err(e);
+            }
+            eprintln!("  Checkpoint saved (epoch {})", completed);
+        }
+    }
+
+    restore_default_handlers();
+
+    // Save final checkpoint with the lr reached at the end of the last completed epoch
+    let lr_now = schedule.at(words_before(completed + 1));
+    let result = save_checkpoint(&output, completed, lr_now);
+    free_model();
+    result?;
+
+    eprintln!("\nDone! Model saved to {}", output);
+    Ok(())
+}
+
+/// Linear learning-rate decay over the words of a run.
+///
+/// Mirrors `decayed_lr` in `fortran/wvec_train.f90`, so the lr saved in a
+/// checkpoint matches what the trainer used.
+#[derive(Debug, Clone, Copy)]
+struct LrSchedule {
+    start: f32,
+    end: f32,
+    total_words: u64,
+}
+
+impl LrSchedule {
+    /// Learning rate after `words_done` words.
+    fn at(&self, words_done: u64) -> f32 {
+        if self.total_words == 0 {
+            return self.start;
+        }
+        let frac = (words_done as f64 / self.total_words as f64).min(1.0);
+        (self.start as f64 - (self.start - self.end) as f64 * frac) as f32
+    }
+}
+
+/// Training architecture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Model {
+    /// Predict each context word from the center word
+    SkipGram,
+    /// Predict the center word from its context, averaged or summed
+    Cbow { mean: bool },
+}
+
+impl std::fmt::Display for Model {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SkipGram => write!(f, "skip-gram"),
+            Self::Cbow { mean: true } => write!(f, "CBOW (mean)"),
+            Self::Cbow { mean: false } => write!(f, "CBOW (sum)"),
+        }
+    }
+}
+
+/// Settings shared by every epoch of a run.
+struct EpochParams<'a> {
+    model: Model,
+    /// Hierarchical softmax instead of negative sampling
+    hs: bool,
+    /// Center tokens per trainer call
+    chunk_tokens: usize,
+    window: i32,
+    neg_samples: i32,
+    /// Unigram^0.75 table for drawing negatives
+    neg_table: &'a [c_int],
+    /// Per-token probability of keeping an occurrence (subsampling)
+    keep_probs: &'a [f32],
+    schedule: LrSchedule,
+}
+
+/// Trains one epoch, streaming the corpus from `files` in chunks.
+///
+/// The corpus is re-read and encoded on the fly through a pre-token cache,
+/// and frequent tokens are subsampled with `rng`, which also seeds the
+/// trainer for each chunk.
+/// The buffer keeps `window` tokens on either side of a chunk's centers,
+/// so context windows carry across chunk boundaries; memory stays bounded
+/// by `chunk_tokens`. The CPU must cool down before each chunk.
+/// `word_offset` is the position of this epoch in the lr schedule.
+/// Returns `Ok(false)` if training was interrupted by a shutdown request.
+fn train_epoch(
+    files: &[PathBuf],
+    format: Option<Format>,
+    vocab: &Vocabulary,
+    params: &EpochParams,
+    word_offset: u64,
+    rng: &mut Rng,
+    thermal: &mut Option<ThermalMonitor>,
+) -> Result<bool, String> {
+    let margin = params.window.max(0) as usize;
+    let mut offset = word_offset;
+    // Layout: [left context | centers | right context], centers from `first_center`
+    let mut buf: Vec<c_int> = Vec::with_capacity(params.chunk_tokens + 2 * margin);
+    let mut first_center = 0;
+    let mut encoder = Encoder::new(vocab);
+
+    let pretokens =
+        MultiPretokens::new(files, format).with_word_boundaries(vocab.has_word_boundaries());
+    for (_, pt) in pretokens {
+        for &id in encoder.encode(&pt) {
+            let p = params.keep_probs.get(id as usize).copied().unwrap_or(1.0);
+            if p >= 1.0 || rng.next_f32() < p {
+                buf.push(id as c_int);
+            }
+        }
+        if buf.len() < first_center + params.chunk_tokens + margin {
+            continue;
+        }
+
+        // Train all centers that already have their full right context
+        let centers_end = buf.len() - margin;
+        if !train_chunk(
+            &buf,
+            first_center..centers_end,
+            params,
+            &mut offset,
+            rng,
+            thermal,
+        )? {
+            return Ok(false);
+        }
+
+        // Keep the left context of the next centers and the untrained tail
+        let keep_from = centers_end.saturating_sub(margin);
+        buf.drain(..keep_from);
+        first_center = centers_end - keep_from;
+    }
+
+    // The corpus ends here, so the last centers have no right context to wait for
+    if first_center < buf.len() {
+        let centers = first_center..buf.len();
+        return train_chunk(&buf, centers, params, &mut offset, rng, thermal);
+    }
+    Ok(true)
+}
+
+/// Trains the tokens of `buf` in `centers` as centers, using the rest of
+/// `buf` as context, and advances `offset` by the number of centers.
+///
+/// Returns `Ok(false)` if training was interrupted by a shutdown request.
+fn train_chunk(
+    buf: &[c_int],
+    centers: Range<usize>,
+    params: &EpochParams,
+    offset: &mut u64,
+    rng: &mut Rng,
+    thermal: &mut Option<ThermalMonitor>,
+) -> Result<bool, String> {
+    let schedule = &params.schedule;
+
+    if let Some(monitor) = thermal
+        && let Err(e) = monitor.wait_until_cool()
+    {
+        eprintln!("  Warning: {}, thermal throttling disabled", e);
+        *thermal = None;
+    }
+
+    let seed = rng.next_u64() as i64;
+    let status = unsafe {
+        match params.model {
+            Model::SkipGram => wvec_train_corpus(
+                buf.as_ptr(),
+                buf.len() as c_int,
+                centers.start as c_int,
+                centers.end as c_int,
+                params.window,
+                params.neg_samples,
+                params.neg_table.as_ptr(),
+                params.neg_table.len() as c_int,
+                schedule.start,
+                schedule.end,
+                *offset as i64,
+                schedule.total_words as i64,
+                seed,
+                params.hs as c_int,
+            ),
+            Model::Cbow { mean } => wvec_train_corpus_cbow(
+                buf.as_ptr(),
+                buf.len() as c_int,
+                centers.start as c_int,
+                centers.end as c_int,
+                params.window,
+                params.neg_samples,
+                params.neg_table.as_ptr(),
+                params.neg_table.len() as c_int,
+                schedule.start,
+                schedule.end,
+                *offset as i64,
+                schedule.total_words as i64,
+                seed,
+                params.hs as c_int,
+                mean as c_int,
+            ),
+        }
+    };
+    *offset += centers.len() as u64;
+
+    if status == ffi::status::STATUS_INTERRUPTED {
+        Ok(false)
+    } else if status != ffi::status::SUCCESS {
+        Err(format!("Training failed: {}", status))
+    } else {
+        Ok(true)
+    }
+}
+
+/// On-disk copies of inputs that can only be read once (stdin, named
+/// pipes), removed when dropped.
+struct Spool {
+    /// Inputs to read each epoch, in the original order
+    files: Vec<PathBuf>,
+    copies: Vec<PathBuf>,
+}
+
+impl Spool {
+    /// Copies every input that cannot be re-read next to `output`.
+    fn create(inputs: &[PathBuf], output: &str) -> Result<Self, String> {
+        let mut spool = Self {
+            files: Vec::with_capacity(inputs.len()),
+            copies: Vec::new(),
+        };
+
+        for (i, input) in inputs.iter().enumerate() {
+            if is_rereadable(input) {
+                spool.files.push(input.clone());
+                continue;
+            }
+
+            // Keep the file name, so format and compression are still detected
+            let name = match input.file_name() {
+                Some(name) if !is_stdin(input) => name.to_string_lossy().into_owned(),
+                _ => "stdin".to_string(),
+            };
+            let copy = PathBuf::from(format!("{}.spool{}.{}", output, i, name));
+            eprintln!("  Spooling {} to {}", input.display(), copy.display());
+
+            // Registered first, so a partial copy is removed too
+            spool.copies.push(copy.clone());
+            copy_input(input, &copy)
+                .map_err(|e| format!("Cannot spool {}: {}", input.display(), e))?;
+            spool.files.push(copy);
+        }
+
+        Ok(spool)
+    }
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        for copy in &self.copies {
+            let _ = fs::remove_file(copy);
+        }
+    }
+}
+
+/// Copies an input (or stdin) byte for byte to `dest`.
+fn copy_input(input: &Path, dest: &Path) -> io::Result<u64> {
+    let mut writer = BufWriter::new(File::create(dest)?);
+    let copied = if is_stdin(input) {
+        io::copy(&mut io::stdin().lock(), &mut writer)?
+    } else {
+        io::copy(&mut File::open(input)?, &mut writer)?
+    };
+    writer.flush()?;
+    Ok(copied)
+}
+
+/// Prints a live progress line while the Fortran trainer runs.
+///
+/// The training call blocks the calling thread, so a background thread
+/// polls `wvec_train_progress` once per second.
+struct ProgressReporter {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ProgressReporter {
+    /// Poll interval for the progress line
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Starts reporting progress against `total_words` words.
+    fn start(total_words: u64) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = Arc::clone(&stop);
+
+        let handle = thread::spawn(move || {
+            let (mut last_words, _, mut last_loss, mut last_pairs) = poll_progress();
+            let mut last_time = Instant::now();
+            let mut loss = 0.0;
+
+            while !stop_flag.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(100));
+                if last_time.elapsed() < Self::INTERVAL {
+                    continue;
+                }
+
+                let (words, lr, loss_sum, pairs) = poll_progress();
+                let elapsed = last_time.elapsed().as_secs_f64();
+                let rate = words.saturating_sub(last_words) as f64 / elapsed;
+                if pairs > last_pairs {
+                    loss = (loss_sum - last_loss) / (pairs - last_pairs) as f64;
+                }
+                let percent = 100.0 * words as f64 / total_words.max(1) as f64;
+
+                eprint!(
+                    "\r    {:5.1}%  lr: {:.6}  {} words/s  loss: {:.4}   ",
+                    percent.min(100.0),
+                    lr,
+                    format_count(rate),
+                    loss
+                );
+
+                (last_words, last_loss, last_pairs) = (words, loss_sum, pairs);
+                last_time = Instant::now();
+            }
+        });
+
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// Stops the reporter and ends the progress line.
+    fn finish(mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        eprintln!();
+    }
+}
+
+/// Reads (words, lr, cumulative loss, loss pairs) from the Fortran trainer.
+fn poll_progress() -> (u64, f32, f64, u64) {
+    let mut words: i64 = 0;
+    let mut lr: f32 = 0.0;
+    let mut loss_sum: f64 = 0.0;
+    let mut loss_pairs: i64 = 0;
+    unsafe {
+        wvec_train_progress(&mut words, &mut lr, &mut loss_sum, &mut loss_pairs);
+    }
+    (words.max(0) as u64, lr, loss_sum, loss_pairs.max(0) as u64)
+}
+
+/// Formats a count with a k/M suffix, e.g. 412500.0 -> "412.5k".
+fn format_count(n: f64) -> String {
+    if n >= 1e6 {
+        format!("{:.2}M", n / 1e6)
+    } else if n >= 1e3 {
+        format!("{:.1}k", n / 1e3)
+    } else {
+        format!("{:.0}", n)
+    }
+}
+
+/// Sets up thermal throttling from `--thermal-zone` or an auto-discovered zone.
+///
+/// An explicit zone must be readable; a missing or broken auto-discovered
+/// zone only disables throttling.
+fn setup_thermal(
+    zone: Option<String>,
+    max_temp: i32,
+    cooldown: Duration,
+) -> Result<Option<ThermalMonitor>, String> {
+    let explicit = zone.is_some();
+    let Some(path) = zone.map(PathBuf::from).or_else(discover_zone) else {
+        eprintln!("  Thermal: no sensor found, throttling disabled");
+        return Ok(None);
+    };
+
+    let monitor = ThermalMonitor::new(path, max_temp, cooldown);
+    match monitor.read_celsius() {
+        Ok(temp) => {
+            eprintln!(
+                "  Thermal: {} ({}°C, limit {}°C)",
+                monitor.path(),
+                temp,
+                max_temp
+            );
+            Ok(Some(monitor))
+        }
+        Err(e) if explicit => Err(e),
+        Err(e) => {
+            eprintln!("  Thermal: {}, throttling disabled", e);
+            Ok(None)
+        }
+    }
+}
+
+/// Loads a checkpoint to resume training from.
+///
+/// Fails if the checkpoint was trained with a different vocabulary size.
+/// Returns the last completed epoch and the saved learning rate.
+fn resume_checkpoint(path: &str, vocab_size: usize) -> Result<(usize, f32), String> {
+    let mut epoch: c_int = 0;
+    let mut lr: f32 = 0.0;
+    let mut ckpt_vocab_size: c_int = 0;
+    let mut dim: c_int = 0;
+
+    unsafe {
+        let status = wvec_checkpoint_load(
+            path.as_ptr() as *const i8,
+            path.len() as c_int,
+            &mut epoch,
+            &mut lr,
         );
         if status != ffi::status::SUCCESS {
-            wvec_model_free();
-            return Err(format!("Failed to save model: {}", status));
+            return Err(format!("Cannot load checkpoint {}: {}", path, status));
         }
+        wvec_model_get_dims(&mut ckpt_vocab_size, &mut dim);
+    }
 
-        wvec_model_free();
+    if ckpt_vocab_size as usize != vocab_size {
+        unsafe { wvec_model_free() };
+        return Err(format!(
+            "Checkpoint vocab size {} does not match vocabulary size {}",
+            ckpt_vocab_size, vocab_size
+        ));
     }
+    eprintln!("  Loaded checkpoint (dim: {})", dim);
 
-    eprintln!("\nDone! Model saved to {}", output);
+    Ok((epoch.max(0) as usize, lr))
+}
+
+/// Loads the Huffman tree of a resumed run, or builds one from the token
+/// counts, and keeps a copy at `<output>.tree`.
+fn prepare_tree(
+    resume_tree: Option<&str>,
+    counts: &[u64],
+    output: &str,
+) -> Result<HuffmanTree, String> {
+    let tree_path = format!("{}.tree", output);
+
+    let tree = match resume_tree {
+        Some(path) => {
+            eprintln!("  Loading Huffman tree from {}", path);
+            let tree = HuffmanTree::load(Path::new(path), counts.len())
+                .map_err(|e| format!("Cannot load tree {}: {}", path, e))?;
+            if path == tree_path {
+                return Ok(tree);
+            }
+            tree
+        }
+        None => HuffmanTree::build(counts),
+    };
+
+    tree.save(Path::new(&tree_path))
+        .map_err(|e| format!("Cannot save tree: {}", e))?;
+    eprintln!("  Saved Huffman tree to {}", tree_path);
+    Ok(tree)
+}
+
+/// Hands a Huffman tree to the Fortran trainer.
+fn install_tree(tree: &HuffmanTree) -> Result<(), String> {
+    let (lens, codes, points) = tree.to_padded();
+    let status = unsafe {
+        wvec_hs_set_tree(
+            tree.len() as c_int,
+            tree.max_code_len() as c_int,
+            lens.as_ptr(),
+            codes.as_ptr(),
+            points.as_ptr(),
+        )
+    };
+    if status != ffi::status::SUCCESS {
+        return Err(format!("Failed to install Huffman tree: {}", status));
+    }
     Ok(())
 }
 
-/// Build negative sampling table from token frequencies
-fn build_neg_table(token_ids: &[c_int], vocab_size: usize) -> Vec<c_int> {
-    // Count token frequencies
-    let mut counts = vec![0u64; vocab_size];
-    for &id in token_ids {
-        if (id as usize) < vocab_size {
-            counts[id as usize] += 1;
+/// Saves the current model state to a checkpoint file.
+fn save_checkpoint(path: &str, epoch: usize, lr: f32) -> Result<(), String> {
+    let status = unsafe {
+        wvec_checkpoint_save(
+            path.as_ptr() as *const i8,
+            path.len() as c_int,
+            epoch as c_int,
+            lr,
+        )
+    };
+    if status != ffi::status::SUCCESS {
+        return Err(format!("Failed to save model: {}", status));
+    }
+    Ok(())
+}
+
+/// Count token frequencies from pre-token frequencies
+fn count_tokens(word_counts: &HashMap<String, u32>, vocab: &Vocabulary) -> Vec<u64> {
+    let mut counts = vec![0u64; vocab.len()];
+    for (word, &count) in word_counts {
+        for id in encode(vocab, word) {
+            if let Some(c) = counts.get_mut(id as usize) {
+                *c += count as u64;
+            }
         }
     }
+    counts
+}
+
+/// Per-token keep probabilities for frequent-token subsampling.
+///
+/// A token with relative frequency `f` is discarded with probability
+/// `1 - sqrt(t / f)` (Mikolov et al., 2013), so tokens rarer than `t` are
+/// always kept. `t <= 0` disables subsampling.
+fn subsample_keep_probs(counts: &[u64], t: f64) -> Vec<f32> {
+    let total: u64 = counts.iter().sum();
+    if t <= 0.0 || total == 0 {
+        return vec![1.0; counts.len()];
+    }
+
+    counts
+        .iter()
+        .map(|&c| {
+            if c == 0 {
+                return 1.0;
+            }
+            let f = c as f64 / total as f64;
+            (t / f).sqrt().min(1.0) as f32
+        })
+        .collect()
+}
+
+/// Build negative sampling table from token frequencies
+fn build_neg_table(counts: &[u64]) -> Vec<c_int> {
+    let vocab_size = counts.len();
 
     // Apply 3/4 power (reduces impact of very frequent words)
     let powered: Vec<f64> = counts.iter().map(|&c| (c as f64).powf(0.75)).collect();
@@ -188,18 +964,51 @@ fn print_help() {
         "wvec train - Train word vectors
 
   USAGE:
-      wvec train --input <file> --output <file> [OPTIONS]
+      wvec train --input <path>... --output <file> [OPTIONS]
 
   OPTIONS:
-      --input <file>       Input text file
+      --input <path>...    Input corpus files, directories (read recursively)
+                           or globs such as 'dump/*.xml'; .gz and .bz2
+                           files are decompressed on the fly. '-' reads
+                           stdin; stdin and named pipes are copied to
+                           <output>.spool* for the epochs and removed after
+      --format <f>         Input format: txt, xml (Wikipedia dump) or html
+                           (default: detected from each file's extension)
       --output <file>      Output model file (.bin)
       --vocab <file>       Load existing BPE vocabulary (optional)
+      --resume <file>      Resume training from a checkpoint
+                           (uses <file>.vocab unless --vocab is given)
       --vocab-size <n>     BPE vocabulary size (default: 50000)
+      --byte-level         Byte-level BPE: any text encodes without [UNK]
+                           (ignored with --vocab)
+      --word-boundaries    Keep the space before a word in its first token
+                           (ignored with --vocab)
+      --model <m>          Architecture: skipgram or cbow (default: skipgram)
+      --cbow-sum           Sum the CBOW context vectors instead of averaging
+      --hs                 Hierarchical softmax instead of negative sampling
+                           (tree saved to <output>.tree; implied on --resume
+                           when <file>.tree exists)
       --dim <n>            Embedding dimension (default: 100)
       --window <n>         Context window size (default: 5)
       --neg-samples <n>    Negative samples (default: 5)
-      --lr <f>             Learning rate (default: 0.025)
+      --lr <f>             Initial learning rate (default: 0.025)
+      --min-lr <f>         Final learning rate of the linear decay
+                           (default: lr * 0.0001)
+      --sample <t>         Subsample tokens more frequent than t
+                           (default: 1e-3, 0 = off)
       --epochs <n>         Training epochs (default: 5)
+      --chunk-tokens <n>   Tokens per training call; bounds memory while
+                           streaming the corpus (default: 1000000)
+      --threads <n>        Training threads (default: 0 = all cores)
+      --seed <n>           Random seed (default: 42); runs are reproducible
+                           with --threads 1
+      --checkpoint-every <n>
+                           Save a checkpoint every n epochs (default: 1, 0 = off)
+      --thermal-zone <path>
+                           Temperature sensor file or thermal zone directory
+                           (default: auto-discover under /sys/class/thermal)
+      --max-temp <C>       Pause training at this temperature (default: 85)
+      --cooldown <secs>    Wait between sensor reads while paused (default: 30)
       -h, --help           Show this help message"
     );
 }
@@ -0,0 +1,144 @@
+//! Word vectors composed from BPE subword vectors
+//!
+//! A word that BPE splits into several tokens has no vector of its own.
+//! As in fastText, it gets the mean (or sum) of the vectors of its pieces,
+//! so queries are not limited to words that happen to be single tokens.
+
+use crate::bpe::{BpeTokenId, UNK_ID, Vocabulary, encode};
+use crate::text::normalize::normalize;
+use crate::text::pretokenize::pretokenize;
+
+/// How subword vectors are combined into a word vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compose {
+    /// Average of the piece vectors
+    #[default]
+    Mean,
+    /// Sum of the piece vectors
+    Sum,
+}
+
+impl Compose {
+    /// Parses a method name (`mean` or `sum`).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "mean" => Some(Self::Mean),
+            "sum" => Some(Self::Sum),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Compose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mean => write!(f, "mean"),
+            Self::Sum => write!(f, "sum"),
+        }
+    }
+}
+
+/// Returns the tokens whose vectors make up the vector of `query`.
+///
+/// A query that is a token of its own maps to that token. Otherwise it is
+/// encoded the way the training corpus was: normalized, pre-tokenized and
+/// BPE-encoded, read as a word inside a text (after a space, which matters
+/// for vocabularies with word boundaries). Unknown pieces are dropped, so
+/// the result is empty if nothing of the query is in the vocabulary.
+pub fn query_tokens(vocab: &Vocabulary, query: &str) -> Vec<BpeTokenId> {
+    if let Some(id) = vocab.get_text_id(query) {
+        return vec![id];
+    }
+
+    let mut pretokens = pretokenize(&normalize(query));
+    if let Some(first) = pretokens.first_mut() {
+        first.space_before = true;
+    }
+
+    pretokens
+        .into_iter()
+        .flat_map(|pt| encode(vocab, &pt.into_bpe_text(vocab.has_word_boundaries())))
+        .filter(|&id| id != UNK_ID)
+        .collect()
+}
+
+/// Combines the vectors of `ids` into one vector of `dim` floats.
+///
+/// `embedding` writes the vector of a token into its buffer. Returns a
+/// zero vector if `ids` is empty.
+pub fn compose<E>(
+    ids: &[BpeTokenId],
+    dim: usize,
+    method: Compose,
+    mut embedding: impl FnMut(BpeTokenId, &mut [f32]) -> Result<(), E>,
+) -> Result<Vec<f32>, E> {
+    let mut sum = vec![0.0f32; dim];
+    let mut piece = vec![0.0f32; dim];
+    for &id in ids {
+        embedding(id, &mut piece)?;
+        for (s, p) in sum.iter_mut().zip(&piece) {
+            *s += p;
+        }
+    }
+
+    if method == Compose::Mean && !ids.is_empty() {
+        let scale = 1.0 / ids.len() as f32;
+        sum.iter_mut().for_each(|s| *s *= scale);
+    }
+    Ok(sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bpe::{TrainOptions, train, train_from_counts_with};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_compose_mean_and_sum() {
+        let lookup = |id: BpeTokenId, out: &mut [f32]| -> Result<(), String> {
+            out.copy_from_slice(&[id as f32, 1.0]);
+            Ok(())
+        };
+
+        let mean = compose(&[4, 6], 2, Compose::Mean, lookup).unwrap();
+        assert_eq!(mean, vec![5.0, 1.0]);
+        let sum = compose(&[4, 6], 2, Compose::Sum, lookup).unwrap();
+        assert_eq!(sum, vec![10.0, 2.0]);
+        assert_eq!(
+            compose(&[], 2, Compose::Mean, lookup).unwrap(),
+            vec![0.0; 2]
+        );
+
+        let failing = |_: BpeTokenId, _: &mut [f32]| Err("no model");
+        assert_eq!(compose(&[4], 2, Compose::Sum, failing), Err("no model"));
+        assert_eq!(Compose::parse("SUM"), Some(Compose::Sum));
+        assert_eq!(Compose::parse("max"), None);
+    }
+
+    #[test]
+    fn test_query_tokens() {
+        let vocab = train(["low", "lower", "lowest", "low"].into_iter(), 12);
+
+        // An unseen word splits into known pieces; unknown characters are dropped
+        let ids = query_tokens(&vocab, "Lowerz");
+        assert!(ids.len() > 1);
+        assert!(!ids.contains(&UNK_ID));
+        assert!(query_tokens(&vocab, "zzz").is_empty());
+        assert_eq!(query_tokens(&vocab, "l"), vec![vocab.get_id("l")]);
+    }
+
+    #[test]
+    fn test_query_tokens_word_boundaries() {
+        let freqs = HashMap::from([(" cat".to_string(), 5), ("cat".to_string(), 1)]);
+        let options = TrainOptions {
+            word_boundaries: true,
+            ..Default::default()
+        };
+        let vocab = train_from_counts_with(&freqs, 20, options);
+
+        // A bare query word matches the word-start token
+        assert_eq!(query_tokens(&vocab, "cat"), vec![vocab.get_id(" cat")]);
+    }
+}
@@ -0,0 +1,105 @@
+//! Cosine similarity helpers
+//!
+//! Shared by the exact searches in Rust (the query server), the HNSW
+//! index and the REPL: vectors are scaled to unit length once, so a dot
+//! product is a cosine similarity, and the best `k` are kept with a
+//! bounded heap.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// A token and its similarity to the current query
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Scored {
+    pub(crate) sim: f32,
+    pub(crate) id: u32,
+}
+
+impl Eq for Scored {}
+
+impl Ord for Scored {
+    /// Higher similarity first; ties go to the lower ID
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sim
+            .total_cmp(&other.sim)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Scales every `dim`-float row to unit length; zero rows stay zero.
+pub(crate) fn normalize_rows(vectors: &mut [f32], dim: usize) {
+    for row in vectors.chunks_mut(dim) {
+        let norm = dot(row, row).sqrt();
+        if norm > 0.0 {
+            row.iter_mut().for_each(|x| *x /= norm);
+        }
+    }
+}
+
+/// Cosine similarity of two vectors; 0 if either is zero.
+pub(crate) fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let norms = (dot(a, a) * dot(b, b)).sqrt();
+    if norms > 0.0 { dot(a, b) / norms } else { 0.0 }
+}
+
+/// Keeps the `k` best of `candidates`, best first.
+pub(crate) fn top_k(candidates: impl IntoIterator<Item = Scored>, k: usize) -> Vec<Scored> {
+    if k == 0 {
+        return Vec::new();
+    }
+
+    // Min-heap of the best k so far
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for s in candidates {
+        if heap.len() < k {
+            heap.push(Reverse(s));
+        } else if heap.peek().is_some_and(|Reverse(worst)| s > *worst) {
+            heap.pop();
+            heap.push(Reverse(s));
+        }
+    }
+
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(s)| s)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_k() {
+        let scored = [0.5f32, 0.9, -0.2, 0.9, 0.1]
+            .iter()
+            .enumerate()
+            .map(|(id, &sim)| Scored { sim, id: id as u32 });
+
+        let best: Vec<u32> = top_k(scored.clone(), 3).iter().map(|s| s.id).collect();
+        assert_eq!(best, vec![1, 3, 0]);
+        assert_eq!(top_k(scored.clone(), 10).len(), 5);
+        assert!(top_k(scored, 0).is_empty());
+    }
+
+    #[test]
+    fn test_cosine_and_normalize() {
+        let sim = cosine(&[1.0, 0.0], &[2.0, 2.0]);
+        assert!((sim - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert_eq!(cosine(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+
+        let mut rows = vec![3.0, 4.0, 0.0, 0.0];
+        normalize_rows(&mut rows, 2);
+        assert_eq!(rows, vec![0.6, 0.8, 0.0, 0.0]);
+    }
+}
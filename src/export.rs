@@ -0,0 +1,271 @@
+//! Word vectors in formats other tools read
+//!
+//! - word2vec text and binary, as written by the original C tool and read
+//!   by gensim's `KeyedVectors.load_word2vec_format`
+//! - GloVe text: the word2vec text format without the header line
+//! - TSV: one tab-separated vector per line, which the TensorFlow
+//!   Embedding Projector pairs with a `metadata.tsv` of token names
+//!
+//! All of these separate a token from its vector with whitespace, so
+//! tokens are written under a whitespace-free name (see `token_name`).
+
+use crate::bpe::{BpeTokenId, Vocabulary, WORD_START_MARKER};
+use std::fmt;
+use std::io::{self, Write};
+
+/// An export format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `<count> <dim>` header, then `token v1 v2 ...` per line
+    W2vText,
+    /// `<count> <dim>` header, then `token ` and `dim` little-endian f32 per token
+    W2vBin,
+    /// `token v1 v2 ...` per line, no header
+    Glove,
+    /// `v1<TAB>v2...` per line, no tokens
+    Tsv,
+}
+
+impl Format {
+    /// Parses a format name (`w2v-text`, `w2v-bin`, `glove` or `tsv`).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "w2v-text" => Some(Self::W2vText),
+            "w2v-bin" => Some(Self::W2vBin),
+            "glove" => Some(Self::Glove),
+            "tsv" => Some(Self::Tsv),
+            _ => None,
+        }
+    }
+
+    /// Suffix of the default output file
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::W2vText => "w2v.txt",
+            Self::W2vBin => "w2v.bin",
+            Self::Glove => "glove.txt",
+            Self::Tsv => "vectors.tsv",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::W2vText => write!(f, "w2v-text"),
+            Self::W2vBin => write!(f, "w2v-bin"),
+            Self::Glove => write!(f, "glove"),
+            Self::Tsv => write!(f, "tsv"),
+        }
+    }
+}
+
+/// Returns the name a token is exported under.
+///
+/// This is the text of the token, as `info` shows it: a space is written
+/// as `WORD_START_MARKER` and other whitespace and control characters as
+/// `\u{..}` escapes, as is a literal `WORD_START_MARKER`. Bytes of a
+/// byte-level token that are not whole UTF-8 are written as `<0xNN>`, so
+/// that no two tokens share a name.
+pub fn token_name(vocab: &Vocabulary, id: BpeTokenId) -> String {
+    let bytes = vocab.get_bytes(id).unwrap_or_default();
+
+    let mut name = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                ' ' => name.push(WORD_START_MARKER),
+                c if c.is_whitespace() || c.is_control() || c == WORD_START_MARKER => {
+                    name.extend(c.escape_unicode())
+                }
+                c => name.push(c),
+            }
+        }
+        for b in chunk.invalid() {
+            name.push_str(&format!("<0x{:02X}>", b));
+        }
+    }
+    name
+}
+
+/// Writes the vectors of `ids` (rows of `vectors`, `dim` floats per token
+/// ID) under their token names.
+pub fn write_vectors<W: Write>(
+    writer: &mut W,
+    format: Format,
+    vocab: &Vocabulary,
+    ids: &[BpeTokenId],
+    vectors: &[f32],
+    dim: usize,
+) -> io::Result<()> {
+    if matches!(format, Format::W2vText | Format::W2vBin) {
+        writeln!(writer, "{} {}", ids.len(), dim)?;
+    }
+
+    for &id in ids {
+        let start = id as usize * dim;
+        let row = &vectors[start..start + dim];
+        match format {
+            Format::W2vText | Format::Glove => {
+                writer.write_all(token_name(vocab, id).as_bytes())?;
+                for x in row {
+                    write!(writer, " {:.6}", x)?;
+                }
+                writer.write_all(b"\n")?;
+            }
+            Format::W2vBin => {
+                writer.write_all(token_name(vocab, id).as_bytes())?;
+                writer.write_all(b" ")?;
+                for x in row {
+                    writer.write_all(&x.to_le_bytes())?;
+                }
+                writer.write_all(b"\n")?;
+            }
+            Format::Tsv => {
+                for (i, x) in row.iter().enumerate() {
+                    if i > 0 {
+                        writer.write_all(b"\t")?;
+                    }
+                    write!(writer, "{:.6}", x)?;
+                }
+                writer.write_all(b"\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes an Embedding Projector `metadata.tsv` for the rows written by
+/// `write_vectors`: a header, then the name and ID of every token and its
+/// corpus count if `counts` is given.
+pub fn write_metadata<W: Write>(
+    writer: &mut W,
+    vocab: &Vocabulary,
+    ids: &[BpeTokenId],
+    counts: Option<&[u64]>,
+) -> io::Result<()> {
+    match counts {
+        Some(_) => writeln!(writer, "token\tid\tcount")?,
+        None => writeln!(writer, "token\tid")?,
+    }
+    for &id in ids {
+        write!(writer, "{}\t{}", token_name(vocab, id), id)?;
+        if let Some(counts) = counts {
+            write!(
+                writer,
+                "\t{}",
+                counts.get(id as usize).copied().unwrap_or(0)
+            )?;
+        }
+        writer.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bpe::{TrainOptions, train, train_from_counts_with};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_token_name() {
+        let freqs = HashMap::from([
+            (" cat".to_string(), 5),
+            ("a\tb".to_string(), 3),
+            (format!("{}x", WORD_START_MARKER), 2),
+            (" x".to_string(), 2),
+        ]);
+        let options = TrainOptions {
+            word_boundaries: true,
+            ..Default::default()
+        };
+        let vocab = train_from_counts_with(&freqs, 30, options);
+
+        let cat = vocab.get_id(" cat");
+        assert_eq!(token_name(&vocab, cat), format!("{}cat", WORD_START_MARKER));
+        assert_eq!(token_name(&vocab, vocab.get_id("\t")), "\\u{9}");
+        assert_eq!(token_name(&vocab, 0), "[UNK]");
+
+        // A literal marker is escaped, so it differs from a space
+        let marker_x = vocab.get_id(&format!("{}x", WORD_START_MARKER));
+        let space_x = vocab.get_id(" x");
+        assert_ne!(marker_x, space_x);
+        assert_eq!(token_name(&vocab, marker_x), "\\u{120}x");
+        assert_eq!(
+            token_name(&vocab, space_x),
+            format!("{}x", WORD_START_MARKER)
+        );
+
+        // Byte tokens inside a UTF-8 sequence show their bytes
+        let options = TrainOptions {
+            byte_level: true,
+            ..Default::default()
+        };
+        let freqs = HashMap::from([("é".to_string(), 1)]);
+        let vocab = train_from_counts_with(&freqs, 300, options);
+        let names: Vec<String> = (0..vocab.len() as u32)
+            .map(|id| token_name(&vocab, id))
+            .collect();
+        let unique: std::collections::HashSet<&String> = names.iter().collect();
+        assert_eq!(unique.len(), names.len());
+        assert!(names.iter().all(|n| !n.contains(char::is_whitespace)));
+        assert!(names.contains(&"é".to_string()));
+        assert!(names.contains(&"<0xC3>".to_string()));
+    }
+
+    #[test]
+    fn test_write_formats() {
+        let vocab = train(["ab", "ab", "b"].into_iter(), 10);
+        let ab = vocab.get_id("ab");
+        let b = vocab.get_id("b");
+        let dim = 2;
+        let mut vectors = vec![0.0f32; vocab.len() * dim];
+        vectors[ab as usize * dim..][..dim].copy_from_slice(&[0.5, -1.0]);
+        vectors[b as usize * dim..][..dim].copy_from_slice(&[2.0, 0.25]);
+        let ids = [ab, b];
+
+        let write = |format| {
+            let mut out = Vec::new();
+            write_vectors(&mut out, format, &vocab, &ids, &vectors, dim).unwrap();
+            out
+        };
+
+        assert_eq!(
+            String::from_utf8(write(Format::W2vText)).unwrap(),
+            "2 2\nab 0.500000 -1.000000\nb 2.000000 0.250000\n"
+        );
+        assert_eq!(
+            String::from_utf8(write(Format::Glove)).unwrap(),
+            "ab 0.500000 -1.000000\nb 2.000000 0.250000\n"
+        );
+        assert_eq!(
+            String::from_utf8(write(Format::Tsv)).unwrap(),
+            "0.500000\t-1.000000\n2.000000\t0.250000\n"
+        );
+
+        let mut expected = b"2 2\nab ".to_vec();
+        for x in [0.5f32, -1.0] {
+            expected.extend_from_slice(&x.to_le_bytes());
+        }
+        expected.extend_from_slice(b"\nb ");
+        for x in [2.0f32, 0.25] {
+            expected.extend_from_slice(&x.to_le_bytes());
+        }
+        expected.push(b'\n');
+        assert_eq!(write(Format::W2vBin), expected);
+
+        let mut out = Vec::new();
+        let mut counts = vec![0u64; vocab.len()];
+        counts[ab as usize] = 2;
+        write_metadata(&mut out, &vocab, &ids, Some(&counts)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("token\tid\tcount\nab\t{}\t2\nb\t{}\t0\n", ab, b)
+        );
+
+        assert_eq!(Format::parse("W2V-BIN"), Some(Format::W2vBin));
+        assert_eq!(Format::parse("fasttext"), None);
+    }
+}
@@ -0,0 +1,700 @@
+//! Approximate nearest-neighbour search (HNSW)
+//!
+//! A hierarchical navigable small world graph (Malkov & Yashunin, 2018).
+//! Every vector is a node on layer 0 and, with geometrically falling
+//! probability, on the layers above. A search walks greedily down from the
+//! sparse top layer and then explores layer 0 with a beam of `ef`
+//! candidates, so it visits a small part of the vocabulary instead of all
+//! of it.
+//!
+//! Similarity is cosine: vectors are L2-normalized when the index is built
+//! or loaded. The index file holds only the graph; the vectors come from
+//! the model it was built for. A fingerprint of those vectors is stored
+//! with the graph, so an index left over from an earlier training run of
+//! the same shape is detected instead of silently searched.
+//!
+//! # File Format (v2)
+//!
+//! All integers are little-endian.
+//!
+//! ```text
+//! ┌─────────────────────────────────────────────────────────┐
+//! │                        HEADER                           │
+//! ├──────────────┬──────────┬───────────────────────────────┤
+//! │ magic        │ [u8; 4]  │ "WVHN" - file identifier      │
+//! │ version      │ u32      │ format version (currently 2)  │
+//! │ count        │ u32      │ number of vectors (nodes)     │
+//! │ dim          │ u32      │ vector dimension              │
+//! │ m            │ u32      │ links per node (2m on layer 0)│
+//! │ max_level    │ u32      │ top layer                     │
+//! │ entry        │ u32      │ entry node (on the top layer) │
+//! │ fingerprint  │ u64      │ FNV-1a hash of the vectors    │
+//! ├──────────────┴──────────┴───────────────────────────────┤
+//! │                     NODES SECTION                       │
+//! │  Repeated `count` times, in ID order (0, 1, 2...)       │
+//! ├──────────────┬──────────┬───────────────────────────────┤
+//! │ layers       │ u32      │ node level + 1                │
+//! ├──────────────┴──────────┴───────────────────────────────┤
+//! │  Repeated `layers` times, from layer 0 up               │
+//! ├──────────────┬──────────┬───────────────────────────────┤
+//! │ len          │ u32      │ number of neighbours          │
+//! │ ids          │ [u32;len]│ neighbour IDs                 │
+//! └──────────────┴──────────┴───────────────────────────────┘
+//! ```
+
+use crate::cosine::{Scored, dot, normalize_rows};
+use crate::rng::{DEFAULT_SEED, Rng};
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Magic bytes identifying an HNSW index file
+const MAGIC: &[u8; 4] = b"WVHN";
+
+/// Current file format version
+const VERSION: u32 = 2;
+
+/// Highest layer a node can be drawn on
+const MAX_LEVEL: usize = 32;
+
+/// Most links per node on the upper layers
+pub const MAX_M: usize = 1024;
+
+/// Default number of links per node
+pub const DEFAULT_M: usize = 16;
+
+/// Default beam width while building
+pub const DEFAULT_EF_CONSTRUCTION: usize = 200;
+
+/// Default beam width while searching
+pub const DEFAULT_EF_SEARCH: usize = 64;
+
+/// Index construction parameters
+#[derive(Debug, Clone, Copy)]
+pub struct HnswParams {
+    /// Links per node on the upper layers; layer 0 keeps up to `2 * m`
+    pub m: usize,
+    /// Beam width when looking for the neighbours of a new node
+    pub ef_construction: usize,
+    /// Seed for drawing node levels
+    pub seed: u64,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: DEFAULT_M,
+            ef_construction: DEFAULT_EF_CONSTRUCTION,
+            seed: DEFAULT_SEED,
+        }
+    }
+}
+
+/// An HNSW graph over a set of unit vectors
+#[derive(Debug, Clone)]
+pub struct Hnsw {
+    dim: usize,
+    m: usize,
+    /// Normalized vectors, `dim` floats per node
+    vectors: Vec<f32>,
+    /// Neighbours of each node, per layer from 0 up to the node's level
+    links: Vec<Vec<Vec<u32>>>,
+    entry: u32,
+    max_level: usize,
+    /// `fingerprint` of the vectors as given to `build`
+    fingerprint: u64,
+}
+
+/// Visited set for graph searches, cleared in O(1) by bumping an epoch
+struct Visited {
+    marks: Vec<u32>,
+    epoch: u32,
+}
+
+thread_local! {
+    /// Visited set shared by the searches on a thread, so a query does not
+    /// allocate a vocabulary-sized buffer
+    static VISITED: RefCell<Visited> = RefCell::new(Visited::new(0));
+}
+
+impl Visited {
+    fn new(n: usize) -> Self {
+        Self {
+            marks: vec![0; n],
+            epoch: 0,
+        }
+    }
+
+    /// Makes room for node IDs below `n`.
+    fn grow(&mut self, n: usize) {
+        if self.marks.len() < n {
+            self.marks.resize(n, 0);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.epoch = self.epoch.wrapping_add(1);
+        if self.epoch == 0 {
+            self.marks.fill(0);
+            self.epoch = 1;
+        }
+    }
+
+    /// Marks `id`; returns false if it was already visited.
+    fn insert(&mut self, id: u32) -> bool {
+        let mark = &mut self.marks[id as usize];
+        if *mark == self.epoch {
+            return false;
+        }
+        *mark = self.epoch;
+        true
+    }
+}
+
+impl Hnsw {
+    /// Builds an index over `vectors`, `dim` floats each, in ID order.
+    pub fn build(vectors: Vec<f32>, dim: usize, params: HnswParams) -> Self {
+        assert!(dim > 0 && vectors.len().is_multiple_of(dim));
+        let n = vectors.len() / dim;
+        let fingerprint = fingerprint(&vectors);
+
+        let mut index = Self {
+            dim,
+            m: params.m.clamp(2, MAX_M),
+            vectors,
+            links: Vec::with_capacity(n),
+            entry: 0,
+            max_level: 0,
+            fingerprint,
+        };
+        normalize_rows(&mut index.vectors, dim);
+
+        // P(level >= l) = m^-l
+        let level_mult = 1.0 / (index.m as f64).ln();
+        let mut rng = Rng::new(params.seed);
+        let mut visited = Visited::new(n);
+        for id in 0..n as u32 {
+            let u = 1.0 - rng.next_f32() as f64;
+            let level = ((-u.ln() * level_mult) as usize).min(MAX_LEVEL);
+            index.insert(id, level, params.ef_construction.max(1), &mut visited);
+        }
+
+        index
+    }
+
+    /// Number of indexed vectors
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    /// True if the index has no vectors
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// Vector dimension
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// Number of layers
+    pub fn layers(&self) -> usize {
+        if self.is_empty() {
+            0
+        } else {
+            self.max_level + 1
+        }
+    }
+
+    /// Returns up to `k` nodes most similar to `query` (cosine), best
+    /// first, leaving out `exclude`. A wider beam `ef` finds more of the
+    /// true neighbours at the cost of speed.
+    pub fn search(&self, query: &[f32], k: usize, ef: usize, exclude: &[u32]) -> Vec<(u32, f32)> {
+        if self.is_empty() || k == 0 {
+            return Vec::new();
+        }
+
+        let mut q = query.to_vec();
+        normalize_rows(&mut q, self.dim);
+
+        let mut ep = Scored {
+            sim: dot(&q, self.vector(self.entry)),
+            id: self.entry,
+        };
+        for layer in (1..=self.max_level).rev() {
+            ep = self.greedy(&q, ep, layer);
+        }
+
+        let ef = ef.max(k + exclude.len());
+        let found = VISITED.with(|visited| {
+            let mut visited = visited.borrow_mut();
+            visited.grow(self.len());
+            self.search_layer(&q, &[ep], ef, 0, &mut visited)
+        });
+        found
+            .into_iter()
+            .filter(|s| !exclude.contains(&s.id))
+            .take(k)
+            .map(|s| (s.id, s.sim))
+            .collect()
+    }
+
+    /// Searches each of the query vectors in `queries` (`dim` floats each),
+    /// with `exclude[i]` left out of the results of query `i`.
+    pub fn search_batch(
+        &self,
+        queries: &[f32],
+        k: usize,
+        ef: usize,
+        exclude: &[Vec<u32>],
+    ) -> Vec<Vec<(u32, f32)>> {
+        queries
+            .chunks(self.dim)
+            .zip(exclude)
+            .map(|(q, exclude)| self.search(q, k, ef, exclude))
+            .collect()
+    }
+
+    fn vector(&self, id: u32) -> &[f32] {
+        let start = id as usize * self.dim;
+        &self.vectors[start..start + self.dim]
+    }
+
+    /// Link capacity of a node on `layer`
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 { 2 * self.m } else { self.m }
+    }
+
+    /// Adds node `id` (the next ID) with top layer `level`.
+    fn insert(&mut self, id: u32, level: usize, ef: usize, visited: &mut Visited) {
+        self.links.push(vec![Vec::new(); level + 1]);
+        if id == 0 {
+            self.entry = 0;
+            self.max_level = level;
+            return;
+        }
+
+        let q = self.vector(id).to_vec();
+        let mut ep = Scored {
+            sim: dot(&q, self.vector(self.entry)),
+            id: self.entry,
+        };
+        for layer in (level + 1..=self.max_level).rev() {
+            ep = self.greedy(&q, ep, layer);
+        }
+
+        let mut entries = vec![ep];
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let candidates = self.search_layer(&q, &entries, ef, layer, visited);
+            let neighbours = self.select(&candidates, self.m);
+            for &n in &neighbours {
+                self.connect(n, id, layer);
+            }
+            self.links[id as usize][layer] = neighbours;
+            entries = candidates;
+        }
+
+        if level > self.max_level {
+            self.max_level = level;
+            self.entry = id;
+        }
+    }
+
+    /// Adds a link from `node` to `new` on `layer`, pruning the node's
+    /// links if it has too many.
+    fn connect(&mut self, node: u32, new: u32, layer: usize) {
+        let max = self.max_links(layer);
+        let links = &mut self.links[node as usize][layer];
+        links.push(new);
+        if links.len() <= max {
+            return;
+        }
+
+        let base = self.vector(node);
+        let mut candidates: Vec<Scored> = self.links[node as usize][layer]
+            .iter()
+            .map(|&n| Scored {
+                sim: dot(base, self.vector(n)),
+                id: n,
+            })
+            .collect();
+        candidates.sort_unstable_by(|a, b| b.cmp(a));
+        self.links[node as usize][layer] = self.select(&candidates, max);
+    }
+
+    /// Picks up to `m` neighbours from `candidates` (best first), skipping
+    /// any that is more similar to an already picked one than to the base
+    /// node, so links spread out in different directions.
+    fn select(&self, candidates: &[Scored], m: usize) -> Vec<u32> {
+        let mut picked: Vec<u32> = Vec::with_capacity(m);
+        for c in candidates {
+            if picked.len() == m {
+                break;
+            }
+            let v = self.vector(c.id);
+            if picked.iter().all(|&p| dot(v, self.vector(p)) < c.sim) {
+                picked.push(c.id);
+            }
+        }
+        picked
+    }
+
+    /// Follows the most similar neighbour on `layer` until none improves.
+    fn greedy(&self, q: &[f32], mut best: Scored, layer: usize) -> Scored {
+        loop {
+            let mut improved = false;
+            for &n in &self.links[best.id as usize][layer] {
+                let sim = dot(q, self.vector(n));
+                if sim > best.sim {
+                    best = Scored { sim, id: n };
+                    improved = true;
+                }
+            }
+            if !improved {
+                return best;
+            }
+        }
+    }
+
+    /// Beam search on `layer` from `entries`; returns the best `ef` nodes
+    /// found, best first.
+    fn search_layer(
+        &self,
+        q: &[f32],
+        entries: &[Scored],
+        ef: usize,
+        layer: usize,
+        visited: &mut Visited,
+    ) -> Vec<Scored> {
+        visited.clear();
+        let mut candidates: BinaryHeap<Scored> = BinaryHeap::new();
+        let mut results: BinaryHeap<Reverse<Scored>> = BinaryHeap::new();
+        for &e in entries {
+            if visited.insert(e.id) {
+                candidates.push(e);
+                results.push(Reverse(e));
+            }
+        }
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(c) = candidates.pop() {
+            let worst = results.peek().map_or(f32::NEG_INFINITY, |r| r.0.sim);
+            if c.sim < worst && results.len() >= ef {
+                break;
+            }
+
+            for &n in &self.links[c.id as usize][layer] {
+                if !visited.insert(n) {
+                    continue;
+                }
+                let s = Scored {
+                    sim: dot(q, self.vector(n)),
+                    id: n,
+                };
+                let worst = results.peek().map_or(f32::NEG_INFINITY, |r| r.0.sim);
+                if results.len() < ef || s.sim > worst {
+                    candidates.push(s);
+                    results.push(Reverse(s));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        let mut found: Vec<Scored> = results.into_iter().map(|Reverse(s)| s).collect();
+        found.sort_unstable_by(|a, b| b.cmp(a));
+        found
+    }
+
+    /// Saves the graph to a binary file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+        write_u32(&mut writer, VERSION)?;
+        write_u32(&mut writer, self.len() as u32)?;
+        write_u32(&mut writer, self.dim as u32)?;
+        write_u32(&mut writer, self.m as u32)?;
+        write_u32(&mut writer, self.max_level as u32)?;
+        write_u32(&mut writer, self.entry)?;
+        writer.write_all(&self.fingerprint.to_le_bytes())?;
+
+        for layers in &self.links {
+            write_u32(&mut writer, layers.len() as u32)?;
+            for links in layers {
+                write_u32(&mut writer, links.len() as u32)?;
+                for &n in links {
+                    write_u32(&mut writer, n)?;
+                }
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Loads a graph from a binary file and attaches `vectors`, `dim`
+    /// floats each, which must be the ones it was built from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, has invalid format, or
+    /// was built from other vectors (a different number, dimension or
+    /// fingerprint, as after retraining the model).
+    pub fn load(path: &Path, mut vectors: Vec<f32>, dim: usize) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("invalid magic bytes".to_string()));
+        }
+
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported version: {}", version)));
+        }
+
+        let count = read_u32(&mut reader)? as usize;
+        let file_dim = read_u32(&mut reader)? as usize;
+        if dim == 0 || file_dim != dim || vectors.len() != count * dim {
+            return Err(invalid(format!(
+                "index is for {} vectors of dim {}, model has {} of dim {}",
+                count,
+                file_dim,
+                vectors.len() / dim.max(1),
+                dim
+            )));
+        }
+        let m = read_u32(&mut reader)? as usize;
+        let max_level = read_u32(&mut reader)? as usize;
+        let entry = read_u32(&mut reader)?;
+        if !(2..=MAX_M).contains(&m)
+            || max_level > MAX_LEVEL
+            || (count > 0 && entry as usize >= count)
+        {
+            return Err(invalid("invalid header".to_string()));
+        }
+        let mut buf = [0u8; 8];
+        reader.read_exact(&mut buf)?;
+        let stored = u64::from_le_bytes(buf);
+        if stored != fingerprint(&vectors) {
+            return Err(invalid(
+                "index was built from other vectors; rebuild it with 'wvec index build'"
+                    .to_string(),
+            ));
+        }
+
+        let mut links = Vec::with_capacity(count);
+        for _ in 0..count {
+            let layers = read_u32(&mut reader)? as usize;
+            if layers == 0 || layers > max_level + 1 {
+                return Err(invalid(format!("invalid layer count: {}", layers)));
+            }
+            let mut node = Vec::with_capacity(layers);
+            for layer in 0..layers {
+                let len = read_u32(&mut reader)? as usize;
+                if len > if layer == 0 { 2 * m } else { m } {
+                    return Err(invalid(format!("too many links: {}", len)));
+                }
+                let mut ids = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let id = read_u32(&mut reader)?;
+                    if id as usize >= count {
+                        return Err(invalid(format!("link to unknown node: {}", id)));
+                    }
+                    ids.push(id);
+                }
+                node.push(ids);
+            }
+            links.push(node);
+        }
+
+        // Every node must have its links on the layers it is linked from
+        for node in &links {
+            for (layer, ids) in node.iter().enumerate() {
+                if ids.iter().any(|&n| links[n as usize].len() <= layer) {
+                    return Err(invalid("link above the target's level".to_string()));
+                }
+            }
+        }
+        if count > 0 && links[entry as usize].len() != max_level + 1 {
+            return Err(invalid("entry node is not on the top layer".to_string()));
+        }
+
+        normalize_rows(&mut vectors, dim);
+        Ok(Self {
+            dim,
+            m,
+            vectors,
+            links,
+            entry,
+            max_level,
+            fingerprint: stored,
+        })
+    }
+}
+
+/// 64-bit FNV-1a hash of the little-endian bytes of `vectors`
+pub fn fingerprint(vectors: &[f32]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for x in vectors {
+        for b in x.to_le_bytes() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_vectors(n: usize, dim: usize, seed: u64) -> Vec<f32> {
+        let mut rng = Rng::new(seed);
+        (0..n * dim).map(|_| rng.next_f32() * 2.0 - 1.0).collect()
+    }
+
+    /// Exact top-k by cosine similarity
+    fn brute_force(vectors: &[f32], dim: usize, query: &[f32], k: usize) -> Vec<u32> {
+        let mut rows = vectors.to_vec();
+        normalize_rows(&mut rows, dim);
+        let mut q = query.to_vec();
+        normalize_rows(&mut q, dim);
+
+        let mut scored: Vec<Scored> = rows
+            .chunks(dim)
+            .enumerate()
+            .map(|(id, v)| Scored {
+                sim: dot(&q, v),
+                id: id as u32,
+            })
+            .collect();
+        scored.sort_unstable_by(|a, b| b.cmp(a));
+        scored.iter().take(k).map(|s| s.id).collect()
+    }
+
+    fn recall_at(index: &Hnsw, vectors: &[f32], queries: &[f32], k: usize, ef: usize) -> f64 {
+        let dim = index.dim();
+        let mut hits = 0;
+        for q in queries.chunks(dim) {
+            let exact = brute_force(vectors, dim, q, k);
+            let found = index.search(q, k, ef, &[]);
+            hits += found.iter().filter(|(id, _)| exact.contains(id)).count();
+        }
+        hits as f64 / (queries.len() / dim * k) as f64
+    }
+
+    #[test]
+    fn test_recall_against_exact_search() {
+        let dim = 32;
+        let vectors = random_vectors(2000, dim, 1);
+        let queries = random_vectors(50, dim, 2);
+        let index = Hnsw::build(vectors.clone(), dim, HnswParams::default());
+
+        assert_eq!(index.len(), 2000);
+        assert!(index.layers() > 1);
+        let recall = recall_at(&index, &vectors, &queries, 10, DEFAULT_EF_SEARCH);
+        assert!(recall >= 0.9, "recall@10 = {}", recall);
+
+        // A wider beam does not find fewer true neighbours
+        let wide = recall_at(&index, &vectors, &queries, 10, 400);
+        assert!(wide >= recall && wide >= 0.98, "recall@10 = {}", wide);
+    }
+
+    #[test]
+    fn test_search_finds_self_and_excludes() {
+        let dim = 8;
+        let vectors = random_vectors(300, dim, 3);
+        let index = Hnsw::build(vectors.clone(), dim, HnswParams::default());
+
+        let q = &vectors[5 * dim..6 * dim];
+        let found = index.search(q, 5, 32, &[]);
+        assert_eq!(found[0].0, 5);
+        assert!((found[0].1 - 1.0).abs() < 1e-5);
+        assert!(found.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        let found = index.search(q, 5, 32, &[5]);
+        assert_eq!(found.len(), 5);
+        assert!(found.iter().all(|&(id, _)| id != 5));
+
+        // Searches of a smaller and a larger index share the visited set
+        let small = Hnsw::build(vectors[..20 * dim].to_vec(), dim, HnswParams::default());
+        assert_eq!(small.search(q, 1, 32, &[])[0].0, 5);
+        assert_eq!(index.search(q, 5, 32, &[5]), found);
+    }
+
+    #[test]
+    fn test_small_and_empty() {
+        let empty = Hnsw::build(Vec::new(), 4, HnswParams::default());
+        assert!(empty.is_empty());
+        assert!(empty.search(&[1.0, 0.0, 0.0, 0.0], 3, 10, &[]).is_empty());
+
+        let index = Hnsw::build(
+            vec![1.0, 0.0, 0.0, 1.0, -1.0, 0.0],
+            2,
+            HnswParams::default(),
+        );
+        let found = index.search(&[2.0, 0.1], 10, 10, &[]);
+        let ids: Vec<u32> = found.iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let dim = 8;
+        let vectors = random_vectors(200, dim, 4);
+        let queries = random_vectors(5, dim, 5);
+        let index = Hnsw::build(vectors.clone(), dim, HnswParams::default());
+
+        let path = std::env::temp_dir().join("wvec_test_index.hnsw");
+        index.save(&path).unwrap();
+        let loaded = Hnsw::load(&path, vectors.clone(), dim).unwrap();
+        assert_eq!(loaded.layers(), index.layers());
+        for q in queries.chunks(dim) {
+            assert_eq!(loaded.search(q, 10, 50, &[]), index.search(q, 10, 50, &[]));
+        }
+
+        // Vectors that do not match the index are rejected
+        assert!(Hnsw::load(&path, vectors[..dim * 100].to_vec(), dim).is_err());
+        assert!(Hnsw::load(&path, vectors.clone(), dim / 2).is_err());
+
+        // So are retrained vectors of the same shape
+        let mut retrained = vectors.clone();
+        retrained[17] += 1e-3;
+        let err = Hnsw::load(&path, retrained, dim).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("rebuild"));
+
+        // A corrupt link count is an error, not a huge allocation
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        let err = Hnsw::load(&path, vectors.clone(), dim).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        std::fs::write(&path, b"XXXX").unwrap();
+        assert!(Hnsw::load(&path, vectors, dim).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,347 @@
+//! Huffman tree for hierarchical softmax
+//!
+//! Built from token counts, as in word2vec: frequent tokens get short
+//! codes. Each token stores its path from the root as a sequence of inner
+//! node ids (`points`) and branch bits (`codes`). Inner nodes are numbered
+//! `0..vocab_size - 1`, the root being the last one.
+//!
+//! # File Format (v1)
+//!
+//! All integers are little-endian.
+//!
+//! ```text
+//! ┌─────────────────────────────────────────────────────────┐
+//! │                        HEADER                           │
+//! ├──────────────┬──────────┬───────────────────────────────┤
+//! │ magic        │ [u8; 4]  │ "WVHT" - file identifier      │
+//! │ version      │ u32      │ format version (currently 1)  │
+//! │ vocab_size   │ u32      │ number of leaves              │
+//! ├──────────────┴──────────┴───────────────────────────────┤
+//! │                    PATHS SECTION                        │
+//! │  Repeated `vocab_size` times, in ID order (0, 1, 2...)  │
+//! ├──────────────┬──────────┬───────────────────────────────┤
+//! │ len          │ u32      │ code length (path depth)      │
+//! │ codes        │ [u8;len] │ branch bits, root first       │
+//! │ points       │ [u32;len]│ inner node ids, root first    │
+//! └──────────────┴──────────┴───────────────────────────────┘
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Magic bytes identifying a Huffman tree file
+const MAGIC: &[u8; 4] = b"WVHT";
+
+/// Current file format version
+const VERSION: u32 = 1;
+
+/// Huffman codes and paths for every token of a vocabulary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffmanTree {
+    codes: Vec<Vec<u8>>,
+    points: Vec<Vec<u32>>,
+}
+
+impl HuffmanTree {
+    /// Builds the tree from per-token counts (index = token id).
+    ///
+    /// Ties are broken by node id, so the same counts always give the same tree.
+    pub fn build(counts: &[u64]) -> Self {
+        let n = counts.len();
+        if n < 2 {
+            return Self {
+                codes: vec![Vec::new(); n],
+                points: vec![Vec::new(); n],
+            };
+        }
+
+        // Nodes 0..n are leaves, n..2n-1 inner nodes in creation order
+        let mut parent = vec![0usize; 2 * n - 1];
+        let mut bit = vec![0u8; 2 * n - 1];
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> = counts
+            .iter()
+            .enumerate()
+            .map(|(id, &count)| Reverse((count, id)))
+            .collect();
+
+        for inner in n..2 * n - 1 {
+            let Reverse((count_a, a)) = heap.pop().unwrap();
+            let Reverse((count_b, b)) = heap.pop().unwrap();
+            parent[a] = inner;
+            parent[b] = inner;
+            bit[b] = 1;
+            heap.push(Reverse((count_a + count_b, inner)));
+        }
+
+        let root = 2 * n - 2;
+        let mut codes = Vec::with_capacity(n);
+        let mut points = Vec::with_capacity(n);
+        for leaf in 0..n {
+            let mut code = Vec::new();
+            let mut point = Vec::new();
+            let mut node = leaf;
+            while node != root {
+                code.push(bit[node]);
+                point.push((parent[node] - n) as u32);
+                node = parent[node];
+            }
+            code.reverse();
+            point.reverse();
+            codes.push(code);
+            points.push(point);
+        }
+
+        Self { codes, points }
+    }
+
+    /// Number of leaves (tokens).
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Returns true if the tree has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Branch bits from the root to a token's leaf.
+    pub fn code(&self, id: usize) -> &[u8] {
+        &self.codes[id]
+    }
+
+    /// Inner node ids from the root to a token's leaf.
+    pub fn points(&self, id: usize) -> &[u32] {
+        &self.points[id]
+    }
+
+    /// Length of the longest code.
+    pub fn max_code_len(&self) -> usize {
+        self.codes.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Flattens the paths into `(code_lens, codes, points)` padded to
+    /// `max_code_len` per token, in the column-major layout Fortran expects.
+    pub fn to_padded(&self) -> (Vec<i32>, Vec<i8>, Vec<i32>) {
+        let max_len = self.max_code_len();
+        let mut lens = Vec::with_capacity(self.len());
+        let mut codes = vec![0i8; self.len() * max_len];
+        let mut points = vec![0i32; self.len() * max_len];
+
+        for id in 0..self.len() {
+            lens.push(self.codes[id].len() as i32);
+            let base = id * max_len;
+            for (d, (&c, &p)) in self.codes[id].iter().zip(&self.points[id]).enumerate() {
+                codes[base + d] = c as i8;
+                points[base + d] = p as i32;
+            }
+        }
+
+        (lens, codes, points)
+    }
+
+    /// Saves the tree to a binary file.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.len() as u32).to_le_bytes())?;
+
+        for (code, point) in self.codes.iter().zip(&self.points) {
+            writer.write_all(&(code.len() as u32).to_le_bytes())?;
+            writer.write_all(code)?;
+            for &p in point {
+                writer.write_all(&p.to_le_bytes())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Loads the tree of a `vocab_size` vocabulary from a binary file.
+    ///
+    /// Sizes read from the file are checked against `vocab_size` and the
+    /// file length before anything is allocated for them.
+    pub fn load(path: &Path, vocab_size: usize) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("invalid magic bytes".to_string()));
+        }
+
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported version: {}", version)));
+        }
+
+        let n = read_u32(&mut reader)? as usize;
+        if n != vocab_size {
+            return Err(invalid(format!(
+                "tree has {} leaves, vocabulary has {}",
+                n, vocab_size
+            )));
+        }
+        // Header, then at least a code length per leaf
+        if file_len < 12 + 4 * n as u64 {
+            return Err(invalid(format!("file too short for {} leaves", n)));
+        }
+
+        let mut codes = Vec::with_capacity(n);
+        let mut points = Vec::with_capacity(n);
+        for _ in 0..n {
+            let len = read_u32(&mut reader)? as usize;
+            // A path never visits more inner nodes than there are
+            if len >= n.max(1) {
+                return Err(invalid(format!("code too long: {}", len)));
+            }
+
+            // Read as far as the file goes, so a bad length cannot allocate
+            let mut code = Vec::new();
+            Read::take(&mut reader, len as u64).read_to_end(&mut code)?;
+            if code.len() != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let mut point = Vec::with_capacity(len);
+            for _ in 0..len {
+                let p = read_u32(&mut reader)?;
+                if p as usize >= n - 1 {
+                    return Err(invalid(format!("inner node out of range: {}", p)));
+                }
+                point.push(p);
+            }
+            if code.iter().any(|&c| c > 1) {
+                return Err(invalid("branch bit is not 0 or 1".to_string()));
+            }
+
+            codes.push(code);
+            points.push(point);
+        }
+
+        Ok(Self { codes, points })
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Reads a u32 in little-endian format.
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashSet;
+    use std::fs;
+
+    #[test]
+    fn test_frequent_tokens_get_short_codes() {
+        let tree = HuffmanTree::build(&[1, 1, 2, 4, 8]);
+
+        assert_eq!(tree.code(4).len(), 1);
+        assert_eq!(tree.code(3).len(), 2);
+        assert_eq!(tree.code(0).len(), 4);
+        assert_eq!(tree.max_code_len(), 4);
+
+        // Every path starts at the root, the last inner node
+        for id in 0..tree.len() {
+            assert_eq!(tree.points(id)[0], 3);
+        }
+    }
+
+    #[test]
+    fn test_codes_are_prefix_free() {
+        let counts: Vec<u64> = (0..50).map(|i| (i * i + 1) as u64).collect();
+        let tree = HuffmanTree::build(&counts);
+
+        let codes: HashSet<&[u8]> = (0..tree.len()).map(|id| tree.code(id)).collect();
+        assert_eq!(codes.len(), tree.len());
+        for a in &codes {
+            for b in &codes {
+                assert!(a == b || !b.starts_with(a), "{:?} prefixes {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tiny_vocab() {
+        assert!(HuffmanTree::build(&[]).is_empty());
+        assert_eq!(HuffmanTree::build(&[5]).max_code_len(), 0);
+
+        let tree = HuffmanTree::build(&[3, 7]);
+        assert_eq!(tree.code(0), &[0]);
+        assert_eq!(tree.code(1), &[1]);
+        assert_eq!(tree.points(0), &[0]);
+    }
+
+    #[test]
+    fn test_to_padded_layout() {
+        let tree = HuffmanTree::build(&[1, 1, 2, 4]);
+        let (lens, codes, points) = tree.to_padded();
+        let max_len = tree.max_code_len();
+
+        assert_eq!(lens, vec![3, 3, 2, 1]);
+        assert_eq!(codes.len(), 4 * max_len);
+        for (id, &len) in lens.iter().enumerate() {
+            let base = id * max_len;
+            let len = len as usize;
+            let padded: Vec<u8> = codes[base..base + len].iter().map(|&c| c as u8).collect();
+            assert_eq!(padded, tree.code(id));
+            let padded: Vec<u32> = points[base..base + len].iter().map(|&p| p as u32).collect();
+            assert_eq!(padded, tree.points(id));
+        }
+    }
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let tree = HuffmanTree::build(&[10, 3, 3, 1, 7, 0, 2]);
+        let path = std::env::temp_dir().join("wvec_test_huffman.tree");
+
+        tree.save(&path).unwrap();
+        assert_eq!(HuffmanTree::load(&path, 7).unwrap(), tree);
+        let e = HuffmanTree::load(&path, 8).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        fs::write(&path, b"XXXX").unwrap();
+        assert!(HuffmanTree::load(&path, 7).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_truncated() {
+        let path = std::env::temp_dir().join("wvec_test_huffman_truncated.tree");
+
+        // A header claiming a huge tree, then nothing
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+        let e = HuffmanTree::load(&path, u32::MAX as usize).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        // A huge code length for a leaf, cut off after the length
+        let n = 1000u32;
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&n.to_le_bytes());
+        bytes.extend_from_slice(&(n - 1).to_le_bytes());
+        bytes.extend(std::iter::repeat_n(0u8, 4 * n as usize));
+        fs::write(&path, &bytes).unwrap();
+        assert!(HuffmanTree::load(&path, n as usize).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
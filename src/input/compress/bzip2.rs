@@ -0,0 +1,460 @@
+//! Streaming bzip2 decoder
+//!
+//! Decodes one block (at most 900 KB) at a time: Huffman and MTF/RLE2
+//! decoding into a buffer, the inverse Burrows-Wheeler transform, then the
+//! initial run-length stage while copying out. Concatenated streams, as
+//! written by `pbzip2`, are decoded back to back.
+
+use std::io::{self, Read};
+
+use super::{BZIP2_CRC_TABLE, invalid};
+
+/// Block header magic (BCD of pi)
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+
+/// End-of-stream magic (BCD of sqrt(pi))
+const END_MAGIC: u64 = 0x1772_4538_5090;
+
+/// Symbols per Huffman group selector
+const GROUP_SIZE: usize = 50;
+
+/// Most selectors the reference decoder keeps; extra ones are read and ignored
+const MAX_SELECTORS: usize = 18002;
+
+/// Longest Huffman code bzip2 allows
+const MAX_CODE_LEN: u32 = 20;
+
+/// MSB-first bit reader, as bzip2 packs its bits.
+struct BitReader<R> {
+    inner: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    end: usize,
+    /// Pending bits in the low `nbits` bits, next bit highest
+    acc: u64,
+    nbits: u32,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; 32 * 1024].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+            acc: 0,
+            nbits: 0,
+        }
+    }
+
+    /// Reads the next input byte, or None at EOF.
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        while self.pos == self.end {
+            self.end = match self.inner.read(&mut self.buf) {
+                Ok(0) => return Ok(None),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            self.pos = 0;
+        }
+        self.pos += 1;
+        Ok(Some(self.buf[self.pos - 1]))
+    }
+
+    /// Reads `n <= 32` bits as an unsigned integer.
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        while self.nbits < n {
+            let byte = self.next_byte()?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "truncated bzip2 stream")
+            })?;
+            self.acc = self.acc << 8 | byte as u64;
+            self.nbits += 8;
+        }
+        self.nbits -= n;
+        Ok(((self.acc >> self.nbits) & ((1u64 << n) - 1)) as u32)
+    }
+
+    fn bit(&mut self) -> io::Result<bool> {
+        Ok(self.bits(1)? == 1)
+    }
+
+    fn u48(&mut self) -> io::Result<u64> {
+        Ok((self.bits(24)? as u64) << 24 | self.bits(24)? as u64)
+    }
+
+    /// Skips to the next byte boundary and returns true if no input is left.
+    fn align_at_end(&mut self) -> io::Result<bool> {
+        self.nbits -= self.nbits % 8;
+        if self.nbits > 0 {
+            return Ok(false);
+        }
+        match self.next_byte()? {
+            Some(byte) => {
+                self.acc = byte as u64;
+                self.nbits = 8;
+                Ok(false)
+            }
+            None => Ok(true),
+        }
+    }
+}
+
+/// Canonical Huffman code, decoded bit by bit.
+struct Huffman {
+    /// Number of codes of each length
+    count: [u16; MAX_CODE_LEN as usize + 1],
+    /// Symbols ordered by code length, then by value
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut count = [0u16; MAX_CODE_LEN as usize + 1];
+        for &len in lengths {
+            count[len as usize] += 1;
+        }
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for len in 1..=MAX_CODE_LEN as u8 {
+            for (symbol, &l) in lengths.iter().enumerate() {
+                if l == len {
+                    symbols.push(symbol as u16);
+                }
+            }
+        }
+        Self { count, symbols }
+    }
+
+    fn decode<R: Read>(&self, bits: &mut BitReader<R>) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0u32, 0u32, 0u32);
+        for len in 1..=MAX_CODE_LEN as usize {
+            code |= bits.bits(1)?;
+            let count = self.count[len] as u32;
+            if code < first + count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid bzip2 Huffman code"))
+    }
+}
+
+/// Streaming bzip2 decoder implementing `Read`.
+///
+/// Checks the CRC of every block and of every stream.
+pub struct Bz2Decoder<R> {
+    bits: BitReader<R>,
+    /// Maximum block size of the current stream
+    block_size: usize,
+    stream_started: bool,
+    in_block: bool,
+    finished: bool,
+
+    /// Inverse BWT vector: low byte = symbol, high bits = next position
+    tt: Vec<u32>,
+    t_pos: u32,
+    /// BWT output bytes left in the current block
+    block_left: usize,
+
+    /// Initial run-length stage: last byte and how often it occurred in a row
+    last: Option<u8>,
+    run: u8,
+    /// Pending copies of `last` from a run-length byte
+    repeat: usize,
+
+    block_crc: u32,
+    expected_block_crc: u32,
+    stream_crc: u32,
+}
+
+impl<R: Read> Bz2Decoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            bits: BitReader::new(inner),
+            block_size: 0,
+            stream_started: false,
+            in_block: false,
+            finished: false,
+            tt: Vec::new(),
+            t_pos: 0,
+            block_left: 0,
+            last: None,
+            run: 0,
+            repeat: 0,
+            block_crc: 0,
+            expected_block_crc: 0,
+            stream_crc: 0,
+        }
+    }
+
+    fn read_stream_header(&mut self) -> io::Result<()> {
+        if self.bits.bits(24)? != u32::from_be_bytes([0, b'B', b'Z', b'h']) {
+            return Err(invalid("not a bzip2 file"));
+        }
+        let level = self.bits.bits(8)?;
+        if !(b'1' as u32..=b'9' as u32).contains(&level) {
+            return Err(invalid("invalid bzip2 block size"));
+        }
+        self.block_size = (level - b'0' as u32) as usize * 100_000;
+        self.stream_crc = 0;
+        self.stream_started = true;
+        Ok(())
+    }
+
+    /// Reads the next block into `tt`, or returns false at the end of the stream.
+    fn read_block(&mut self) -> io::Result<bool> {
+        let magic = self.bits.u48()?;
+        let crc = self.bits.bits(32)?;
+        if magic == END_MAGIC {
+            if crc != self.stream_crc {
+                return Err(invalid("bzip2 stream CRC mismatch"));
+            }
+            return Ok(false);
+        }
+        if magic != BLOCK_MAGIC {
+            return Err(invalid("invalid bzip2 block header"));
+        }
+        self.expected_block_crc = crc;
+
+        if self.bits.bit()? {
+            return Err(invalid("randomised bzip2 blocks are not supported"));
+        }
+        let orig_ptr = self.bits.bits(24)? as usize;
+
+        // Byte values used in the block, as a two-level bitmap
+        let mut seq_to_byte = Vec::with_capacity(256);
+        let used_groups = self.bits.bits(16)?;
+        for group in 0..16 {
+            if used_groups & (0x8000 >> group) != 0 {
+                let used = self.bits.bits(16)?;
+                for i in 0..16 {
+                    if used & (0x8000 >> i) != 0 {
+                        seq_to_byte.push((group * 16 + i) as u8);
+                    }
+                }
+            }
+        }
+        if seq_to_byte.is_empty() {
+            return Err(invalid("bzip2 block uses no symbols"));
+        }
+        let alpha_size = seq_to_byte.len() + 2;
+
+        let n_groups = self.bits.bits(3)? as usize;
+        if !(2..=6).contains(&n_groups) {
+            return Err(invalid("invalid number of bzip2 Huffman groups"));
+        }
+        let n_selectors = self.bits.bits(15)? as usize;
+        if n_selectors == 0 {
+            return Err(invalid("bzip2 block has no selectors"));
+        }
+
+        // Selectors are MTF-encoded as unary numbers
+        let mut group_mtf: Vec<u8> = (0..n_groups as u8).collect();
+        let mut selectors = Vec::with_capacity(n_selectors.min(MAX_SELECTORS));
+        for _ in 0..n_selectors {
+            let mut j = 0;
+            while self.bits.bit()? {
+                j += 1;
+                if j >= n_groups {
+                    return Err(invalid("invalid bzip2 selector"));
+                }
+            }
+            let group = group_mtf.remove(j);
+            group_mtf.insert(0, group);
+            if selectors.len() < MAX_SELECTORS {
+                selectors.push(group);
+            }
+        }
+
+        // Code lengths are delta-encoded per group
+        let mut tables = Vec::with_capacity(n_groups);
+        for _ in 0..n_groups {
+            let mut len = self.bits.bits(5)? as i32;
+            let mut lengths = vec![0u8; alpha_size];
+            for l in &mut lengths {
+                loop {
+                    if !(1..=MAX_CODE_LEN as i32).contains(&len) {
+                        return Err(invalid("invalid bzip2 code length"));
+                    }
+                    if !self.bits.bit()? {
+                        break;
+                    }
+                    len += if self.bits.bit()? { -1 } else { 1 };
+                }
+                *l = len as u8;
+            }
+            tables.push(Huffman::new(&lengths));
+        }
+
+        self.decode_symbols(&tables, &selectors, &seq_to_byte)?;
+        let n_block = self.tt.len();
+        if orig_ptr >= n_block {
+            return Err(invalid("bzip2 origin pointer out of range"));
+        }
+
+        // Inverse BWT: link every position to the next one in the original text
+        let mut next = [0u32; 256];
+        let mut sum = 0;
+        for (byte, count) in self.byte_counts().iter().enumerate() {
+            next[byte] = sum;
+            sum += count;
+        }
+        for i in 0..n_block {
+            let byte = (self.tt[i] & 0xff) as usize;
+            self.tt[next[byte] as usize] |= (i as u32) << 8;
+            next[byte] += 1;
+        }
+
+        self.t_pos = self.tt[orig_ptr] >> 8;
+        self.block_left = n_block;
+        self.last = None;
+        self.run = 0;
+        self.repeat = 0;
+        self.block_crc = 0xffff_ffff;
+        self.in_block = true;
+        Ok(true)
+    }
+
+    /// Decodes the Huffman/MTF/RLE2 symbols of a block into the low bytes of `tt`.
+    fn decode_symbols(
+        &mut self,
+        tables: &[Huffman],
+        selectors: &[u8],
+        seq_to_byte: &[u8],
+    ) -> io::Result<()> {
+        let end_of_block = (seq_to_byte.len() + 1) as u16;
+        let mut mtf: Vec<u8> = (0..seq_to_byte.len()).map(|i| i as u8).collect();
+        self.tt.clear();
+
+        let mut run = 0usize;
+        let mut run_weight = 1usize;
+        let mut decoded = 0;
+        loop {
+            let selector = *selectors
+                .get(decoded / GROUP_SIZE)
+                .ok_or_else(|| invalid("bzip2 selectors exhausted"))?;
+            let symbol = tables[selector as usize].decode(&mut self.bits)?;
+            decoded += 1;
+
+            // RUNA and RUNB spell a bijective base-2 run length of the front symbol
+            if symbol <= 1 {
+                run += run_weight << symbol;
+                run_weight <<= 1;
+                if run > self.block_size {
+                    return Err(invalid("bzip2 run exceeds block size"));
+                }
+                continue;
+            }
+
+            if run > 0 {
+                if self.tt.len() + run > self.block_size {
+                    return Err(invalid("bzip2 block exceeds declared size"));
+                }
+                let byte = seq_to_byte[mtf[0] as usize] as u32;
+                self.tt.extend(std::iter::repeat_n(byte, run));
+                run = 0;
+                run_weight = 1;
+            }
+
+            if symbol == end_of_block {
+                return Ok(());
+            }
+
+            let j = (symbol - 1) as usize;
+            if j >= mtf.len() {
+                return Err(invalid("invalid bzip2 MTF index"));
+            }
+            let value = mtf.remove(j);
+            mtf.insert(0, value);
+            if self.tt.len() >= self.block_size {
+                return Err(invalid("bzip2 block exceeds declared size"));
+            }
+            self.tt.push(seq_to_byte[value as usize] as u32);
+        }
+    }
+
+    fn byte_counts(&self) -> [u32; 256] {
+        let mut counts = [0u32; 256];
+        for &t in &self.tt {
+            counts[(t & 0xff) as usize] += 1;
+        }
+        counts
+    }
+
+    /// Checks the CRC of the finished block and moves to the next one,
+    /// across stream boundaries. Returns false once all input is decoded.
+    fn next_block(&mut self) -> io::Result<bool> {
+        if self.in_block {
+            let crc = !self.block_crc;
+            if crc != self.expected_block_crc {
+                return Err(invalid("bzip2 block CRC mismatch"));
+            }
+            self.stream_crc = self.stream_crc.rotate_left(1) ^ crc;
+            self.in_block = false;
+        }
+
+        loop {
+            if !self.stream_started {
+                self.read_stream_header()?;
+            }
+            if self.read_block()? {
+                return Ok(true);
+            }
+            self.stream_started = false;
+            if self.bits.align_at_end()? {
+                return Ok(false);
+            }
+        }
+    }
+
+    #[inline]
+    fn emit(&mut self, out: &mut [u8], n: &mut usize, byte: u8) {
+        out[*n] = byte;
+        *n += 1;
+        self.block_crc =
+            (self.block_crc << 8) ^ BZIP2_CRC_TABLE[((self.block_crc >> 24) as u8 ^ byte) as usize];
+    }
+}
+
+impl<R: Read> Read for Bz2Decoder<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < out.len() && !self.finished {
+            if self.repeat > 0 {
+                let byte = self.last.unwrap_or(0);
+                self.emit(out, &mut n, byte);
+                self.repeat -= 1;
+                continue;
+            }
+
+            if self.block_left == 0 {
+                if !self.next_block()? {
+                    self.finished = true;
+                }
+                continue;
+            }
+
+            self.t_pos = self.tt[self.t_pos as usize];
+            let byte = (self.t_pos & 0xff) as u8;
+            self.t_pos >>= 8;
+            self.block_left -= 1;
+
+            // Four equal bytes are followed by a count of further copies
+            if self.run == 4 {
+                self.repeat = byte as usize;
+                self.run = 0;
+                continue;
+            }
+            if self.last == Some(byte) {
+                self.run += 1;
+            } else {
+                self.last = Some(byte);
+                self.run = 1;
+            }
+            self.emit(out, &mut n, byte);
+        }
+        Ok(n)
+    }
+}
@@ -0,0 +1,523 @@
+//! Streaming gzip / DEFLATE decoder (RFC 1951, RFC 1952)
+//!
+//! Decodes one Huffman symbol at a time into the caller's buffer, keeping
+//! only the 32 KB history window in memory. Concatenated gzip members are
+//! decoded back to back, as `gzip -d` does.
+
+use std::io::{self, Read};
+
+use super::{crc32_update, invalid};
+
+/// DEFLATE history window size
+const WINDOW_SIZE: usize = 32 * 1024;
+
+/// Longest Huffman code allowed by DEFLATE
+const MAX_CODE_LEN: usize = 15;
+
+/// Base lengths for length symbols 257..=285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances for distance symbols 0..=29
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Order in which code length code lengths are stored
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// gzip header flags
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// LSB-first bit reader, as DEFLATE packs its bits.
+struct BitReader<R> {
+    inner: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    end: usize,
+    /// Pending bits, next bit in the lowest position
+    acc: u64,
+    nbits: u32,
+    eof: bool,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; 32 * 1024].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+            acc: 0,
+            nbits: 0,
+            eof: false,
+        }
+    }
+
+    /// Tops up the bit buffer to at least 57 bits, or as many as remain.
+    fn refill(&mut self) -> io::Result<()> {
+        while self.nbits <= 56 {
+            if self.pos == self.end {
+                if self.eof {
+                    break;
+                }
+                self.end = match self.inner.read(&mut self.buf) {
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                self.pos = 0;
+                if self.end == 0 {
+                    self.eof = true;
+                    break;
+                }
+            }
+            self.acc |= (self.buf[self.pos] as u64) << self.nbits;
+            self.pos += 1;
+            self.nbits += 8;
+        }
+        Ok(())
+    }
+
+    /// Returns the next `n` bits without consuming them, zero-padded at EOF.
+    fn peek(&mut self, n: u32) -> io::Result<u32> {
+        if self.nbits < n {
+            self.refill()?;
+        }
+        Ok((self.acc & ((1u64 << n) - 1)) as u32)
+    }
+
+    fn consume(&mut self, n: u32) -> io::Result<()> {
+        if n > self.nbits {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated gzip stream",
+            ));
+        }
+        self.acc >>= n;
+        self.nbits -= n;
+        Ok(())
+    }
+
+    fn bits(&mut self, n: u32) -> io::Result<u32> {
+        let value = self.peek(n)?;
+        self.consume(n)?;
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary.
+    fn align(&mut self) {
+        let rest = self.nbits % 8;
+        self.acc >>= rest;
+        self.nbits -= rest;
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        Ok(self.bits(8)? as u8)
+    }
+
+    fn u16_le(&mut self) -> io::Result<u16> {
+        Ok(self.bits(16)? as u16)
+    }
+
+    fn u32_le(&mut self) -> io::Result<u32> {
+        Ok(self.u16_le()? as u32 | (self.u16_le()? as u32) << 16)
+    }
+
+    /// Returns true if no input is left (call on a byte boundary).
+    fn at_end(&mut self) -> io::Result<bool> {
+        if self.nbits == 0 {
+            self.refill()?;
+        }
+        Ok(self.nbits == 0)
+    }
+}
+
+/// Canonical Huffman decoding table indexed by the next `max_len` bits.
+///
+/// Entries are `symbol << 4 | code_len`; 0 marks an unused code.
+struct Huffman {
+    table: Vec<u16>,
+    max_len: u32,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Self> {
+        let mut count = [0u16; MAX_CODE_LEN + 1];
+        for &len in lengths {
+            count[len as usize] += 1;
+        }
+        count[0] = 0;
+        let max_len = (1..=MAX_CODE_LEN)
+            .rev()
+            .find(|&l| count[l] > 0)
+            .unwrap_or(0);
+
+        // Reject over-subscribed codes; incomplete ones decode until an unused code is hit
+        let mut left = 1i32;
+        for &c in &count[1..] {
+            left = 2 * left - c as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed Huffman code"));
+            }
+        }
+
+        let mut next_code = [0u32; MAX_CODE_LEN + 2];
+        for len in 1..=MAX_CODE_LEN {
+            next_code[len + 1] = (next_code[len] + count[len] as u32) << 1;
+        }
+
+        let mut table = vec![0u16; 1 << max_len];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len == 0 {
+                continue;
+            }
+            let len = len as usize;
+            let code = next_code[len];
+            next_code[len] += 1;
+            // Codes are stored MSB first in an LSB-first stream, so index by the reversed code
+            let reversed = code.reverse_bits() >> (32 - len);
+            let entry = (symbol as u16) << 4 | len as u16;
+            let mut i = reversed as usize;
+            while i < table.len() {
+                table[i] = entry;
+                i += 1 << len;
+            }
+        }
+
+        Ok(Self {
+            table,
+            max_len: max_len as u32,
+        })
+    }
+
+    fn decode<R: Read>(&self, bits: &mut BitReader<R>) -> io::Result<u16> {
+        let entry = self.table[bits.peek(self.max_len)? as usize];
+        if entry == 0 {
+            return Err(invalid("invalid Huffman code"));
+        }
+        bits.consume((entry & 0xf) as u32)?;
+        Ok(entry >> 4)
+    }
+}
+
+/// Position within the DEFLATE stream
+enum State {
+    BlockHeader,
+    /// Inside a stored block with this many bytes left
+    Stored(usize),
+    /// Inside a compressed block
+    Codes,
+    /// Copying a back-reference
+    Copy {
+        len: usize,
+        dist: usize,
+    },
+    /// Final block finished
+    Done,
+}
+
+/// Raw DEFLATE decoder
+struct Inflate<R> {
+    bits: BitReader<R>,
+    state: State,
+    last_block: bool,
+    window: Box<[u8]>,
+    window_pos: usize,
+    /// Bytes decoded so far, to validate back-reference distances
+    total_out: u64,
+    literals: Huffman,
+    distances: Huffman,
+}
+
+impl<R: Read> Inflate<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            bits: BitReader::new(inner),
+            state: State::BlockHeader,
+            last_block: false,
+            window: vec![0; WINDOW_SIZE].into_boxed_slice(),
+            window_pos: 0,
+            total_out: 0,
+            literals: Huffman {
+                table: Vec::new(),
+                max_len: 0,
+            },
+            distances: Huffman {
+                table: Vec::new(),
+                max_len: 0,
+            },
+        }
+    }
+
+    /// Starts a new, independent DEFLATE stream on the same input.
+    fn reset(&mut self) {
+        self.state = State::BlockHeader;
+        self.last_block = false;
+        self.window_pos = 0;
+        self.total_out = 0;
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    fn read_block_header(&mut self) -> io::Result<()> {
+        if self.last_block {
+            self.state = State::Done;
+            return Ok(());
+        }
+        self.last_block = self.bits.bits(1)? == 1;
+
+        match self.bits.bits(2)? {
+            0 => {
+                self.bits.align();
+                let len = self.bits.u16_le()?;
+                let nlen = self.bits.u16_le()?;
+                if len != !nlen {
+                    return Err(invalid("corrupt stored block length"));
+                }
+                self.state = State::Stored(len as usize);
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                self.literals = Huffman::new(&lengths)?;
+                self.distances = Huffman::new(&[5; 30])?;
+                self.state = State::Codes;
+            }
+            2 => {
+                self.read_dynamic_tables()?;
+                self.state = State::Codes;
+            }
+            _ => return Err(invalid("invalid DEFLATE block type")),
+        }
+        Ok(())
+    }
+
+    fn read_dynamic_tables(&mut self) -> io::Result<()> {
+        let n_lit = self.bits.bits(5)? as usize + 257;
+        let n_dist = self.bits.bits(5)? as usize + 1;
+        let n_clen = self.bits.bits(4)? as usize + 4;
+        if n_lit > 286 || n_dist > 30 {
+            return Err(invalid("too many DEFLATE codes"));
+        }
+
+        let mut clen_lengths = [0u8; 19];
+        for &i in &CLEN_ORDER[..n_clen] {
+            clen_lengths[i] = self.bits.bits(3)? as u8;
+        }
+        let clen = Huffman::new(&clen_lengths)?;
+
+        let mut lengths = vec![0u8; n_lit + n_dist];
+        let mut i = 0;
+        while i < lengths.len() {
+            let symbol = clen.decode(&mut self.bits)?;
+            let (value, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    let prev = *lengths[..i]
+                        .last()
+                        .ok_or_else(|| invalid("repeat with no previous length"))?;
+                    (prev, 3 + self.bits.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.bits.bits(3)? as usize),
+                _ => (0, 11 + self.bits.bits(7)? as usize),
+            };
+            if i + repeat > lengths.len() {
+                return Err(invalid("code lengths overflow"));
+            }
+            lengths[i..i + repeat].fill(value);
+            i += repeat;
+        }
+
+        if lengths[256] == 0 {
+            return Err(invalid("missing end-of-block code"));
+        }
+        self.literals = Huffman::new(&lengths[..n_lit])?;
+        self.distances = Huffman::new(&lengths[n_lit..])?;
+        Ok(())
+    }
+
+    /// Decodes into `out`, returning the number of bytes written
+    /// (0 only once the final block is finished).
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < out.len() {
+            match self.state {
+                State::Done => break,
+                State::BlockHeader => self.read_block_header()?,
+                State::Stored(0) => self.state = State::BlockHeader,
+                State::Stored(left) => {
+                    let byte = self.bits.byte()?;
+                    self.push(out, &mut n, byte);
+                    self.state = State::Stored(left - 1);
+                }
+                State::Codes => {
+                    let symbol = self.literals.decode(&mut self.bits)? as usize;
+                    if symbol < 256 {
+                        self.push(out, &mut n, symbol as u8);
+                    } else if symbol == 256 {
+                        self.state = State::BlockHeader;
+                    } else {
+                        self.state = self.read_back_reference(symbol - 257)?;
+                    }
+                }
+                State::Copy { mut len, dist } => {
+                    while len > 0 && n < out.len() {
+                        let byte =
+                            self.window[(self.window_pos + WINDOW_SIZE - dist) & (WINDOW_SIZE - 1)];
+                        self.push(out, &mut n, byte);
+                        len -= 1;
+                    }
+                    self.state = if len == 0 {
+                        State::Codes
+                    } else {
+                        State::Copy { len, dist }
+                    };
+                }
+            }
+        }
+        Ok(n)
+    }
+
+    fn read_back_reference(&mut self, code: usize) -> io::Result<State> {
+        if code >= LENGTH_BASE.len() {
+            return Err(invalid("invalid length symbol"));
+        }
+        let len = LENGTH_BASE[code] as usize + self.bits.bits(LENGTH_EXTRA[code] as u32)? as usize;
+
+        let code = self.distances.decode(&mut self.bits)? as usize;
+        if code >= DIST_BASE.len() {
+            return Err(invalid("invalid distance symbol"));
+        }
+        let dist = DIST_BASE[code] as usize + self.bits.bits(DIST_EXTRA[code] as u32)? as usize;
+        if dist as u64 > self.total_out {
+            return Err(invalid("distance too far back"));
+        }
+        Ok(State::Copy { len, dist })
+    }
+
+    #[inline]
+    fn push(&mut self, out: &mut [u8], n: &mut usize, byte: u8) {
+        out[*n] = byte;
+        *n += 1;
+        self.window[self.window_pos] = byte;
+        self.window_pos = (self.window_pos + 1) & (WINDOW_SIZE - 1);
+        self.total_out += 1;
+    }
+}
+
+/// Streaming gzip decoder implementing `Read`.
+///
+/// Checks the CRC-32 and length of every member.
+pub struct GzDecoder<R> {
+    inflate: Inflate<R>,
+    crc: u32,
+    size: u32,
+    header_read: bool,
+    finished: bool,
+}
+
+impl<R: Read> GzDecoder<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inflate: Inflate::new(inner),
+            crc: 0,
+            size: 0,
+            header_read: false,
+            finished: false,
+        }
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let bits = &mut self.inflate.bits;
+        if bits.byte()? != 0x1f || bits.byte()? != 0x8b {
+            return Err(invalid("not a gzip file"));
+        }
+        if bits.byte()? != 8 {
+            return Err(invalid("unsupported gzip compression method"));
+        }
+        let flags = bits.byte()?;
+        // MTIME, XFL, OS
+        for _ in 0..6 {
+            bits.byte()?;
+        }
+        if flags & FEXTRA != 0 {
+            for _ in 0..bits.u16_le()? {
+                bits.byte()?;
+            }
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                while bits.byte()? != 0 {}
+            }
+        }
+        if flags & FHCRC != 0 {
+            bits.u16_le()?;
+        }
+
+        self.crc = 0;
+        self.size = 0;
+        self.header_read = true;
+        Ok(())
+    }
+
+    fn read_trailer(&mut self) -> io::Result<()> {
+        let bits = &mut self.inflate.bits;
+        bits.align();
+        if bits.u32_le()? != self.crc {
+            return Err(invalid("gzip CRC mismatch"));
+        }
+        if bits.u32_le()? != self.size {
+            return Err(invalid("gzip length mismatch"));
+        }
+        self.header_read = false;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for GzDecoder<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        while !self.finished {
+            if !self.header_read {
+                self.read_header()?;
+                self.inflate.reset();
+            }
+
+            let n = self.inflate.read(out)?;
+            if n > 0 {
+                self.crc = crc32_update(self.crc, &out[..n]);
+                self.size = self.size.wrapping_add(n as u32);
+                return Ok(n);
+            }
+
+            if self.inflate.is_done() {
+                self.read_trailer()?;
+                self.finished = self.inflate.bits.at_end()?;
+            }
+        }
+        Ok(0)
+    }
+}
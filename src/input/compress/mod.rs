@@ -0,0 +1,291 @@
+//! Decompression for corpus files
+//!
+//! Stdlib-only streaming decoders for gzip (`.gz`) and bzip2 (`.bz2`).
+//! Both implement `Read`, so any reader can consume a compressed file
+//! through `open`, which picks the decoder by file extension.
+
+mod bzip2;
+mod gzip;
+
+pub use bzip2::Bz2Decoder;
+pub use gzip::GzDecoder;
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Compression of a corpus file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+}
+
+impl Compression {
+    /// Detects the compression from the file extension (`.gz`, `.bz2`).
+    pub fn detect<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("gz") => Self::Gzip,
+            Some(ext) if ext.eq_ignore_ascii_case("bz2") => Self::Bzip2,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Opens a file, decompressing it on the fly if its extension says so.
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Read>> {
+    let file = File::open(&path)?;
+    Ok(match Compression::detect(&path) {
+        Compression::None => Box::new(file),
+        Compression::Gzip => Box::new(GzDecoder::new(file)),
+        Compression::Bzip2 => Box::new(Bz2Decoder::new(file)),
+    })
+}
+
+/// Returns the path without its compression extension, e.g. for detecting
+/// the format of `enwiki.xml.bz2`.
+pub fn strip_extension(path: &Path) -> &Path {
+    match Compression::detect(path) {
+        Compression::None => path,
+        _ => path.file_stem().map(Path::new).unwrap_or(path),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// CRC-32 table (reflected, polynomial 0xEDB88320) as used by gzip
+const CRC32_TABLE: [u32; 256] = crc_table(true);
+
+/// CRC-32 table (MSB first, polynomial 0x04C11DB7) as used by bzip2
+const BZIP2_CRC_TABLE: [u32; 256] = crc_table(false);
+
+const fn crc_table(reflected: bool) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = if reflected {
+            i as u32
+        } else {
+            (i as u32) << 24
+        };
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if reflected {
+                if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xedb8_8320
+                } else {
+                    crc >> 1
+                }
+            } else if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04c1_1db7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Continues a gzip CRC-32 over `data`.
+fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in data {
+        crc = (crc >> 8) ^ CRC32_TABLE[((crc as u8) ^ byte) as usize];
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// gzip of `sample_text()`, compressed with dynamic Huffman codes
+    const SAMPLE_GZ: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xed, 0x92, 0x51, 0x0a, 0xc2,
+        0x30, 0x10, 0x44, 0xaf, 0x92, 0x23, 0xd8, 0x24, 0xf5, 0x3e, 0x2b, 0x16, 0x14, 0xaa, 0x16,
+        0xdc, 0xfb, 0x63, 0x93, 0xec, 0xec, 0xce, 0x09, 0xfc, 0xca, 0x57, 0xc3, 0x23, 0x53, 0x1e,
+        0x93, 0x91, 0xfd, 0x78, 0xc8, 0x25, 0x6d, 0xc7, 0xf7, 0xb9, 0x7f, 0xde, 0x4b, 0xda, 0xe5,
+        0x75, 0xbb, 0x4b, 0x4e, 0x9b, 0x4a, 0x01, 0xad, 0x38, 0xac, 0x0d, 0x5f, 0xed, 0x0e, 0x85,
+        0xa4, 0xfd, 0x24, 0x1b, 0x2f, 0xf6, 0xad, 0x03, 0xaf, 0xb8, 0x46, 0x39, 0x95, 0x05, 0x34,
+        0xe3, 0x50, 0x1a, 0xae, 0x76, 0x87, 0x42, 0x32, 0x04, 0x07, 0x0f, 0xbf, 0x8e, 0xc9, 0xd0,
+        0x73, 0x4d, 0xd0, 0x28, 0x19, 0x9e, 0x38, 0xfc, 0x3c, 0x64, 0x82, 0x83, 0x87, 0x5f, 0xc7,
+        0x64, 0xe8, 0xb9, 0x26, 0x18, 0x55, 0xc0, 0xf0, 0xc4, 0xe1, 0xe7, 0x21, 0x13, 0x44, 0x17,
+        0xf0, 0xeb, 0x98, 0x0c, 0x3d, 0xd7, 0x04, 0xa3, 0x0a, 0x2f, 0x5e, 0xc9, 0xcf, 0x43, 0x26,
+        0x88, 0x2e, 0xe0, 0xd7, 0x31, 0x19, 0x7a, 0x4e, 0xf9, 0xb1, 0xa2, 0x78, 0x25, 0x3f, 0x0f,
+        0x99, 0x20, 0xba, 0x80, 0x9f, 0x3d, 0xb1, 0x1b, 0x7a, 0x4e, 0xf9, 0xb1, 0xa2, 0x78, 0x25,
+        0x3f, 0x0f, 0x99, 0x20, 0xba, 0x80, 0x9f, 0xcc, 0x0d, 0xce, 0x0d, 0xce, 0x0d, 0xce, 0x0d,
+        0xce, 0x0d, 0xce, 0x0d, 0xfe, 0x6f, 0x83, 0x3f, 0x8c, 0x0d, 0x22, 0xa3, 0xfb, 0x08, 0x00,
+        0x00,
+    ];
+
+    /// bzip2 of `sample_text()`
+    const SAMPLE_BZ2: &[u8] = &[
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0x3b, 0x2d, 0x67, 0x15, 0x00,
+        0x01, 0x8f, 0x99, 0x80, 0x40, 0x00, 0x7f, 0x00, 0x36, 0x67, 0xcc, 0x00, 0x30, 0x01, 0x53,
+        0x0c, 0x82, 0x2a, 0xa7, 0xfe, 0xaa, 0x99, 0x32, 0x61, 0x31, 0x0c, 0x37, 0xaa, 0xa8, 0x9a,
+        0x7f, 0xfa, 0xaa, 0x4d, 0x0d, 0x18, 0x01, 0x49, 0x4a, 0x80, 0x34, 0x03, 0x6a, 0x73, 0x38,
+        0x9f, 0x07, 0x2d, 0xb7, 0xce, 0x5b, 0x6e, 0x79, 0x32, 0x10, 0x99, 0x0b, 0x10, 0x88, 0x88,
+        0xb6, 0xd6, 0x5b, 0x68, 0x58, 0xb1, 0x0b, 0x10, 0x8b, 0xd4, 0xfc, 0xb5, 0x63, 0x0d, 0x6a,
+        0xd6, 0xac, 0x61, 0xad, 0x58, 0xd5, 0x8d, 0x58, 0xcb, 0x1a, 0xb5, 0xab, 0x1a, 0xb5, 0x86,
+        0xb0, 0xc6, 0xad, 0x61, 0x8d, 0x58, 0xcb, 0x5a, 0xb1, 0xab, 0x5a, 0xb1, 0x86, 0x32, 0xd6,
+        0x18, 0xd5, 0xac, 0xb1, 0x86, 0x35, 0xd8, 0x75, 0x7d, 0x1c, 0xae, 0x1c, 0x39, 0x5c, 0x38,
+        0x72, 0xb9, 0x4c, 0xb3, 0x32, 0xc1, 0x04, 0xb0, 0x41, 0x01, 0x30, 0x4b, 0x04, 0xc3, 0x04,
+        0xc0, 0x4c, 0x12, 0xcc, 0x12, 0xc1, 0x32, 0xc1, 0x38, 0xec, 0x80, 0xf2, 0x08, 0x3d, 0x11,
+        0x7d, 0x91, 0x78, 0x29, 0x1d, 0xd1, 0x74, 0x45, 0xc2, 0x2e, 0xe4, 0x5d, 0x94, 0x8e, 0x88,
+        0xbd, 0x54, 0x8e, 0x88, 0xbd, 0x08, 0xb2, 0x2f, 0xf1, 0x77, 0x24, 0x53, 0x85, 0x09, 0x03,
+        0xb2, 0xd6, 0x71, 0x50,
+    ];
+
+    /// bzip2 of "aaaaaaaaaaaaaaaaaaaabbbbbcd banana bandana\n"
+    const RUNS_BZ2: &[u8] = &[
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0x0c, 0xae, 0xf2, 0xc9, 0x00,
+        0x00, 0x05, 0x71, 0x00, 0x20, 0x10, 0x40, 0x00, 0x40, 0x00, 0x3c, 0x01, 0x20, 0x00, 0x31,
+        0x06, 0x4c, 0x40, 0x95, 0x3d, 0x4c, 0xd4, 0xf2, 0x8d, 0x91, 0x54, 0x90, 0xf3, 0x37, 0x90,
+        0x44, 0x95, 0xd0, 0xf8, 0xbb, 0x92, 0x29, 0xc2, 0x84, 0x80, 0x65, 0x77, 0x96, 0x48,
+    ];
+
+    /// bzip2 of the bytes 0..=255, a block using every symbol
+    const ALL_BYTES_BZ2: &[u8] = &[
+        0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0xb6, 0xb5, 0xee, 0x95, 0x00,
+        0x00, 0x00, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xb0, 0x00, 0xc5, 0x52, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x30, 0x09, 0x80,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4c, 0x00, 0x04, 0xc0, 0x04,
+        0x98, 0x00, 0x26, 0x00, 0x02, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x02, 0x4c, 0x00, 0x13, 0x00, 0x01, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xf0, 0x08, 0x0c, 0x10, 0x14, 0x18, 0x1c,
+        0x20, 0x24, 0x28, 0x2c, 0x30, 0x34, 0x38, 0x3c, 0x40, 0x44, 0x48, 0x4c, 0x50, 0x54, 0x58,
+        0x5c, 0x60, 0x64, 0x68, 0x6c, 0x70, 0x74, 0x78, 0x7c, 0x80, 0x84, 0x88, 0x8c, 0x90, 0x94,
+        0x98, 0x9c, 0xa0, 0xa4, 0xa8, 0xac, 0xb0, 0xb4, 0xb8, 0xbc, 0xc0, 0xc4, 0xc8, 0xcc, 0xd0,
+        0xd4, 0xd8, 0xdc, 0xe0, 0xe4, 0xe8, 0xec, 0xf0, 0xf4, 0xf8, 0xfd, 0x01, 0x05, 0x09, 0x0d,
+        0x11, 0x15, 0x19, 0x1d, 0x21, 0x25, 0x29, 0x2d, 0x31, 0x35, 0x39, 0x3d, 0x41, 0x45, 0x49,
+        0x4d, 0x51, 0x55, 0x59, 0x5d, 0x61, 0x65, 0x69, 0x6d, 0x71, 0x75, 0x79, 0x7d, 0x81, 0x85,
+        0x89, 0x8d, 0x91, 0x85, 0x89, 0x8d, 0x91, 0x95, 0x99, 0x9d, 0xa1, 0xa5, 0xa9, 0xad, 0xb1,
+        0xb5, 0xb9, 0xbd, 0xc1, 0xc5, 0xc9, 0xcd, 0xd1, 0xd5, 0xd9, 0xdd, 0xe1, 0xe5, 0xe9, 0xed,
+        0xf1, 0xf5, 0xf9, 0xfe, 0x02, 0x06, 0x0a, 0x0e, 0x12, 0x16, 0x1a, 0x1e, 0x22, 0x26, 0x2a,
+        0x2e, 0x32, 0x36, 0x3a, 0x3e, 0x42, 0x46, 0x4a, 0x56, 0x5a, 0x5e, 0x62, 0x66, 0x6a, 0x6e,
+        0x72, 0x76, 0x7a, 0x7e, 0x82, 0x86, 0x8a, 0x8e, 0x92, 0x96, 0x9a, 0x9e, 0xa2, 0xa6, 0xaa,
+        0xae, 0xb2, 0xb6, 0xba, 0xbe, 0xc2, 0xc6, 0xca, 0xce, 0xd2, 0xd6, 0xda, 0xde, 0xe2, 0xe6,
+        0xea, 0xee, 0xf2, 0xf6, 0xfa, 0xff, 0x03, 0x07, 0x0b, 0x0f, 0x13, 0x17, 0x1b, 0x17, 0x1b,
+        0x1f, 0x23, 0x27, 0x2b, 0x2f, 0x33, 0x37, 0x3b, 0x3f, 0x43, 0x47, 0x4b, 0x4f, 0x53, 0x57,
+        0x5b, 0x5f, 0x63, 0x67, 0x6b, 0x6f, 0x73, 0x77, 0x7b, 0x7f, 0x83, 0x87, 0x8b, 0x8f, 0x93,
+        0x97, 0x9b, 0x9f, 0xa3, 0xa7, 0xab, 0xaf, 0xb3, 0xb7, 0xbb, 0xbf, 0xc3, 0xc7, 0xcb, 0xcf,
+        0xd3, 0xd7, 0xdb, 0xdf, 0xe3, 0xe7, 0xeb, 0xef, 0xf3, 0xf4, 0x5d, 0xc9, 0x14, 0xe1, 0x42,
+        0x42, 0xda, 0xd7, 0xba, 0x54,
+    ];
+
+    fn sample_text() -> String {
+        const WORDS: [&str; 12] = [
+            "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "iota", "kappa",
+            "lambda", "mu",
+        ];
+        (0..300)
+            .map(|i| format!("{}{}", WORDS[(i * i + 3 * i) % 12], i % 7))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn decode<D: Read>(mut decoder: D) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        decoder.read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_gzip_dynamic_and_multi_member() {
+        let text = sample_text();
+        assert_eq!(decode(GzDecoder::new(SAMPLE_GZ)).unwrap(), text.as_bytes());
+
+        let twice = [SAMPLE_GZ, SAMPLE_GZ].concat();
+        let expected = [text.as_bytes(), text.as_bytes()].concat();
+        assert_eq!(decode(GzDecoder::new(&twice[..])).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_gzip_stored_block_byte_by_byte() {
+        let data = b"stored, not compressed";
+        // Header with the FNAME flag set
+        let mut gz = vec![0x1f, 0x8b, 8, 0x08, 0, 0, 0, 0, 0, 3];
+        gz.extend_from_slice(b"name.txt\0");
+        gz.push(1); // final stored block
+        gz.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        gz.extend_from_slice(&(!(data.len() as u16)).to_le_bytes());
+        gz.extend_from_slice(data);
+        gz.extend_from_slice(&crc32_update(0, data).to_le_bytes());
+        gz.extend_from_slice(&(data.len() as u32).to_le_bytes());
+
+        // One byte per call, so decoding resumes mid-block
+        let mut decoder = GzDecoder::new(&gz[..]);
+        let mut bytes = Vec::new();
+        let mut byte = [0u8; 1];
+        while decoder.read(&mut byte).unwrap() == 1 {
+            bytes.push(byte[0]);
+        }
+        assert_eq!(bytes, data);
+    }
+
+    #[test]
+    fn test_bzip2_streams_and_runs() {
+        let text = sample_text();
+        assert_eq!(
+            decode(Bz2Decoder::new(SAMPLE_BZ2)).unwrap(),
+            text.as_bytes()
+        );
+
+        let twice = [SAMPLE_BZ2, SAMPLE_BZ2].concat();
+        let expected = [text.as_bytes(), text.as_bytes()].concat();
+        assert_eq!(decode(Bz2Decoder::new(&twice[..])).unwrap(), expected);
+
+        assert_eq!(
+            decode(Bz2Decoder::new(RUNS_BZ2)).unwrap(),
+            b"aaaaaaaaaaaaaaaaaaaabbbbbcd banana bandana\n"
+        );
+
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(Bz2Decoder::new(ALL_BYTES_BZ2)).unwrap(), all);
+    }
+
+    #[test]
+    fn test_corrupt_input_is_rejected() {
+        let mut gz = SAMPLE_GZ.to_vec();
+        let crc_pos = gz.len() - 8;
+        gz[crc_pos] ^= 1;
+        assert!(decode(GzDecoder::new(&gz[..])).is_err());
+        assert!(decode(GzDecoder::new(&SAMPLE_GZ[..100])).is_err());
+
+        // Byte 10 lies in the block CRC
+        let mut bz = SAMPLE_BZ2.to_vec();
+        bz[10] ^= 1;
+        assert!(decode(Bz2Decoder::new(&bz[..])).is_err());
+        assert!(decode(Bz2Decoder::new(&SAMPLE_BZ2[..100])).is_err());
+
+        assert!(decode(GzDecoder::new(SAMPLE_BZ2)).is_err());
+        assert!(decode(Bz2Decoder::new(SAMPLE_GZ)).is_err());
+    }
+
+    #[test]
+    fn test_detect_and_strip_extension() {
+        assert_eq!(Compression::detect("enwiki.xml.bz2"), Compression::Bzip2);
+        assert_eq!(Compression::detect("page.html.GZ"), Compression::Gzip);
+        assert_eq!(Compression::detect("corpus.txt"), Compression::None);
+        assert_eq!(
+            strip_extension(Path::new("enwiki.xml.bz2")),
+            Path::new("enwiki.xml")
+        );
+        assert_eq!(
+            strip_extension(Path::new("corpus.txt")),
+            Path::new("corpus.txt")
+        );
+    }
+}
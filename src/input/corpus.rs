@@ -0,0 +1,326 @@
+//! Corpus reader over all input formats
+//!
+//! Picks a reader by file extension or explicit format and yields plain
+//! text, so training can stream a Wikipedia dump or HTML page directly
+//! without an intermediate extraction step.
+
+use std::{
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use crate::input::{compress, files, html::HtmlReader, text::TextReader, xml::WikiXmlReader};
+use crate::text::{normalize::normalize, pretokenize::pretokenize};
+
+/// Input file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Plain text, one line at a time
+    Text,
+    /// MediaWiki XML dump, one article at a time
+    Xml,
+    /// HTML document, stripped of tags
+    Html,
+}
+
+impl Format {
+    /// Parses a `--format` value (`txt`, `xml` or `html`).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "txt" | "text" => Some(Self::Text),
+            "xml" => Some(Self::Xml),
+            "html" | "htm" => Some(Self::Html),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the file extension, defaulting to plain text.
+    ///
+    /// A compression extension is skipped, so `enwiki.xml.bz2` is XML.
+    pub fn detect<P: AsRef<Path>>(path: P) -> Self {
+        compress::strip_extension(path.as_ref())
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::parse)
+            .unwrap_or(Self::Text)
+    }
+}
+
+/// Read buffer size for corpus files (64 KB)
+const READ_BUF_SIZE: usize = 64 * 1024;
+
+/// Buffered byte source of a corpus: a file or stdin
+type Input = Box<dyn BufRead>;
+
+/// Format-specific reader behind a `CorpusReader`
+enum Source {
+    Text(TextReader<Input>),
+    Xml(WikiXmlReader<Input>),
+    /// Taken on first read; an HTML file yields a single document
+    Html(Option<HtmlReader<Input>>),
+}
+
+/// Streams the plain text of a corpus file in any supported format.
+///
+/// Yields lines for text files, article bodies for XML dumps and the
+/// stripped document for HTML files.
+pub struct CorpusReader {
+    source: Source,
+}
+
+impl CorpusReader {
+    /// Opens a corpus file in the given format; `-` reads stdin.
+    pub fn open<P: AsRef<Path>>(path: P, format: Format) -> io::Result<Self> {
+        let path = path.as_ref();
+        let input: Input = if files::is_stdin(path) {
+            Box::new(io::stdin().lock())
+        } else {
+            Box::new(BufReader::with_capacity(
+                READ_BUF_SIZE,
+                compress::open(path)?,
+            ))
+        };
+        Ok(Self::from_reader(input, format))
+    }
+
+    /// Reads a corpus in the given format from any buffered source.
+    pub fn from_reader<R: BufRead + 'static>(reader: R, format: Format) -> Self {
+        let input: Input = Box::new(reader);
+        let source = match format {
+            Format::Text => Source::Text(TextReader::new(input)),
+            Format::Xml => Source::Xml(WikiXmlReader::new(input)),
+            Format::Html => Source::Html(Some(HtmlReader::new(input))),
+        };
+        Self { source }
+    }
+}
+
+impl Iterator for CorpusReader {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.source {
+            Source::Text(reader) => reader.next(),
+            Source::Xml(reader) => Some(reader.next()?.map(|article| article.text)),
+            Source::Html(reader) => Some(reader.take()?.read_all()),
+        }
+    }
+}
+
+/// Streams normalized pre-tokens from a corpus file.
+pub struct Pretokens {
+    corpus: CorpusReader,
+    pending: std::vec::IntoIter<String>,
+    /// Mark pre-tokens that follow whitespace, see `PreToken::into_bpe_text`
+    word_boundaries: bool,
+}
+
+impl Pretokens {
+    /// Opens a corpus file in the given format; `-` reads stdin.
+    pub fn open<P: AsRef<Path>>(path: P, format: Format) -> io::Result<Self> {
+        Ok(Self::new(CorpusReader::open(path, format)?))
+    }
+
+    /// Splits the text of a corpus into pre-tokens.
+    pub fn new(corpus: CorpusReader) -> Self {
+        Self {
+            corpus,
+            pending: Vec::new().into_iter(),
+            word_boundaries: false,
+        }
+    }
+
+    /// Yields pre-tokens in the form used by BPE vocabularies with word
+    /// boundaries: a leading space on pre-tokens that follow whitespace.
+    pub fn with_word_boundaries(mut self, word_boundaries: bool) -> Self {
+        self.word_boundaries = word_boundaries;
+        self
+    }
+}
+
+impl Iterator for Pretokens {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(pt) = self.pending.next() {
+                return Some(Ok(pt));
+            }
+            match self.corpus.next()? {
+                Ok(text) => {
+                    let normalized = normalize(&text);
+                    let pretokens: Vec<String> = pretokenize(&normalized)
+                        .into_iter()
+                        .map(|pt| pt.into_bpe_text(self.word_boundaries))
+                        .collect();
+                    self.pending = pretokens.into_iter();
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Streams normalized pre-tokens from several corpus files in turn.
+///
+/// Each file is read in `format`, or in the format detected from its
+/// extension if none is given. Files that cannot be opened or fail
+/// mid-read are skipped with a warning. Items carry the index of the file
+/// they came from.
+pub struct MultiPretokens<'a> {
+    files: &'a [PathBuf],
+    format: Option<Format>,
+    word_boundaries: bool,
+    next_file: usize,
+    current: Option<Pretokens>,
+}
+
+impl<'a> MultiPretokens<'a> {
+    pub fn new(files: &'a [PathBuf], format: Option<Format>) -> Self {
+        Self {
+            files,
+            format,
+            word_boundaries: false,
+            next_file: 0,
+            current: None,
+        }
+    }
+
+    /// Marks pre-tokens that follow whitespace, see [`Pretokens::with_word_boundaries`].
+    pub fn with_word_boundaries(mut self, word_boundaries: bool) -> Self {
+        self.word_boundaries = word_boundaries;
+        self
+    }
+}
+
+impl Iterator for MultiPretokens<'_> {
+    type Item = (usize, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let index = self.next_file.checked_sub(1);
+            if let (Some(index), Some(current)) = (index, self.current.as_mut()) {
+                match current.next() {
+                    Some(Ok(pt)) => return Some((index, pt)),
+                    Some(Err(e)) => {
+                        eprintln!(
+                            "  Warning: skipping rest of {}: {}",
+                            self.files[index].display(),
+                            e
+                        );
+                        self.current = None;
+                    }
+                    None => self.current = None,
+                }
+            }
+
+            let path = self.files.get(self.next_file)?;
+            self.next_file += 1;
+            let format = self.format.unwrap_or_else(|| Format::detect(path));
+            match Pretokens::open(path, format) {
+                Ok(pretokens) => {
+                    self.current = Some(pretokens.with_word_boundaries(self.word_boundaries))
+                }
+                Err(e) => eprintln!("  Warning: skipping {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_format_detect() {
+        assert_eq!(Format::detect("enwiki-latest.xml"), Format::Xml);
+        assert_eq!(Format::detect("page.HTML"), Format::Html);
+        assert_eq!(Format::detect("page.htm"), Format::Html);
+        assert_eq!(Format::detect("corpus.txt"), Format::Text);
+        assert_eq!(Format::detect("corpus"), Format::Text);
+        assert_eq!(Format::detect("enwiki-latest.xml.bz2"), Format::Xml);
+        assert_eq!(Format::detect("page.html.gz"), Format::Html);
+        assert_eq!(Format::parse("pdf"), None);
+    }
+
+    #[test]
+    fn test_xml_yields_article_text() {
+        let path = std::env::temp_dir().join("wvec_test_corpus.xml");
+        fs::write(
+            &path,
+            "<mediawiki>\n<page>\n<title>Cat</title>\n<ns>0</ns>\n\
+             <text>The '''cat''' sat.</text>\n</page>\n\
+             <page>\n<title>Talk:Cat</title>\n<ns>1</ns>\n<text>talk</text>\n</page>\n\
+             </mediawiki>\n",
+        )
+        .unwrap();
+
+        let texts: Vec<String> = CorpusReader::open(&path, Format::Xml)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(texts, vec!["The cat sat."]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_html_pretokens() {
+        let path = std::env::temp_dir().join("wvec_test_corpus.html");
+        fs::write(
+            &path,
+            "<html><script>var x;</script><p>Hello world</p></html>",
+        )
+        .unwrap();
+
+        let pretokens: Vec<String> = Pretokens::open(&path, Format::Html)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert!(pretokens.iter().any(|pt| pt.contains("hello")));
+        assert!(pretokens.iter().all(|pt| !pt.contains("var")));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_pretokens_from_memory() {
+        let corpus = CorpusReader::from_reader(&b"Hello world\nagain"[..], Format::Text);
+        let pretokens: Vec<String> = Pretokens::new(corpus).map(Result::unwrap).collect();
+
+        assert!(pretokens.iter().any(|pt| pt.contains("hello")));
+        assert!(pretokens.iter().any(|pt| pt.contains("again")));
+
+        let corpus = CorpusReader::from_reader(&b"Hello world\nagain"[..], Format::Text);
+        let marked: Vec<String> = Pretokens::new(corpus)
+            .with_word_boundaries(true)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(marked, vec!["hello", " world", "again"]);
+    }
+
+    #[test]
+    fn test_multi_pretokens_skips_unreadable_files() {
+        let dir = std::env::temp_dir();
+        let text = dir.join("wvec_test_multi.txt");
+        let html = dir.join("wvec_test_multi.html");
+        fs::write(&text, "alpha beta\n").unwrap();
+        fs::write(&html, "<p>gamma</p>").unwrap();
+        let files = vec![
+            text.clone(),
+            dir.join("wvec_test_missing.txt"),
+            html.clone(),
+        ];
+
+        let items: Vec<(usize, String)> = MultiPretokens::new(&files, None).collect();
+        let indices: Vec<usize> = items.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices.first(), Some(&0));
+        assert_eq!(indices.last(), Some(&2));
+        assert!(!indices.contains(&1));
+        assert!(items.iter().any(|(_, pt)| pt.contains("gamma")));
+
+        fs::remove_file(&text).unwrap();
+        fs::remove_file(&html).unwrap();
+    }
+}
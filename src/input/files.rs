@@ -0,0 +1,231 @@
+//! Corpus file discovery
+//!
+//! Expands `--input` values into a list of files: plain paths, directories
+//! (walked recursively) and simple globs with `*` and `?`, e.g.
+//! `shards/*/part-*.xml`. `-` stands for stdin.
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Input path that reads stdin
+pub const STDIN: &str = "-";
+
+/// Returns true if the path stands for stdin.
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN)
+}
+
+/// Returns true if the input can be read more than once, i.e. it is a
+/// regular file rather than stdin or a named pipe.
+pub fn is_rereadable(path: &Path) -> bool {
+    !is_stdin(path) && fs::metadata(path).is_ok_and(|m| m.is_file())
+}
+
+/// Expands input specifications into a sorted, de-duplicated file list.
+///
+/// Specs that match nothing are reported on stderr and skipped.
+/// Named pipes and `-` (stdin) are passed through as given.
+pub fn expand_inputs(specs: &[String]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    for spec in specs {
+        if spec == STDIN {
+            files.push(PathBuf::from(STDIN));
+            continue;
+        }
+
+        let before = files.len();
+        let matches = if has_glob(spec) {
+            expand_glob(Path::new(spec))
+        } else {
+            vec![PathBuf::from(spec)]
+        };
+
+        for path in matches {
+            if path.is_dir() {
+                walk_dir(&path, &mut files);
+            } else if path.exists() {
+                files.push(path);
+            }
+        }
+
+        if files.len() == before {
+            eprintln!("  Warning: no input files found for {}", spec);
+        }
+    }
+
+    // Keep the first occurrence of a file named by overlapping specs
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|f| seen.insert(f.clone()));
+    files
+}
+
+/// Returns true if a glob pattern matches a file name.
+///
+/// `*` matches any run of characters (including none), `?` exactly one.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // Iterative matcher with backtracking to the last `*`
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn has_glob(s: &str) -> bool {
+    s.contains('*') || s.contains('?')
+}
+
+/// Expands a glob component by component, returning matches in sorted order.
+fn expand_glob(pattern: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::new()];
+
+    for component in pattern.components() {
+        let part = match component {
+            Component::Normal(part) => part.to_string_lossy(),
+            other => {
+                // Root, `.` and `..` are taken literally
+                for path in &mut paths {
+                    path.push(other.as_os_str());
+                }
+                continue;
+            }
+        };
+
+        if !has_glob(&part) {
+            for path in &mut paths {
+                path.push(part.as_ref());
+            }
+            continue;
+        }
+
+        let mut next = Vec::new();
+        for dir in &paths {
+            let read_from = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir.as_path()
+            };
+            let Ok(entries) = fs::read_dir(read_from) else {
+                continue;
+            };
+            let mut names: Vec<String> = entries
+                .flatten()
+                .filter_map(|e| e.file_name().into_string().ok())
+                .filter(|name| glob_match(&part, name))
+                .collect();
+            names.sort();
+            next.extend(names.into_iter().map(|name| dir.join(name)));
+        }
+        paths = next;
+    }
+
+    paths
+}
+
+/// Appends every file under `dir` in sorted order, recursing into subdirectories.
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("  Warning: cannot read directory {}: {}", dir.display(), e);
+            return;
+        }
+    };
+
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            walk_dir(&path, files);
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("a/nested")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        for file in [
+            "a/1.html",
+            "a/2.html",
+            "a/nested/3.html",
+            "b/part-1.xml",
+            "b/notes.txt",
+        ] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.xml", "part-1.xml"));
+        assert!(glob_match("part-?.xml", "part-1.xml"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(!glob_match("*.xml", "part-1.xml.bz2"));
+        assert!(!glob_match("part-?.xml", "part-10.xml"));
+    }
+
+    #[test]
+    fn test_expand_directory_recursively() {
+        let root = make_tree("wvec_test_files_dir");
+
+        let files = expand_inputs(&[root.join("a").to_string_lossy().into_owned()]);
+        assert_eq!(
+            files,
+            vec![
+                root.join("a/1.html"),
+                root.join("a/2.html"),
+                root.join("a/nested/3.html"),
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_expand_globs_and_paths() {
+        let root = make_tree("wvec_test_files_glob");
+        let spec = |s: &str| root.join(s).to_string_lossy().into_owned();
+
+        let files = expand_inputs(&[
+            spec("*/*.xml"),
+            spec("b/notes.txt"),
+            spec("b/part-1.xml"), // duplicate of the glob match
+            spec("missing.txt"),
+        ]);
+        assert_eq!(
+            files,
+            vec![root.join("b/part-1.xml"), root.join("b/notes.txt")]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -4,29 +4,37 @@
 
 use std::{
     borrow::Cow,
-    fs::File,
-    io::{self, BufReader, Read},
+    io::{self, BufRead, BufReader, Read},
     path::Path,
 };
 
+use crate::input::compress;
+
 /// Default buffer size for reading (24 KB)
 const DEFAULT_BUF_SIZE: usize = 24 * 1024;
 
 /// A streaming reader for HTML files that strips tags and extracts text.
-pub struct HtmlReader {
-    /// Buffered reader for the HTML file
-    reader: BufReader<File>,
+pub struct HtmlReader<R = BufReader<Box<dyn Read>>> {
+    /// Buffered reader for the HTML source
+    reader: R,
 }
 
 impl HtmlReader {
-    /// Opens an HTML file for text extraction.
+    /// Opens an HTML file for text extraction (`.gz`/`.bz2` decompressed).
     pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let file = File::open(path)?;
+        let file = compress::open(path)?;
         let reader = BufReader::with_capacity(DEFAULT_BUF_SIZE, file);
-        Ok(Self { reader })
+        Ok(HtmlReader::new(reader))
+    }
+}
+
+impl<R: BufRead> HtmlReader<R> {
+    /// Extracts text from any buffered source.
+    pub fn new(reader: R) -> Self {
+        Self { reader }
     }
 
-    /// Reads and processes the entire HTML file, returning stripped text.
+    /// Reads and processes the entire HTML source, returning stripped text.
     pub fn read_all(&mut self) -> io::Result<String> {
         let mut content = String::new();
         self.reader.read_to_string(&mut content)?;
@@ -244,6 +252,7 @@ fn normalize_whitespace(s: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs::File;
     use std::io::Write;
 
     #[test]
@@ -593,6 +602,14 @@ mod tests {
         std::fs::remove_file(&path).unwrap();
     }
 
+    #[test]
+    fn test_html_reader_from_memory() {
+        let html = "<p>café &amp; <b>crème</b></p><style>p {}</style>";
+        let text = HtmlReader::new(html.as_bytes()).read_all().unwrap();
+
+        assert_eq!(text, "café & crème");
+    }
+
     #[test]
     fn test_html_reader_with_script() {
         let dir = std::env::temp_dir();
@@ -1,7 +1,11 @@
 //! Input format handlers
 //!
-//! Supports: plain text, XML (Wikipedia), HTML
+//! Supports: plain text, XML (Wikipedia), HTML, each optionally gzip or
+//! bzip2 compressed; several files, directories and globs per run
 
+pub mod compress;
+pub mod corpus;
+pub mod files;
 pub mod html;
 pub mod text;
 pub mod xml;
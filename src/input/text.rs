@@ -1,21 +1,23 @@
 //! Plain text file reader
 //!
 //! Reads .txt files line by line with streaming (memory efficient).
+//! Works over any `BufRead` source, e.g. stdin or an in-memory buffer.
 
 use std::{
-    fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, Read},
     path::Path,
 };
 
+use crate::input::compress;
+
 /// Default maximum line length (10 MB)
 pub const DEFAULT_MAX_LINE_LENGTH: usize = 10 * 1024 * 1024;
 
 /// A stream reader for plain text files.
-pub struct TextReader {
-    /// Buffered reader wrapping the underlying file.
-    /// Reads in 8KB chunks by default.
-    reader: BufReader<File>,
+pub struct TextReader<R = BufReader<Box<dyn Read>>> {
+    /// Buffered reader wrapping the underlying source.
+    /// Files are read in 8KB chunks by default.
+    reader: R,
 
     /// Maximum allowed line length in bytes.
     /// Lines exceeding this limit are truncated, and remaining bytes
@@ -29,19 +31,34 @@ pub struct TextReader {
 
 impl TextReader {
     /// Opens a text file for streaming line-by-line reading.
+    /// `.gz` and `.bz2` files are decompressed on the fly.
     pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         Self::open_with_limit(path, DEFAULT_MAX_LINE_LENGTH)
     }
 
     /// Opens a text file with custom max line length.
     pub fn open_with_limit<P: AsRef<Path>>(path: P, max_line_length: usize) -> io::Result<Self> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        Ok(Self {
+        let file = compress::open(path)?;
+        Ok(TextReader::with_limit(
+            BufReader::new(file),
+            max_line_length,
+        ))
+    }
+}
+
+impl<R: BufRead> TextReader<R> {
+    /// Reads lines from any buffered source.
+    pub fn new(reader: R) -> Self {
+        Self::with_limit(reader, DEFAULT_MAX_LINE_LENGTH)
+    }
+
+    /// Reads lines from any buffered source with custom max line length.
+    pub fn with_limit(reader: R, max_line_length: usize) -> Self {
+        Self {
             reader,
             max_line_length,
             buffer: String::new(),
-        })
+        }
     }
 
     /// Reads next line, truncating if it exceeds max_line_length.
@@ -144,7 +161,7 @@ impl TextReader {
     }
 }
 
-impl Iterator for TextReader {
+impl<R: BufRead> Iterator for TextReader<R> {
     type Item = io::Result<String>;
 
     /// Advances the iterator and returns the next line.
@@ -160,6 +177,7 @@ impl Iterator for TextReader {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs::File;
     use std::io::Write;
 
     #[test]
@@ -210,4 +228,12 @@ mod tests {
 
         std::fs::remove_file(&path).unwrap();
     }
+
+    #[test]
+    fn test_read_from_memory() {
+        let reader = TextReader::with_limit(&b"first\nsecond line\nno newline"[..], 6);
+        let lines: Vec<String> = reader.map(|l| l.unwrap()).collect();
+
+        assert_eq!(lines, vec!["first", "second", "no new"]);
+    }
 }
@@ -4,11 +4,12 @@
 //! Extracts article text and strips wikitext markup.
 
 use std::{
-    fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, Read},
     path::Path,
 };
 
+use crate::input::compress;
+
 /// Default buffer size for reading (24 KB)
 const DEFAULT_BUF_SIZE: usize = 24 * 1024;
 
@@ -43,9 +44,9 @@ pub struct WikiArticle {
 ///
 /// Yields `WikiArticle` items as it parses through the dump.
 /// Memory-efficient: only holds one article at a time.
-pub struct WikiXmlReader {
-    /// Buffered reader for the XML file
-    reader: BufReader<File>,
+pub struct WikiXmlReader<R = BufReader<Box<dyn Read>>> {
+    /// Buffered reader for the XML source
+    reader: R,
 
     /// Current parser state
     state: State,
@@ -63,7 +64,7 @@ pub struct WikiXmlReader {
 }
 
 impl WikiXmlReader {
-    /// Opens a Wikipedia XML dump file.
+    /// Opens a Wikipedia XML dump file, decompressing `.bz2` or `.gz` dumps.
     pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         Self::open_with_options(path, true)
     }
@@ -73,9 +74,21 @@ impl WikiXmlReader {
         path: P,
         main_namespace_only: bool,
     ) -> io::Result<Self> {
-        let file = File::open(path)?;
+        let file = compress::open(path)?;
         let reader = BufReader::with_capacity(DEFAULT_BUF_SIZE, file);
-        Ok(Self {
+        Ok(WikiXmlReader::with_options(reader, main_namespace_only))
+    }
+}
+
+impl<R: BufRead> WikiXmlReader<R> {
+    /// Parses a dump from any buffered source, keeping main-namespace articles.
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, true)
+    }
+
+    /// Parses a dump from any buffered source with custom options.
+    pub fn with_options(reader: R, main_namespace_only: bool) -> Self {
+        Self {
             reader,
             state: State::Idle,
             line_buffer: String::new(),
@@ -83,7 +96,7 @@ impl WikiXmlReader {
             current_namespace: 0,
             current_text: String::new(),
             main_namespace_only,
-        })
+        }
     }
 
     /// Parses the next article from the dump.
@@ -173,7 +186,7 @@ impl WikiXmlReader {
     }
 }
 
-impl Iterator for WikiXmlReader {
+impl<R: BufRead> Iterator for WikiXmlReader<R> {
     type Item = io::Result<WikiArticle>;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -389,6 +402,24 @@ fn strip_wikitext(text: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_read_from_memory() {
+        let dump = "<mediawiki>\n<page>\n<title>Cat</title>\n<ns>0</ns>\n\
+                    <text>A [[cat]] sat.</text>\n</page>\n\
+                    <page>\n<title>User:Cat</title>\n<ns>2</ns>\n<text>x</text>\n</page>\n\
+                    </mediawiki>\n";
+
+        let articles: Vec<WikiArticle> = WikiXmlReader::new(dump.as_bytes())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(articles.len(), 1);
+        assert_eq!(articles[0].title, "Cat");
+        assert_eq!(articles[0].text, "A cat sat.");
+
+        let all = WikiXmlReader::with_options(dump.as_bytes(), false).count();
+        assert_eq!(all, 2);
+    }
+
     #[test]
     fn test_strip_comments() {
         assert_eq!(
@@ -6,6 +6,16 @@
 pub mod bpe;
 pub mod cli;
 pub mod cmd;
+pub mod compose;
+mod cosine;
+pub mod export;
 pub mod ffi;
+pub mod hnsw;
+pub mod huffman;
 pub mod input;
+pub mod pooling;
+pub mod rng;
+pub mod server;
+pub mod signal;
 pub mod text;
+pub mod thermal;
@@ -19,6 +19,13 @@ fn main() {
             }
         }
 
+        SubCommand::EmbedText => {
+            if let Err(e) = wvec::cmd::embed_text::run(&args.args) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+
         SubCommand::Similar => {
             if let Err(e) = wvec::cmd::similar::run(&args.args) {
                 eprintln!("Error: {}", e);
@@ -33,6 +40,34 @@ fn main() {
             }
         }
 
+        SubCommand::Index => {
+            if let Err(e) = wvec::cmd::index::run(&args.args) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+
+        SubCommand::Repl => {
+            if let Err(e) = wvec::cmd::repl::run(&args.args) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+
+        SubCommand::Serve => {
+            if let Err(e) = wvec::cmd::serve::run(&args.args) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+
+        SubCommand::Export => {
+            if let Err(e) = wvec::cmd::export::run(&args.args) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+
         SubCommand::BpeTrain => {
             if let Err(e) = wvec::cmd::bpe_train::run(&args.args) {
                 eprintln!("Error: {}", e);
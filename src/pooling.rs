@@ -0,0 +1,384 @@
+//! Document vectors pooled from token vectors
+//!
+//! A sentence or document gets the average of its token vectors, either
+//! plain or with SIF weighting ("smooth inverse frequency", Arora et al.,
+//! 2017): token `t` is weighted by `a / (a + p(t))`, so frequent tokens
+//! count less, and the first principal component shared by all document
+//! vectors is removed.
+//!
+//! SIF needs the token counts of the training corpus, which `train` saves
+//! next to the model.
+//!
+//! # Counts File Format (v1)
+//!
+//! All integers are little-endian.
+//!
+//! ```text
+//! ┌─────────────────────────────────────────────────────────┐
+//! │                        HEADER                           │
+//! ├──────────────┬──────────┬───────────────────────────────┤
+//! │ magic        │ [u8; 4]  │ "WVTC" - file identifier      │
+//! │ version      │ u32      │ format version (currently 1)  │
+//! │ vocab_size   │ u32      │ number of tokens              │
+//! ├──────────────┴──────────┴───────────────────────────────┤
+//! │ counts       │ [u64; vocab_size] in ID order            │
+//! └──────────────┴──────────────────────────────────────────┘
+//! ```
+//!
+//! # Component File Format (v1)
+//!
+//! The common component of a set of documents, saved so that queries
+//! pooled later are projected the same way as the documents.
+//!
+//! ```text
+//! ┌─────────────────────────────────────────────────────────┐
+//! │                        HEADER                           │
+//! ├──────────────┬──────────┬───────────────────────────────┤
+//! │ magic        │ [u8; 4]  │ "WVPC" - file identifier      │
+//! │ version      │ u32      │ format version (currently 1)  │
+//! │ dim          │ u32      │ vector dimension              │
+//! ├──────────────┴──────────┴───────────────────────────────┤
+//! │ component    │ [f32; dim], unit length                  │
+//! └──────────────┴──────────────────────────────────────────┘
+//! ```
+
+use crate::bpe::BpeTokenId;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Magic bytes identifying a token counts file
+const MAGIC: &[u8; 4] = b"WVTC";
+
+/// Current file format version
+const VERSION: u32 = 1;
+
+/// Magic bytes identifying a common component file
+const COMPONENT_MAGIC: &[u8; 4] = b"WVPC";
+
+/// Current component file format version
+const COMPONENT_VERSION: u32 = 1;
+
+/// Default SIF smoothing parameter `a`
+pub const DEFAULT_SIF_A: f64 = 1e-3;
+
+/// Power iterations for the common component
+const PC_ITERATIONS: usize = 100;
+
+/// How token vectors are pooled into a document vector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pooling {
+    /// Plain average of the token vectors
+    #[default]
+    Mean,
+    /// Frequency-weighted average, then common-component removal
+    Sif,
+}
+
+impl Pooling {
+    /// Parses a pooling name (`mean` or `sif`).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "mean" => Some(Self::Mean),
+            "sif" => Some(Self::Sif),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Pooling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mean => write!(f, "mean"),
+            Self::Sif => write!(f, "SIF"),
+        }
+    }
+}
+
+/// SIF weight `a / (a + p(t))` of every token, from per-token counts.
+pub fn sif_weights(counts: &[u64], a: f64) -> Vec<f32> {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return vec![1.0; counts.len()];
+    }
+    counts
+        .iter()
+        .map(|&c| (a / (a + c as f64 / total as f64)) as f32)
+        .collect()
+}
+
+/// Averages the vectors of `ids`, each scaled by its weight if `weights`
+/// is given. Tokens without a weight count fully. An empty document gets
+/// a zero vector.
+pub fn pool<E>(
+    ids: &[BpeTokenId],
+    dim: usize,
+    weights: Option<&[f32]>,
+    mut embedding: impl FnMut(BpeTokenId, &mut [f32]) -> Result<(), E>,
+) -> Result<Vec<f32>, E> {
+    let mut sum = vec![0.0f32; dim];
+    let mut vec = vec![0.0f32; dim];
+    for &id in ids {
+        embedding(id, &mut vec)?;
+        let w = weights
+            .and_then(|w| w.get(id as usize))
+            .copied()
+            .unwrap_or(1.0);
+        for (s, v) in sum.iter_mut().zip(&vec) {
+            *s += w * v;
+        }
+    }
+
+    if !ids.is_empty() {
+        let scale = 1.0 / ids.len() as f32;
+        sum.iter_mut().for_each(|s| *s *= scale);
+    }
+    Ok(sum)
+}
+
+/// Returns the first principal component (unit length) of `vectors`,
+/// uncentered as in SIF, or None if all vectors are zero.
+///
+/// Power iteration on `XᵀX`, started from the sum of the vectors, so the
+/// result is deterministic.
+pub fn first_component(vectors: &[Vec<f32>], dim: usize) -> Option<Vec<f32>> {
+    let mut u = vec![0.0f64; dim];
+    for v in vectors {
+        for (u, &x) in u.iter_mut().zip(v) {
+            *u += x as f64;
+        }
+    }
+    if u.iter().all(|&x| x == 0.0) {
+        // Sum cancels out: start from the largest vector instead
+        let largest = vectors.iter().max_by(|a, b| norm(a).total_cmp(&norm(b)))?;
+        u = largest.iter().map(|&x| x as f64).collect();
+    }
+    normalize(&mut u)?;
+
+    for _ in 0..PC_ITERATIONS {
+        let mut next = vec![0.0f64; dim];
+        for v in vectors {
+            let proj: f64 = v.iter().zip(&u).map(|(&x, &u)| x as f64 * u).sum();
+            for (n, &x) in next.iter_mut().zip(v) {
+                *n += proj * x as f64;
+            }
+        }
+        normalize(&mut next)?;
+        let converged = next.iter().zip(&u).all(|(a, b)| (a - b).abs() < 1e-9);
+        u = next;
+        if converged {
+            break;
+        }
+    }
+
+    Some(u.into_iter().map(|x| x as f32).collect())
+}
+
+/// Subtracts from each vector its projection onto unit vector `u`.
+pub fn remove_component(vectors: &mut [Vec<f32>], u: &[f32]) {
+    for v in vectors {
+        let proj: f32 = v.iter().zip(u).map(|(x, u)| x * u).sum();
+        for (x, u) in v.iter_mut().zip(u) {
+            *x -= proj * u;
+        }
+    }
+}
+
+fn norm(v: &[f32]) -> f32 {
+    v.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
+/// Scales `v` to unit length; None if it is zero.
+fn normalize(v: &mut [f64]) -> Option<()> {
+    let n = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if n == 0.0 {
+        return None;
+    }
+    v.iter_mut().for_each(|x| *x /= n);
+    Some(())
+}
+
+/// Saves per-token counts to a binary file.
+pub fn save_counts(counts: &[u64], path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(counts.len() as u32).to_le_bytes())?;
+    for &c in counts {
+        writer.write_all(&c.to_le_bytes())?;
+    }
+
+    writer.flush()
+}
+
+/// Loads per-token counts from a binary file.
+pub fn load_counts(path: &Path) -> io::Result<Vec<u64>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("invalid magic bytes".to_string()));
+    }
+
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    let version = u32::from_le_bytes(buf);
+    if version != VERSION {
+        return Err(invalid(format!("unsupported version: {}", version)));
+    }
+
+    reader.read_exact(&mut buf)?;
+    let n = u32::from_le_bytes(buf) as usize;
+    let mut counts = Vec::new();
+    let mut buf = [0u8; 8];
+    for _ in 0..n {
+        reader.read_exact(&mut buf)?;
+        counts.push(u64::from_le_bytes(buf));
+    }
+
+    Ok(counts)
+}
+
+/// Saves a common component to a binary file.
+pub fn save_component(u: &[f32], path: &Path) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writer.write_all(COMPONENT_MAGIC)?;
+    writer.write_all(&COMPONENT_VERSION.to_le_bytes())?;
+    writer.write_all(&(u.len() as u32).to_le_bytes())?;
+    for &x in u {
+        writer.write_all(&x.to_le_bytes())?;
+    }
+
+    writer.flush()
+}
+
+/// Loads a common component from a binary file.
+pub fn load_component(path: &Path) -> io::Result<Vec<f32>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != COMPONENT_MAGIC {
+        return Err(invalid("invalid magic bytes".to_string()));
+    }
+
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    let version = u32::from_le_bytes(buf);
+    if version != COMPONENT_VERSION {
+        return Err(invalid(format!("unsupported version: {}", version)));
+    }
+
+    reader.read_exact(&mut buf)?;
+    let dim = u32::from_le_bytes(buf) as usize;
+    let mut u = Vec::new();
+    for _ in 0..dim {
+        reader.read_exact(&mut buf)?;
+        u.push(f32::from_le_bytes(buf));
+    }
+
+    Ok(u)
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(table: &[[f32; 2]]) -> impl FnMut(BpeTokenId, &mut [f32]) -> Result<(), ()> + '_ {
+        |id, out| {
+            out.copy_from_slice(&table[id as usize]);
+            Ok(())
+        }
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert!(
+            a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-6),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_pool_mean_and_weighted() {
+        let table = [[1.0, 0.0], [0.0, 1.0], [3.0, 3.0]];
+
+        let mean = pool(&[0, 1, 1], 2, None, lookup(&table)).unwrap();
+        assert_close(&mean, &[1.0 / 3.0, 2.0 / 3.0]);
+
+        let weights = [1.0, 0.5];
+        let weighted = pool(&[0, 1, 2], 2, Some(&weights), lookup(&table)).unwrap();
+        assert_close(&weighted, &[4.0 / 3.0, 3.5 / 3.0]);
+
+        assert_eq!(pool(&[], 2, None, lookup(&table)).unwrap(), vec![0.0; 2]);
+    }
+
+    #[test]
+    fn test_sif_weights() {
+        let w = sif_weights(&[0, 1, 999], 1e-3);
+        assert_eq!(w[0], 1.0);
+        assert!((w[1] - 0.5).abs() < 1e-6);
+        assert!(w[2] < 0.01);
+        assert_eq!(sif_weights(&[0, 0], 1e-3), vec![1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_remove_common_component() {
+        // All vectors share a large component along (1, 1)/√2
+        let mut vectors = vec![vec![5.0, 5.0], vec![6.0, 4.0], vec![4.0, 6.0]];
+        let u = first_component(&vectors, 2).unwrap();
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((u[0] - s).abs() < 1e-4 && (u[1] - s).abs() < 1e-4);
+
+        remove_component(&mut vectors, &u);
+        for v in &vectors {
+            assert!((v[0] + v[1]).abs() < 1e-4);
+        }
+        assert!(first_component(&[vec![0.0, 0.0]], 2).is_none());
+    }
+
+    #[test]
+    fn test_saved_component_pools_queries_like_documents() {
+        let table = [[5.0, 5.0], [6.0, 4.0], [4.0, 6.0], [7.0, 5.0]];
+        let documents: [&[BpeTokenId]; 3] = [&[0, 1], &[2], &[1, 3]];
+        let weights = [1.0, 0.5, 0.8, 0.2];
+        let pool_one = |ids: &[BpeTokenId]| pool(ids, 2, Some(&weights), lookup(&table)).unwrap();
+
+        // The component of a batch of documents, saved with them
+        let mut batch: Vec<Vec<f32>> = documents.iter().map(|ids| pool_one(ids)).collect();
+        let u = first_component(&batch, 2).unwrap();
+        remove_component(&mut batch, &u);
+        let path = std::env::temp_dir().join("wvec_test_component.bin");
+        save_component(&u, &path).unwrap();
+
+        // A query pooled on its own ends up where it would in the batch
+        let loaded = load_component(&path).unwrap();
+        assert_eq!(loaded, u);
+        let mut alone = vec![pool_one(documents[2])];
+        remove_component(&mut alone, &loaded);
+        assert_close(&alone[0], &batch[2]);
+
+        std::fs::write(&path, b"WVTC").unwrap();
+        assert!(load_component(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_counts_roundtrip() {
+        let path = std::env::temp_dir().join("wvec_test_counts.bin");
+        save_counts(&[3, 0, u64::MAX], &path).unwrap();
+        assert_eq!(load_counts(&path).unwrap(), vec![3, 0, u64::MAX]);
+
+        std::fs::write(&path, b"XXXX").unwrap();
+        assert!(load_counts(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,319 @@
+//! Minimal HTTP/1.1 requests and responses
+//!
+//! Reads one request per connection (request line, headers and a
+//! `Content-Length` body) and writes one JSON response, then the server
+//! closes the connection.
+
+use super::json::{self, Value};
+use std::io::{self, BufRead, Read, Write};
+
+/// Limit on the request line and headers together
+const MAX_HEAD: u64 = 16 * 1024;
+
+/// Limit on the request body
+const MAX_BODY: usize = 1024 * 1024;
+
+/// A parsed request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    /// Query string parameters, then the fields of a JSON object body
+    pub params: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Returns the first value of a parameter.
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// An error answered with a status code and a JSON message
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl HttpError {
+    pub fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(400, message)
+    }
+}
+
+impl From<io::Error> for HttpError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            // A socket read timeout is WouldBlock on Unix, TimedOut on Windows
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+                Self::new(408, "Request timed out")
+            }
+            _ => Self::bad_request(format!("Cannot read request: {}", e)),
+        }
+    }
+}
+
+/// Reads a request from a connection.
+pub fn read_request(reader: &mut impl BufRead) -> Result<Request, HttpError> {
+    let mut head = Read::take(&mut *reader, MAX_HEAD);
+
+    let request_line = read_head_line(&mut head)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_string(), target)
+        }
+        _ => return Err(HttpError::bad_request("Malformed request line")),
+    };
+
+    let mut content_length = 0;
+    let mut json_body = false;
+    loop {
+        let line = read_head_line(&mut head)?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| HttpError::bad_request("Malformed header"))?;
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => {
+                content_length = value
+                    .parse()
+                    .map_err(|_| HttpError::bad_request("Invalid Content-Length"))?;
+            }
+            "content-type" => json_body = value.starts_with("application/json"),
+            "transfer-encoding" => {
+                return Err(HttpError::new(411, "Send the body with a Content-Length"));
+            }
+            _ => {}
+        }
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut params = parse_query(query)?;
+
+    if content_length > MAX_BODY {
+        return Err(HttpError::new(413, "Request body too large"));
+    }
+    if content_length > 0 {
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body)?;
+        let body =
+            String::from_utf8(body).map_err(|_| HttpError::bad_request("Body is not UTF-8"))?;
+        if json_body || body.trim_start().starts_with('{') {
+            params.extend(parse_json_params(&body)?);
+        } else {
+            params.extend(parse_query(body.trim_end())?);
+        }
+    }
+
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        params,
+    })
+}
+
+/// Reads one header line without its line ending. Running into the
+/// header limit is an error.
+fn read_head_line(head: &mut impl BufRead) -> Result<String, HttpError> {
+    let mut line = String::new();
+    let n = head.read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(if n == 0 {
+            HttpError::bad_request("Incomplete request")
+        } else {
+            HttpError::new(431, "Request headers too large")
+        });
+    }
+    line.truncate(line.trim_end_matches(['\r', '\n']).len());
+    Ok(line)
+}
+
+/// Parses `a=1&b=x+y` form encoding.
+fn parse_query(query: &str) -> Result<Vec<(String, String)>, HttpError> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((percent_decode(key)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+/// Decodes `%XX` escapes and `+` (a space).
+fn percent_decode(text: &str) -> Result<String, HttpError> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' => {
+                let byte = bytes
+                    .get(i + 1..i + 3)
+                    .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| HttpError::bad_request("Invalid percent escape"))?;
+                out.push(byte);
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8(out).map_err(|_| HttpError::bad_request("Parameter is not UTF-8"))
+}
+
+/// Takes the fields of a JSON object body as parameters. Strings are
+/// taken as they are and numbers and booleans in their JSON form.
+fn parse_json_params(body: &str) -> Result<Vec<(String, String)>, HttpError> {
+    let pairs = match json::parse(body).map_err(HttpError::bad_request)? {
+        Value::Object(pairs) => pairs,
+        _ => return Err(HttpError::bad_request("Body must be a JSON object")),
+    };
+    pairs
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::String(s) => Ok((key, s)),
+            Value::Number(_) | Value::Bool(_) => Ok((key, value.to_string())),
+            _ => Err(HttpError::bad_request(format!(
+                "Field '{}' must be a string or a number",
+                key
+            ))),
+        })
+        .collect()
+}
+
+/// Writes a complete JSON response.
+pub fn write_response(writer: &mut impl Write, status: u16, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(raw: &str) -> Result<Request, HttpError> {
+        read_request(&mut raw.as_bytes())
+    }
+
+    #[test]
+    fn test_read_request() {
+        let request =
+            read("GET /similar?word=caf%C3%A9&k=5&text=a+b HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/similar");
+        assert_eq!(request.param("word"), Some("café"));
+        assert_eq!(request.param("k"), Some("5"));
+        assert_eq!(request.param("text"), Some("a b"));
+        assert_eq!(request.param("missing"), None);
+
+        let body = r#"{"query": "king - man + woman", "k": 3}"#;
+        let raw = format!(
+            "POST /analogy HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let request = read(&raw).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.param("query"), Some("king - man + woman"));
+        assert_eq!(request.param("k"), Some("3"));
+
+        let raw = "POST /embed HTTP/1.1\r\nContent-Length: 8\r\n\r\nword=low";
+        assert_eq!(read(raw).unwrap().param("word"), Some("low"));
+    }
+
+    #[test]
+    fn test_read_request_errors() {
+        let status = |raw: &str| read(raw).unwrap_err().status;
+        assert_eq!(status(""), 400);
+        assert_eq!(status("GET /\r\n\r\n"), 400);
+        assert_eq!(status("GET / HTTP/1.1\r\nHost x\r\n\r\n"), 400);
+        assert_eq!(status("GET /?w=%zz HTTP/1.1\r\n\r\n"), 400);
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nContent-Length: 9\r\n\r\n[1, 2]"),
+            400
+        );
+        assert_eq!(
+            status(
+                "POST / HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 6\r\n\r\n[1, 2]"
+            ),
+            400
+        );
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n"),
+            413
+        );
+        assert_eq!(
+            status("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            411
+        );
+        let long = format!("GET /?w={} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEAD as usize));
+        assert_eq!(status(&long), 431);
+
+        struct Stalled;
+        impl Read for Stalled {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        }
+        let e = read_request(&mut io::BufReader::new(Stalled)).unwrap_err();
+        assert_eq!(e.status, 408);
+    }
+
+    #[test]
+    fn test_write_response() {
+        let mut out = Vec::new();
+        write_response(&mut out, 404, &Value::object([("error", "nope".into())])).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\n\
+             Content-Length: 16\r\nConnection: close\r\n\r\n{\"error\":\"nope\"}"
+        );
+    }
+}
@@ -0,0 +1,533 @@
+//! HTTP query server
+//!
+//! Answers embedding, similarity, analogy and tokenization queries as
+//! JSON over HTTP. The model is copied out of Fortran once at startup;
+//! requests then only read the Rust copy, so a pool of worker threads can
+//! share it without locking.
+
+pub mod http;
+pub mod json;
+
+use crate::bpe::{BpeTokenId, UNK_ID, Vocabulary, encode};
+use crate::cmd::analogy::{combine_terms, parse_query, resolve_terms};
+use crate::compose::{Compose, compose, query_tokens};
+use crate::cosine::{self, Scored, dot, normalize_rows};
+use crate::hnsw::Hnsw;
+use crate::text::normalize::normalize;
+use crate::text::pretokenize::pretokenize;
+use http::{HttpError, Request, read_request, write_response};
+use json::Value;
+use std::convert::Infallible;
+use std::io::{self, BufReader, Read};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of results when a query does not ask for `k`
+pub const DEFAULT_K: usize = 10;
+
+/// Largest `k` a query may ask for
+pub const MAX_K: usize = 1000;
+
+/// How long a connection may take to send its whole request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How neighbours are found
+enum Search {
+    /// Scan of all unit-length word vectors
+    Exact(Vec<f32>),
+    /// HNSW index and its search width
+    Index(Hnsw, usize),
+}
+
+/// A model held in memory for answering queries
+pub struct QueryModel {
+    vocab: Vocabulary,
+    dim: usize,
+    /// Word vectors as trained, `dim` floats per token ID
+    vectors: Vec<f32>,
+    search: Search,
+    method: Compose,
+}
+
+impl QueryModel {
+    /// Wraps the word vectors of a model (`dim` floats per token of
+    /// `vocab`), searched exactly.
+    pub fn new(vocab: Vocabulary, vectors: Vec<f32>, dim: usize, method: Compose) -> Self {
+        assert!(dim > 0 && vectors.len().is_multiple_of(dim));
+        let mut normalized = vectors.clone();
+        normalize_rows(&mut normalized, dim);
+        Self {
+            vocab,
+            dim,
+            vectors,
+            search: Search::Exact(normalized),
+            method,
+        }
+    }
+
+    /// Searches with an HNSW index over the same vectors instead.
+    pub fn with_index(mut self, index: Hnsw, ef: usize) -> Self {
+        self.search = Search::Index(index, ef);
+        self
+    }
+
+    /// Number of tokens with a vector
+    pub fn vocab_size(&self) -> usize {
+        self.vectors.len() / self.dim
+    }
+
+    /// Vector dimension
+    pub fn dim(&self) -> usize {
+        self.dim
+    }
+
+    /// True if neighbours come from an HNSW index
+    pub fn has_index(&self) -> bool {
+        matches!(self.search, Search::Index(..))
+    }
+
+    /// Answers a request with a status code and a JSON body.
+    pub fn respond(&self, request: &Request) -> (u16, Value) {
+        match self.route(request) {
+            Ok(body) => (200, body),
+            Err(e) => (e.status, Value::object([("error", e.message.into())])),
+        }
+    }
+
+    fn route(&self, request: &Request) -> Result<Value, HttpError> {
+        let known = matches!(
+            request.path.as_str(),
+            "/health" | "/embed" | "/similar" | "/analogy" | "/tokenize"
+        );
+        if !known {
+            return Err(HttpError::new(
+                404,
+                format!("No such endpoint: {}", request.path),
+            ));
+        }
+        if request.method != "GET" && request.method != "POST" {
+            return Err(HttpError::new(405, "Use GET or POST"));
+        }
+
+        match request.path.as_str() {
+            "/health" => Ok(Value::object([
+                ("status", "ok".into()),
+                ("vocab_size", self.vocab_size().into()),
+                ("dim", self.dim.into()),
+                ("index", self.has_index().into()),
+            ])),
+            "/embed" => self.embed(request),
+            "/similar" => self.similar(request),
+            "/analogy" => self.analogy(request),
+            _ => self.tokenize(request),
+        }
+    }
+
+    /// `/embed?word=`: the vector of a word
+    fn embed(&self, request: &Request) -> Result<Value, HttpError> {
+        let word = required(request, "word")?;
+        let ids = self.tokens(word)?;
+        Ok(Value::object([
+            ("word", word.into()),
+            ("tokens", self.token_texts(&ids).into()),
+            ("vector", self.word_vector(&ids).into()),
+        ]))
+    }
+
+    /// `/similar?word=&k=`: nearest neighbours of a word
+    fn similar(&self, request: &Request) -> Result<Value, HttpError> {
+        let word = required(request, "word")?;
+        let k = top_k(request)?;
+        let ids = self.tokens(word)?;
+        let results = self.nearest(&self.word_vector(&ids), k, &ids);
+        Ok(Value::object([
+            ("word", word.into()),
+            ("tokens", self.token_texts(&ids).into()),
+            ("results", self.results(&results)),
+        ]))
+    }
+
+    /// `/analogy?query=&k=`: solve an expression such as `king - man + woman`
+    fn analogy(&self, request: &Request) -> Result<Value, HttpError> {
+        let expr = required(request, "query")?;
+        let k = top_k(request)?;
+        let terms = parse_query(expr).map_err(|e| HttpError::new(400, e))?;
+        if terms.is_empty() {
+            return Err(HttpError::new(400, "Empty query"));
+        }
+        let terms = resolve_terms(&self.vocab, terms).map_err(|e| HttpError::new(404, e))?;
+        let (vector, exclude) = combine_terms(&terms, self.dim, |_, ids| {
+            Ok::<_, Infallible>(self.word_vector(ids))
+        })
+        .unwrap_or_else(|never| match never {});
+
+        let results = self.nearest(&vector, k, &exclude);
+        Ok(Value::object([
+            ("query", expr.into()),
+            ("results", self.results(&results)),
+        ]))
+    }
+
+    /// `/tokenize?text=`: the BPE tokens of a text
+    fn tokenize(&self, request: &Request) -> Result<Value, HttpError> {
+        let text = required(request, "text")?;
+        let mut tokens = Vec::new();
+        for pt in pretokenize(&normalize(text)) {
+            for id in encode(
+                &self.vocab,
+                &pt.into_bpe_text(self.vocab.has_word_boundaries()),
+            ) {
+                let token = match id {
+                    UNK_ID => "[UNK]".to_string(),
+                    _ => self.vocab.get_text(id).unwrap_or_default(),
+                };
+                tokens.push(Value::object([("id", id.into()), ("token", token.into())]));
+            }
+        }
+        Ok(Value::object([("tokens", Value::Array(tokens))]))
+    }
+
+    fn tokens(&self, word: &str) -> Result<Vec<BpeTokenId>, HttpError> {
+        let ids = query_tokens(&self.vocab, word);
+        if ids.is_empty() {
+            return Err(HttpError::new(
+                404,
+                format!("Word '{}' not in vocabulary", word),
+            ));
+        }
+        Ok(ids)
+    }
+
+    fn token_texts(&self, ids: &[BpeTokenId]) -> Vec<String> {
+        ids.iter()
+            .map(|&id| self.vocab.get_text(id).unwrap_or_default())
+            .collect()
+    }
+
+    fn word_vector(&self, ids: &[BpeTokenId]) -> Vec<f32> {
+        let composed = compose(ids, self.dim, self.method, |id, out| {
+            let start = id as usize * self.dim;
+            out.copy_from_slice(&self.vectors[start..start + self.dim]);
+            Ok::<_, Infallible>(())
+        });
+        match composed {
+            Ok(vector) => vector,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Finds the `k` words most similar (cosine) to `query`, leaving out
+    /// `exclude`.
+    fn nearest(&self, query: &[f32], k: usize, exclude: &[u32]) -> Vec<(u32, f32)> {
+        let normalized = match &self.search {
+            Search::Index(index, ef) => return index.search(query, k, *ef, exclude),
+            Search::Exact(normalized) => normalized,
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut query = query.to_vec();
+        normalize_rows(&mut query, self.dim);
+
+        let candidates = normalized
+            .chunks(self.dim)
+            .enumerate()
+            .map(|(id, row)| Scored {
+                sim: dot(&query, row),
+                id: id as u32,
+            })
+            .filter(|hit| !exclude.contains(&hit.id));
+        cosine::top_k(candidates, k)
+            .into_iter()
+            .map(|hit| (hit.id, hit.sim))
+            .collect()
+    }
+
+    fn results(&self, neighbours: &[(u32, f32)]) -> Value {
+        Value::Array(
+            neighbours
+                .iter()
+                .map(|&(id, sim)| {
+                    Value::object([
+                        ("id", id.into()),
+                        ("token", self.vocab.get_text(id).unwrap_or_default().into()),
+                        ("similarity", sim.into()),
+                    ])
+                })
+                .collect(),
+        )
+    }
+}
+
+fn required<'a>(request: &'a Request, key: &str) -> Result<&'a str, HttpError> {
+    request
+        .param(key)
+        .filter(|v| !v.trim().is_empty())
+        .ok_or_else(|| HttpError::new(400, format!("Missing parameter '{}'", key)))
+}
+
+fn top_k(request: &Request) -> Result<usize, HttpError> {
+    match request.param("k") {
+        None => Ok(DEFAULT_K),
+        Some(k) => match k.parse() {
+            Ok(k) if (1..=MAX_K).contains(&k) => Ok(k),
+            _ => Err(HttpError::new(
+                400,
+                format!("Parameter 'k' must be between 1 and {}", MAX_K),
+            )),
+        },
+    }
+}
+
+/// Accepts connections on `listener` and answers them on `threads`
+/// worker threads. Runs until the listener fails.
+pub fn serve(listener: TcpListener, model: Arc<QueryModel>, threads: usize) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel::<TcpStream>();
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    let workers: Vec<_> = (0..threads.max(1))
+        .map(|_| {
+            let receiver = Arc::clone(&receiver);
+            let model = Arc::clone(&model);
+            thread::spawn(move || {
+                loop {
+                    // The lock is released before the connection is handled
+                    let next = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => break,
+                    };
+                    match next {
+                        Ok(stream) => handle(&model, stream, REQUEST_TIMEOUT),
+                        Err(_) => break,
+                    }
+                }
+            })
+        })
+        .collect();
+
+    let mut result = Ok(());
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if sender.send(stream).is_err() {
+                    break;
+                }
+            }
+            // A connection that failed before it was accepted
+            Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => {}
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+
+    drop(sender);
+    for worker in workers {
+        let _ = worker.join();
+    }
+    result
+}
+
+/// Answers one request on a connection, then closes it. A request not
+/// read in full within `timeout` is answered with 408.
+fn handle(model: &QueryModel, stream: TcpStream, timeout: Duration) {
+    let _ = stream.set_write_timeout(Some(timeout));
+
+    let mut reader = BufReader::new(DeadlineReader {
+        stream: &stream,
+        deadline: Instant::now() + timeout,
+    });
+    let (status, body) = match read_request(&mut reader) {
+        Ok(request) => model.respond(&request),
+        Err(e) => (e.status, Value::object([("error", e.message.into())])),
+    };
+    // The client may already be gone; there is no one to report that to
+    let _ = write_response(&mut &stream, status, &body);
+}
+
+/// Reads a connection until a deadline. A socket read timeout alone
+/// restarts with every read, so a client sending a byte at a time could
+/// hold a worker forever.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        (&mut &*self.stream).read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::bpe::train;
+    use std::io::{Read, Write};
+    use std::net::SocketAddr;
+
+    const DIM: usize = 4;
+
+    fn test_model() -> QueryModel {
+        let words = ["low", "lower", "lowest", "newer", "wider", "new", "low"];
+        let vocab = train(words.into_iter(), 30);
+        let vectors: Vec<f32> = (0..vocab.len() * DIM)
+            .map(|i| ((i * 7919) % 13) as f32 - 6.0)
+            .collect();
+        QueryModel::new(vocab, vectors, DIM, Compose::Mean)
+    }
+
+    fn request(addr: SocketAddr, raw: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        (status, json::parse(body).unwrap())
+    }
+
+    fn get(addr: SocketAddr, target: &str) -> (u16, Value) {
+        request(
+            addr,
+            &format!("GET {} HTTP/1.1\r\nHost: test\r\n\r\n", target),
+        )
+    }
+
+    #[test]
+    fn test_exact_nearest() {
+        let model = test_model();
+        let query = &model.vectors[..DIM];
+
+        let results = model.nearest(query, 3, &[]);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, 0);
+        assert!((results[0].1 - 1.0).abs() < 1e-5);
+        assert!(results.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        // Same answer as a full sort
+        let mut all: Vec<(u32, f32)> = (1..model.vocab_size() as u32)
+            .map(|id| {
+                let row = &model.vectors[id as usize * DIM..(id as usize + 1) * DIM];
+                let sim = dot(query, row) / (dot(query, query) * dot(row, row)).sqrt();
+                (id, sim)
+            })
+            .collect();
+        all.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        let excluded = model.nearest(query, 5, &[0]);
+        let expected: Vec<u32> = all.iter().take(5).map(|&(id, _)| id).collect();
+        let ids: Vec<u32> = excluded.iter().map(|&(id, _)| id).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_serve_loopback() {
+        let model = Arc::new(test_model());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, model, 2));
+
+        let (status, body) = get(addr, "/health");
+        assert_eq!(status, 200);
+        assert_eq!(body.get("status"), Some(&Value::from("ok")));
+        assert_eq!(body.get("dim"), Some(&Value::from(DIM)));
+        assert_eq!(body.get("index"), Some(&Value::from(false)));
+
+        let (status, body) = get(addr, "/embed?word=lowest");
+        assert_eq!(status, 200);
+        match body.get("vector") {
+            Some(Value::Array(vector)) => assert_eq!(vector.len(), DIM),
+            other => panic!("no vector: {:?}", other),
+        }
+
+        let (status, body) = get(addr, "/similar?word=low&k=3");
+        assert_eq!(status, 200);
+        match body.get("results") {
+            Some(Value::Array(results)) => {
+                assert_eq!(results.len(), 3);
+                assert!(
+                    results
+                        .iter()
+                        .all(|r| r.get("token") != Some(&"low".into()))
+                );
+                assert!(results.iter().all(|r| r.get("similarity").is_some()));
+            }
+            other => panic!("no results: {:?}", other),
+        }
+
+        let body = r#"{"query": "lower - low + new", "k": 2}"#;
+        let (status, response) = request(
+            addr,
+            &format!(
+                "POST /analogy HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            ),
+        );
+        assert_eq!(status, 200);
+        match response.get("results") {
+            Some(Value::Array(results)) => assert_eq!(results.len(), 2),
+            other => panic!("no results: {:?}", other),
+        }
+
+        let (status, body) = get(addr, "/tokenize?text=lower+newer");
+        assert_eq!(status, 200);
+        assert!(matches!(body.get("tokens"), Some(Value::Array(t)) if !t.is_empty()));
+
+        // Errors come back as JSON with a status
+        assert_eq!(get(addr, "/similar?word=zzz").0, 404);
+        assert_eq!(get(addr, "/similar").0, 400);
+        assert_eq!(get(addr, "/similar?word=low&k=0").0, 400);
+        assert_eq!(get(addr, "/analogy?query=-").0, 400);
+        assert_eq!(get(addr, "/nope").0, 404);
+        let (status, body) = request(addr, "DELETE /embed HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 405);
+        assert!(body.get("error").is_some());
+        assert_eq!(request(addr, "garbage\r\n\r\n").0, 400);
+    }
+
+    #[test]
+    fn test_request_deadline() {
+        let model = test_model();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle(&model, stream, Duration::from_millis(300));
+        });
+
+        // A client trickling its headers a byte at a time never trips a
+        // per-read timeout, but runs out the request deadline
+        let start = Instant::now();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let mut writer = stream.try_clone().unwrap();
+        thread::spawn(move || {
+            let _ = writer.write_all(b"GET /health HTTP/1.1\r\n");
+            for _ in 0..40 {
+                thread::sleep(Duration::from_millis(50));
+                if writer.write_all(b"x").is_err() {
+                    break;
+                }
+            }
+        });
+        // The late bytes may reset the connection after the response
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        server.join().unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+        assert!(start.elapsed() < Duration::from_millis(1500));
+    }
+}
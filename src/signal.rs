@@ -0,0 +1,84 @@
+//! Signal handling for graceful shutdown
+//!
+//! Hooks SIGINT (Ctrl-C) and SIGTERM using the C library's `signal()`,
+//! which std already links against. The handler only sets the Fortran
+//! shutdown flag; the training loop notices it, stops early, and the
+//! caller saves a checkpoint.
+//!
+//! The first signal restores the default disposition, so pressing Ctrl-C
+//! a second time terminates the process immediately.
+
+use std::ffi::c_int;
+
+use crate::ffi::wvec_shutdown_request;
+
+/// Interrupt from keyboard (Ctrl-C)
+pub const SIGINT: c_int = 2;
+
+/// Termination request (e.g. `kill`, systemd stop)
+pub const SIGTERM: c_int = 15;
+
+/// Default signal disposition (`SIG_DFL`)
+const SIG_DFL: usize = 0;
+
+/// Error return value of `signal()` (`SIG_ERR`)
+const SIG_ERR: usize = usize::MAX;
+
+unsafe extern "C" {
+    fn signal(signum: c_int, handler: usize) -> usize;
+}
+
+/// Signal handler: requests shutdown and re-arms the default behavior.
+///
+/// Only async-signal-safe work happens here: a store to the Fortran flag
+/// and a call to `signal()`.
+extern "C" fn handle_shutdown(signum: c_int) {
+    unsafe {
+        wvec_shutdown_request();
+        signal(signum, SIG_DFL);
+    }
+}
+
+/// Installs the graceful shutdown handler for SIGINT and SIGTERM.
+pub fn install_shutdown_handler() -> Result<(), String> {
+    let handler = handle_shutdown as extern "C" fn(c_int) as usize;
+    for signum in [SIGINT, SIGTERM] {
+        if unsafe { signal(signum, handler) } == SIG_ERR {
+            return Err(format!("Cannot install handler for signal {}", signum));
+        }
+    }
+    Ok(())
+}
+
+/// Restores the default disposition for SIGINT and SIGTERM.
+pub fn restore_default_handlers() {
+    for signum in [SIGINT, SIGTERM] {
+        unsafe {
+            signal(signum, SIG_DFL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::{wvec_shutdown_check, wvec_shutdown_reset};
+
+    unsafe extern "C" {
+        fn raise(signum: c_int) -> c_int;
+    }
+
+    #[test]
+    fn test_sigterm_requests_shutdown() {
+        unsafe {
+            wvec_shutdown_reset();
+            install_shutdown_handler().unwrap();
+
+            assert_eq!(raise(SIGTERM), 0);
+            assert_eq!(wvec_shutdown_check(), 1);
+
+            restore_default_handlers();
+            wvec_shutdown_reset();
+        }
+    }
+}
@@ -10,6 +10,21 @@ use crate::text::normalize::is_cjk;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PreToken {
     pub text: String,
+    /// Whitespace came right before this pre-token
+    pub space_before: bool,
+}
+
+impl PreToken {
+    /// Returns the text to encode with BPE. Vocabularies with word
+    /// boundaries see a leading space on pre-tokens that follow whitespace,
+    /// so decoding restores the spaces between words.
+    pub fn into_bpe_text(self, word_boundaries: bool) -> String {
+        if word_boundaries && self.space_before {
+            format!(" {}", self.text)
+        } else {
+            self.text
+        }
+    }
 }
 
 /// Pre-tokenizes text based on language characteristics.
@@ -20,6 +35,7 @@ pub fn pretokenize(text: &str) -> Vec<PreToken> {
     let estimated_tokens = text.len() / 4 + 1;
     let mut tokens = Vec::with_capacity(estimated_tokens);
     let mut current = String::new();
+    let mut space_before = false;
 
     for ch in text.chars() {
         let ch_is_cjk = is_cjk(ch);
@@ -27,27 +43,26 @@ pub fn pretokenize(text: &str) -> Vec<PreToken> {
         // CJK characters: each is its own token
         if ch_is_cjk {
             // Flush any accumulated Latin text
-            flush_token(&mut tokens, &mut current);
+            flush_token(&mut tokens, &mut current, &mut space_before);
 
             // Add CJK char as its own token (reuse a small buffer)
             let mut s = String::with_capacity(4); // Max 4 bytes for UTF-8 char
             s.push(ch);
-            tokens.push(PreToken { text: s });
+            push_token(&mut tokens, s, &mut space_before);
             continue;
         }
 
         // Whitespace: flush current token
         if ch.is_whitespace() {
-            flush_token(&mut tokens, &mut current);
+            flush_token(&mut tokens, &mut current, &mut space_before);
+            space_before = true;
             continue;
         }
 
         // Punctuation: separate token (unless it's an apostrophe in a word)
         if ch.is_ascii_punctuation() && ch != '\'' {
-            flush_token(&mut tokens, &mut current);
-            tokens.push(PreToken {
-                text: ch.to_string(),
-            });
+            flush_token(&mut tokens, &mut current, &mut space_before);
+            push_token(&mut tokens, ch.to_string(), &mut space_before);
             continue;
         }
 
@@ -56,20 +71,26 @@ pub fn pretokenize(text: &str) -> Vec<PreToken> {
     }
 
     // Flush remaining
-    flush_token(&mut tokens, &mut current);
+    flush_token(&mut tokens, &mut current, &mut space_before);
 
     tokens
 }
 
 #[inline]
-fn flush_token(tokens: &mut Vec<PreToken>, current: &mut String) {
+fn flush_token(tokens: &mut Vec<PreToken>, current: &mut String, space_before: &mut bool) {
     if !current.is_empty() {
-        tokens.push(PreToken {
-            text: std::mem::take(current),
-        });
+        push_token(tokens, std::mem::take(current), space_before);
     }
 }
 
+#[inline]
+fn push_token(tokens: &mut Vec<PreToken>, text: String, space_before: &mut bool) {
+    tokens.push(PreToken {
+        text,
+        space_before: std::mem::take(space_before),
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,6 +135,22 @@ mod tests {
         assert_eq!(texts(&tokens), vec!["größe", "über"]);
     }
 
+    #[test]
+    fn test_space_before() {
+        let text = "hi, 你好 there!";
+        let marked: Vec<String> = pretokenize(text)
+            .into_iter()
+            .map(|t| t.into_bpe_text(true))
+            .collect();
+        assert_eq!(marked, vec!["hi", ",", " 你", "好", " there", "!"]);
+        assert_eq!(marked.concat(), text);
+
+        // Whitespace runs collapse into one marker
+        let tokens = pretokenize("  a \t b");
+        assert!(tokens.iter().all(|t| t.space_before));
+        assert_eq!(tokens[1].clone().into_bpe_text(false), "b");
+    }
+
     #[test]
     fn test_empty() {
         let tokens = pretokenize("");
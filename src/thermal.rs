@@ -0,0 +1,241 @@
+//! Thermal throttling for long training runs
+//!
+//! Locates a CPU temperature sensor under `/sys/class/thermal` and pauses
+//! training between chunks while it reads above a threshold. Sensor reads
+//! go through the Fortran thermal module (`wvec_thermal_*`).
+
+use std::{
+    ffi::{c_char, c_int},
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use crate::ffi::{self, wvec_shutdown_check, wvec_thermal_check, wvec_thermal_get_celsius};
+
+/// Root of the Linux thermal sysfs tree
+pub const THERMAL_ROOT: &str = "/sys/class/thermal";
+
+/// Default temperature threshold in Celsius
+pub const DEFAULT_MAX_TEMP_C: i32 = 85;
+
+/// Default pause between sensor reads while overheating, in seconds
+pub const DEFAULT_COOLDOWN_SECS: u64 = 30;
+
+/// Zone types that report the CPU package temperature, most preferred first
+const PREFERRED_ZONE_TYPES: &[&str] = &[
+    "x86_pkg_temp",
+    "k10temp",
+    "cpu-thermal",
+    "cpu_thermal",
+    "soc_thermal",
+    "acpitz",
+];
+
+/// Finds a usable thermal zone under `/sys/class/thermal`.
+///
+/// Returns the path to the zone's `temp` file.
+pub fn discover_zone() -> Option<PathBuf> {
+    discover_zone_in(Path::new(THERMAL_ROOT))
+}
+
+/// Finds a usable thermal zone under the given sysfs root.
+///
+/// A zone is usable if its `temp` file holds a positive millidegree value.
+/// Zones whose `type` names a CPU sensor win over the rest; ties go to the
+/// lowest zone number.
+pub fn discover_zone_in(root: &Path) -> Option<PathBuf> {
+    let mut zones: Vec<(usize, u32, PathBuf)> = Vec::new();
+
+    for entry in fs::read_dir(root).ok()?.flatten() {
+        let name = entry.file_name();
+        let Some(number) = name
+            .to_str()
+            .and_then(|n| n.strip_prefix("thermal_zone"))
+            .and_then(|n| n.parse::<u32>().ok())
+        else {
+            continue;
+        };
+
+        let temp_path = entry.path().join("temp");
+        let readable = fs::read_to_string(&temp_path)
+            .ok()
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .is_some_and(|mc| mc > 0);
+        if !readable {
+            continue;
+        }
+
+        let zone_type = fs::read_to_string(entry.path().join("type")).unwrap_or_default();
+        let rank = PREFERRED_ZONE_TYPES
+            .iter()
+            .position(|&t| t == zone_type.trim())
+            .unwrap_or(PREFERRED_ZONE_TYPES.len());
+
+        zones.push((rank, number, temp_path));
+    }
+
+    zones.into_iter().min().map(|(_, _, path)| path)
+}
+
+/// Pauses training while a temperature sensor is above a threshold.
+#[derive(Debug, Clone)]
+pub struct ThermalMonitor {
+    /// Path to a file holding the temperature in millidegrees Celsius
+    path: String,
+    /// Threshold in Celsius; at or above it training pauses
+    max_temp_c: i32,
+    /// Time to wait between reads while overheating
+    cooldown: Duration,
+}
+
+impl ThermalMonitor {
+    /// Creates a monitor for a sensor file or thermal zone directory.
+    pub fn new<P: AsRef<Path>>(path: P, max_temp_c: i32, cooldown: Duration) -> Self {
+        let path = path.as_ref();
+        let path = if path.is_dir() {
+            path.join("temp")
+        } else {
+            path.to_path_buf()
+        };
+
+        Self {
+            path: path.to_string_lossy().into_owned(),
+            max_temp_c,
+            cooldown,
+        }
+    }
+
+    /// Returns the sensor file path.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Reads the current temperature in Celsius.
+    pub fn read_celsius(&self) -> Result<i32, String> {
+        let mut temp_c: c_int = 0;
+        let status = unsafe {
+            wvec_thermal_get_celsius(
+                self.path.as_ptr() as *const c_char,
+                self.path.len() as c_int,
+                &mut temp_c,
+            )
+        };
+        if status != ffi::status::SUCCESS {
+            return Err(format!("Cannot read sensor {}: {}", self.path, status));
+        }
+        Ok(temp_c)
+    }
+
+    /// Returns true if the sensor is at or above the threshold.
+    pub fn is_hot(&self) -> Result<bool, String> {
+        let result = unsafe {
+            wvec_thermal_check(
+                self.path.as_ptr() as *const c_char,
+                self.path.len() as c_int,
+                self.max_temp_c,
+            )
+        };
+        if result < 0 {
+            return Err(format!("Cannot read sensor {}: {}", self.path, result));
+        }
+        Ok(result == 1)
+    }
+
+    /// Blocks until the sensor is below the threshold.
+    ///
+    /// Logs the temperature while waiting. Returns early if a graceful
+    /// shutdown was requested, so Ctrl-C is not delayed by the cooldown.
+    pub fn wait_until_cool(&self) -> Result<(), String> {
+        let mut paused = false;
+
+        while self.is_hot()? {
+            if unsafe { wvec_shutdown_check() } == 1 {
+                return Ok(());
+            }
+
+            eprintln!(
+                "  CPU at {}°C (limit {}°C), pausing {}s...",
+                self.read_celsius()?,
+                self.max_temp_c,
+                self.cooldown.as_secs()
+            );
+            paused = true;
+
+            // Sleep in short steps to stay responsive to shutdown requests
+            let step = Duration::from_secs(1).min(self.cooldown);
+            let mut waited = Duration::ZERO;
+            while waited < self.cooldown && unsafe { wvec_shutdown_check() } == 0 {
+                thread::sleep(step);
+                waited += step;
+            }
+        }
+
+        if paused {
+            eprintln!("  CPU at {}°C, resuming", self.read_celsius()?);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_zone(root: &Path, name: &str, zone_type: &str, temp: &str) {
+        let dir = root.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("type"), format!("{}\n", zone_type)).unwrap();
+        fs::write(dir.join("temp"), format!("{}\n", temp)).unwrap();
+    }
+
+    #[test]
+    fn test_discover_prefers_cpu_zone() {
+        let root = std::env::temp_dir().join("wvec_test_thermal_prefer");
+        let _ = fs::remove_dir_all(&root);
+        make_zone(&root, "thermal_zone0", "acpitz", "40000");
+        make_zone(&root, "thermal_zone3", "x86_pkg_temp", "52000");
+        make_zone(&root, "thermal_zone7", "iwlwifi_1", "38000");
+
+        let zone = discover_zone_in(&root).unwrap();
+        assert_eq!(zone, root.join("thermal_zone3").join("temp"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_discover_skips_unreadable_zone() {
+        let root = std::env::temp_dir().join("wvec_test_thermal_unreadable");
+        let _ = fs::remove_dir_all(&root);
+        make_zone(&root, "thermal_zone0", "x86_pkg_temp", "not a number");
+        make_zone(&root, "thermal_zone1", "pch_cannonlake", "45000");
+        fs::create_dir_all(root.join("cooling_device0")).unwrap();
+
+        let zone = discover_zone_in(&root).unwrap();
+        assert_eq!(zone, root.join("thermal_zone1").join("temp"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_discover_missing_root() {
+        assert!(discover_zone_in(Path::new("/nonexistent/thermal")).is_none());
+    }
+
+    #[test]
+    fn test_monitor_reads_sensor_file() {
+        let path = std::env::temp_dir().join("wvec_test_thermal_sensor");
+        fs::write(&path, "61000\n").unwrap();
+
+        let cool = ThermalMonitor::new(&path, 90, Duration::from_secs(1));
+        assert_eq!(cool.read_celsius(), Ok(61));
+        assert_eq!(cool.is_hot(), Ok(false));
+        assert!(cool.wait_until_cool().is_ok());
+
+        let hot = ThermalMonitor::new(&path, 60, Duration::from_secs(1));
+        assert_eq!(hot.is_hot(), Ok(true));
+
+        fs::remove_file(&path).unwrap();
+    }
+}